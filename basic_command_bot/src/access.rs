@@ -0,0 +1,153 @@
+/// access.rs
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use teloxide::prelude::*;
+use crate::admin::{list_roles, Role};
+use crate::audit::{admin_channel, record, AuditEntry};
+use crate::config;
+use crate::database::DbPool;
+use crate::enums::Command;
+use crate::commands::schedule_commands::format_duration;
+
+// When each user last got a reply about a refused command or an unknown message
+static LAST_REPLY: OnceLock<Mutex<HashMap<UserId, Instant>>> = OnceLock::new();
+
+// The refused commands of each user, see count_denial
+static DENIALS: OnceLock<Mutex<HashMap<UserId, Denials>>> = OnceLock::new();
+
+struct Denials {
+    // When the current alert window started and how many commands were refused in it
+    window_start: Instant,
+    in_window: i64,
+    // When a refusal last went into the audit log, and how many were left out since
+    last_recorded: Instant,
+    unrecorded: i64,
+}
+
+// Whether the user may get another reply, and if so note that they get one now
+fn may_reply(user_id: UserId) -> bool {
+    let interval = Duration::from_secs(config::get().access.reply_interval_secs as u64);
+    let now = Instant::now();
+    let mut last_reply = LAST_REPLY.get_or_init(Default::default).lock().expect("The reply times lock is poisoned");
+
+    // Forget the users whose interval is over, so the map only holds recent ones
+    last_reply.retain(|_, replied_at| now.duration_since(*replied_at) < interval);
+    if last_reply.contains_key(&user_id) {
+        return false;
+    }
+    last_reply.insert(user_id, now);
+    true
+}
+
+// Count a refused command of the user. Returns how many they were refused in the current alert window,
+// and if this refusal goes into the audit log, how many were left out before it. A user gets at most
+// one entry per `reply_interval_secs`, so spamming a command can't flood the log or the admin channel.
+fn count_denial(user_id: UserId) -> (i64, Option<i64>) {
+    let access = &config::get().access;
+    let interval = Duration::from_secs(access.reply_interval_secs as u64);
+    let window = Duration::from_secs(access.alert_window_secs as u64);
+    let now = Instant::now();
+    let mut denials = DENIALS.get_or_init(Default::default).lock().expect("The refused commands lock is poisoned");
+
+    // Forget the users who have been quiet for longer than both, unless some refusals still wait for an entry
+    denials.retain(|_, user| {
+        now.duration_since(user.window_start) < window || now.duration_since(user.last_recorded) < interval || user.unrecorded > 0
+    });
+    let user = match denials.get_mut(&user_id) {
+        Some(user) => user,
+        None => {
+            denials.insert(user_id, Denials { window_start: now, in_window: 1, last_recorded: now, unrecorded: 0 });
+            return (1, Some(0));
+        }
+    };
+
+    if now.duration_since(user.window_start) >= window {
+        user.window_start = now;
+        user.in_window = 0;
+    }
+    user.in_window += 1;
+
+    let recorded = if now.duration_since(user.last_recorded) >= interval {
+        user.last_recorded = now;
+        Some(std::mem::take(&mut user.unrecorded))
+    } else {
+        user.unrecorded += 1;
+        None
+    };
+    (user.in_window, recorded)
+}
+
+// A command the sender's role doesn't allow, see Command::min_role. Refusals go into the audit log
+// at most once per `reply_interval_secs` per user, with the count of those left out in between.
+// The reply and the alert depend on `[access]`.
+pub async fn denied_command(bot: Bot, msg: Message, cmd: Command, db_pool: &DbPool) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let user = match msg.from() {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+    let required = cmd.min_role();
+    let (attempts, recorded) = count_denial(user.id);
+    let result = match recorded {
+        Some(0) | None => format!("denied: needs {}", required),
+        Some(left_out) => format!("denied: needs {}, {} more refused since the last entry", required, left_out),
+    };
+    let entry = AuditEntry::from_command(&msg, &cmd, result);
+    if recorded.is_some() {
+        record(&bot, db_pool, &entry).await?;
+    }
+    log::warn!("Refused {} to user {}", entry.command, user.id);
+
+    let access = &config::get().access;
+    if access.reply_denied && may_reply(user.id) {
+        bot.send_message(msg.chat.id, format!("🚫 {} needs the {} role. Ask an admin if you think you should have it.", entry.command, required)).await?;
+    }
+
+    // Only once per run of attempts, when the count reaches the threshold
+    if access.alert_after > 0 && attempts == access.alert_after {
+        let alert = format!(
+            "⚠️ {} commands refused in the last {}, the latest:\n{}",
+            access.alert_after, format_duration(access.alert_window_secs), entry
+        );
+        alert_admins(&bot, db_pool, &alert).await?;
+    }
+    Ok(())
+}
+
+// A message that isn't a command the bot knows. Only answered in private chats, groups talk among themselves.
+pub async fn unknown_message(bot: &Bot, msg: &Message) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if !config::get().access.reply_unknown || !msg.chat.is_private() || msg.text().is_none() {
+        return Ok(());
+    }
+    if let Some(user) = msg.from() {
+        if may_reply(user.id) {
+            bot.send_message(msg.chat.id, "That isn't a command I know. Try /help.").await?;
+        }
+    }
+    Ok(())
+}
+
+// Post to the admin channel, or without one send every admin and owner a private message
+async fn alert_admins(bot: &Bot, db_pool: &DbPool, text: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if let Some(channel_id) = admin_channel() {
+        bot.send_message(channel_id, text).await?;
+        return Ok(());
+    }
+
+    let mut admin_ids: Vec<i64> = list_roles(db_pool)?
+        .into_iter()
+        .filter(|grant| grant.role.includes(Role::Admin))
+        .filter_map(|grant| grant.user_id)
+        .collect();
+    admin_ids.sort_unstable();
+    admin_ids.dedup();
+    for admin_id in admin_ids {
+        // Admins who never started a chat with the bot can't be messaged
+        if let Err(e) = bot.send_message(ChatId(admin_id), text).await {
+            log::warn!("Failed to alert admin {}: {}", admin_id, e);
+        }
+    }
+    Ok(())
+}
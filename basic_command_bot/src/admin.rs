@@ -1,64 +1,316 @@
 /// admin.rs
 
 use teloxide::{prelude::*};
+use teloxide::types::User;
 use crate::database::{DbPool};
-use rusqlite::Error as RusqliteError;
+use crate::config::BotConfig;
+use rusqlite::{params, Connection, OptionalExtension, Error as RusqliteError};
 //mod gamefunctions;
 //use crate::gamefunctions::season::{start_new_season, stop_current_season, current_active_season};
 
-// Function to add an administrator to the database
-pub fn add_admin(pool: &DbPool, username: &str) -> Result<(), RusqliteError> {
+// What a user may do. Everyone is a player. Admins run seasons, moderators handle the signups,
+// devs get the database commands and owners everything. Owners and devs come from the configuration,
+// the other roles are granted with /grant.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    Player,
+    Moderator,
+    Admin,
+    Dev,
+    Owner,
+}
+
+impl Role {
+    pub const ALL: [Role; 5] = [Role::Player, Role::Moderator, Role::Admin, Role::Dev, Role::Owner];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Player => "player",
+            Role::Moderator => "moderator",
+            Role::Admin => "admin",
+            Role::Dev => "dev",
+            Role::Owner => "owner",
+        }
+    }
+
+    // Whether this role may do what `required` may. Dev is beside the others: an admin
+    // doesn't get the database commands, a dev doesn't get to run seasons.
+    pub fn includes(&self, required: Role) -> bool {
+        match self {
+            Role::Owner => true,
+            Role::Admin => matches!(required, Role::Admin | Role::Moderator | Role::Player),
+            Role::Dev => matches!(required, Role::Dev | Role::Player),
+            Role::Moderator => matches!(required, Role::Moderator | Role::Player),
+            Role::Player => required == Role::Player,
+        }
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Role::ALL
+            .iter()
+            .find(|role| role.as_str().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or_else(|| format!("Unknown role '{}', use any of owner, admin, moderator, dev", s))
+    }
+}
+
+impl rusqlite::types::ToSql for Role {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl rusqlite::types::FromSql for Role {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: String| rusqlite::types::FromSqlError::Other(e.into()))
+    }
+}
+
+// A role held by a user, everywhere or only while one season is active. Users granted a role by
+// username have no id until they next message the bot, see `claim_roles`. The username is only
+// shown, never trusted once the id is known.
+pub struct RoleGrant {
+    pub user_id: Option<i64>,
+    pub username: Option<String>,
+    pub role: Role,
+    pub season_name: Option<String>,
+    pub from_config: bool,
+}
+
+impl RoleGrant {
+    pub fn holder(&self) -> String {
+        display_user(self.user_id, self.username.as_deref())
+    }
+}
+
+impl std::fmt::Display for RoleGrant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.role, self.holder())?;
+        if let Some(season_name) = &self.season_name {
+            write!(f, " in season {}", season_name)?;
+        }
+        if self.from_config {
+            write!(f, " (configuration)")?;
+        }
+        Ok(())
+    }
+}
+
+pub fn display_user(user_id: Option<i64>, username: Option<&str>) -> String {
+    match (username, user_id) {
+        (Some(username), Some(user_id)) => format!("@{} ({})", username, user_id),
+        (Some(username), None) => format!("@{} (waiting for their first message)", username),
+        (None, Some(user_id)) => format!("{}", user_id),
+        (None, None) => "?".to_string(),
+    }
+}
+
+// Function to give a user a role, everywhere or in one season. Returns false if they already had it.
+pub fn grant_role(pool: &DbPool, user_id: Option<i64>, username: Option<&str>, role: Role, season_id: Option<i32>, granted_by: i64) -> Result<bool, RusqliteError> {
     let conn = pool.get().expect("Failed to get connection from pool");
-    conn.execute("INSERT INTO administrators (username) VALUES (?1)", [username])?;
-    Ok(())
+    let inserted = conn.execute(
+        "INSERT INTO user_roles (user_id, username, role, season_id, granted_by, granted_at, from_config)
+         SELECT ?1, ?2, ?3, ?4, ?5, CURRENT_TIMESTAMP, 0
+         WHERE NOT EXISTS (
+             SELECT 1 FROM user_roles
+             WHERE role = ?3 AND season_id IS ?4
+               AND (user_id = ?1 OR (user_id IS NULL AND username = ?2 COLLATE NOCASE))
+         )",
+        params![user_id, username, role, season_id, granted_by],
+    )?;
+    Ok(inserted > 0)
 }
 
-// Function to remove an administrator from the database
-pub fn remove_admin(pool: &DbPool, username: &str) -> Result<(), RusqliteError> {
+// Function to take a granted role away, by user id and by the username it was granted to.
+// Roles from the configuration stay, they come back at every start.
+pub fn revoke_role(pool: &DbPool, user_id: Option<i64>, username: Option<&str>, role: Role, season_id: Option<i32>) -> Result<usize, RusqliteError> {
     let conn = pool.get().expect("Failed to get connection from pool");
-    conn.execute("DELETE FROM administrators WHERE username = ?1", [username])?;
-    Ok(())
+    conn.execute(
+        "DELETE FROM user_roles
+         WHERE role = ?3 AND season_id IS ?4 AND from_config = 0
+           AND (user_id = ?1 OR username = ?2 COLLATE NOCASE)",
+        params![user_id, username, role, season_id],
+    )
 }
 
-// Function to list all administrators from the database
-pub fn list_admins(pool: &DbPool) -> Result<Vec<String>, RusqliteError> {
+// Function to list everyone with a role, owners first. The username of a player is the latest one we saw.
+pub fn list_roles(pool: &DbPool) -> Result<Vec<RoleGrant>, RusqliteError> {
     let conn = pool.get().expect("Failed to get connection from pool");
-    let mut stmt = conn.prepare("SELECT username FROM administrators")?;
-    let rows = stmt.query_map([], |row| row.get(0))?;
-    
-    let mut admins = Vec::new();
-    for admin in rows {
-        admins.push(admin?);
-    }
-    Ok(admins)
+    let mut stmt = conn.prepare(
+        "SELECT r.user_id, COALESCE(p.username, r.username), r.role, s.name, r.from_config
+         FROM user_roles r
+         LEFT JOIN players p ON p.player_id = r.user_id
+         LEFT JOIN seasons s ON s.id = r.season_id
+         ORDER BY CASE r.role WHEN 'owner' THEN 0 WHEN 'admin' THEN 1 WHEN 'moderator' THEN 2 ELSE 3 END, r.id",
+    )?;
+    let grants = stmt
+        .query_map([], |row| {
+            Ok(RoleGrant {
+                user_id: row.get(0)?,
+                username: row.get(1)?,
+                role: row.get(2)?,
+                season_name: row.get(3)?,
+                from_config: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<RoleGrant>, RusqliteError>>()?;
+
+    Ok(grants)
 }
 
-// Function to check if a username is an administrator
-pub fn is_admin(pool: &DbPool, username: &str) -> Result<bool, RusqliteError> {
+// Whether a user holds a role in any season, by user id or by the username it was granted to
+pub fn holds_role(pool: &DbPool, user_id: Option<i64>, username: Option<&str>, role: Role) -> Result<bool, RusqliteError> {
     let conn = pool.get().expect("Failed to get connection from pool");
-    let mut stmt = conn.prepare("SELECT COUNT(*) FROM administrators WHERE username = ?1")?;
-    let count: i64 = stmt.query_row([username], |row| row.get(0))?;
-    
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM user_roles WHERE role = ?3 AND (user_id = ?1 OR username = ?2 COLLATE NOCASE)",
+        params![user_id, username, role],
+        |row| row.get(0),
+    )?;
     Ok(count > 0)
 }
 
-pub fn is_authorized_sender(msg: &Message, pool: &DbPool) -> bool {
-    if let Some(true_sender_username) = msg.from().and_then(|user| user.username.as_ref()) {
-        true_sender_username == "juno0x153" || true_sender_username == "novo2424" || is_admin(pool, true_sender_username).unwrap_or(false)
-    } else {
-        false
-    }
+// How many grants in every season of `role`, or of a role that includes it, would be left after
+// revoke_role took the user's grant away. Roles from the configuration always stay.
+pub fn remaining_holders(pool: &DbPool, user_id: Option<i64>, username: Option<&str>, role: Role) -> Result<i64, RusqliteError> {
+    let including = Role::ALL
+        .iter()
+        .filter(|holder| **holder != Role::Player && holder.includes(role))
+        .map(|holder| format!("'{}'", holder.as_str()))
+        .collect::<Vec<String>>()
+        .join(", ");
+    let conn = pool.get().expect("Failed to get connection from pool");
+    conn.query_row(
+        &format!(
+            "SELECT COUNT(*) FROM user_roles
+             WHERE season_id IS NULL AND role IN ({including})
+               AND NOT IFNULL(from_config = 0 AND role = ?3 AND (user_id = ?1 OR username = ?2 COLLATE NOCASE), 0)"
+        ),
+        params![user_id, username, role],
+        |row| row.get(0),
+    )
+}
+
+// The roles a user holds right now: their global roles and those for the active season
+pub fn user_roles(pool: &DbPool, user_id: i64) -> Result<Vec<Role>, RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    let mut stmt = conn.prepare(
+        "SELECT role FROM user_roles
+         WHERE user_id = ?1
+           AND (season_id IS NULL OR season_id = (SELECT id FROM seasons WHERE is_active = true LIMIT 1))",
+    )?;
+    let roles = stmt
+        .query_map(params![user_id], |row| row.get(0))?
+        .collect::<Result<Vec<Role>, RusqliteError>>()?;
+
+    Ok(roles)
 }
 
+// The first message of a user granted a role by username ties those roles to their user id.
+// From then on the roles no longer depend on the username.
+pub fn claim_roles(pool: &DbPool, user: &User) -> Result<usize, RusqliteError> {
+    let username = match &user.username {
+        Some(username) => username,
+        None => return Ok(0),
+    };
+    let conn = pool.get().expect("Failed to get connection from pool");
+    let claimed = conn.execute(
+        "UPDATE user_roles SET user_id = ?1 WHERE user_id IS NULL AND username = ?2 COLLATE NOCASE",
+        params![user.id.0 as i64, username],
+    )?;
+    if claimed > 0 {
+        log::info!("Roles of @{} are now tied to user id {}", username, user.id.0);
+    }
+
+    Ok(claimed)
+}
 
-pub fn is_authorized_dev(msg: &Message) -> bool {
-    if let Some(true_sender_username) = msg.from().and_then(|user| user.username.as_ref()) {
-        true_sender_username == "juno0x153" 
-    } else {
-        false
+// Whether the user holds a role that includes `required`
+pub fn has_role(pool: &DbPool, user: &User, required: Role) -> bool {
+    if required == Role::Player {
+        return true;
+    }
+    if let Err(e) = claim_roles(pool, user) {
+        log::error!("Failed to claim the roles of {}: {}", user.id.0, e);
+    }
+    match user_roles(pool, user.id.0 as i64) {
+        Ok(roles) => roles.iter().any(|role| role.includes(required)),
+        Err(e) => {
+            log::error!("Failed to read the roles of {}: {}", user.id.0, e);
+            false
+        }
     }
 }
 
+// Whether the user holds a role that includes `required` in every season, or in `season_id` as well if given.
+// Unlike has_role, a role for the active season counts for nothing else.
+pub fn has_role_in(pool: &DbPool, user: &User, required: Role, season_id: Option<i32>) -> Result<bool, RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    let mut stmt = conn.prepare("SELECT role FROM user_roles WHERE user_id = ?1 AND (season_id IS NULL OR season_id = ?2)")?;
+    let roles = stmt
+        .query_map(params![user.id.0 as i64, season_id], |row| row.get(0))?
+        .collect::<Result<Vec<Role>, RusqliteError>>()?;
+
+    Ok(roles.iter().any(|role: &Role| role.includes(required)))
+}
 
+// The check of the command filter in `schema()`
+pub fn is_authorized(msg: &Message, pool: &DbPool, required: Role) -> bool {
+    match msg.from() {
+        Some(user) => has_role(pool, user, required),
+        None => required == Role::Player,
+    }
+}
 
+// The owners (`admins.bootstrap`) and devs of the configuration: a numeric user id, or a username to be
+// resolved later. Each entry keeps its row across restarts, so a username stays tied to the user id that
+// claimed it, see `claim_roles`. Taking someone out of the configuration takes their role away.
+pub fn sync_config_roles(conn: &Connection, config: &BotConfig) -> Result<(), RusqliteError> {
+    let mut kept = Vec::new();
+    for (role, entries) in [(Role::Owner, &config.admins.bootstrap), (Role::Dev, &config.admins.devs)] {
+        for entry in entries {
+            let (user_id, username) = match entry.parse::<i64>() {
+                Ok(user_id) => (Some(user_id), None),
+                Err(_) => (None, Some(entry.as_str())),
+            };
+            let existing: Option<i64> = conn.query_row(
+                "SELECT id FROM user_roles
+                 WHERE from_config = 1 AND role = ?3
+                   AND ((username IS NULL AND user_id = ?1) OR username = ?2 COLLATE NOCASE)",
+                params![user_id, username, role],
+                |row| row.get(0),
+            ).optional()?;
+            let id = match existing {
+                Some(id) => id,
+                None => {
+                    conn.execute(
+                        "INSERT INTO user_roles (user_id, username, role, season_id, granted_by, granted_at, from_config)
+                         VALUES (?1, ?2, ?3, NULL, NULL, CURRENT_TIMESTAMP, 1)",
+                        params![user_id, username, role],
+                    )?;
+                    conn.last_insert_rowid()
+                }
+            };
+            kept.push(id.to_string());
+        }
+    }
 
+    // Entries no longer in the configuration
+    conn.execute(
+        &format!("DELETE FROM user_roles WHERE from_config = 1 AND id NOT IN ({})", kept.join(", ")),
+        [],
+    )?;
+    Ok(())
+}
@@ -0,0 +1,182 @@
+/// audit.rs
+
+use std::{cell::RefCell, error::Error, ops::ControlFlow, sync::Arc};
+use teloxide::dispatching::UpdateHandler;
+use teloxide::dptree::di::{DependencyMap, DependencySupplier};
+use teloxide::prelude::*;
+use teloxide::RequestError;
+use rusqlite::{params, Error as RusqliteError};
+use crate::config;
+use crate::database::DbPool;
+use crate::enums::Command;
+use crate::messages::send_long_message;
+
+tokio::task_local! {
+    // How the command being audited turned out, when its handler says so, see refuse and fail
+    static OUTCOME: RefCell<Option<String>>;
+}
+
+// One administrative action. Entries are only ever added, the table refuses updates and deletes.
+pub struct AuditEntry {
+    pub actor_id: Option<i64>,
+    pub actor_username: Option<String>,
+    pub command: String,
+    pub arguments: Option<String>,
+    pub target: Option<String>,
+    pub result: String,
+}
+
+impl AuditEntry {
+    // The command of a message as the user typed it, "/approveplayer@bot juno" gives "/approveplayer" and "juno"
+    pub fn from_command(msg: &Message, cmd: &Command, result: String) -> AuditEntry {
+        let text = msg.text().unwrap_or_default().trim();
+        let (command, arguments) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let command = command.split('@').next().unwrap_or_default().to_lowercase();
+        let arguments = arguments.trim();
+
+        AuditEntry {
+            actor_id: msg.from().map(|user| user.id.0 as i64),
+            actor_username: msg.from().and_then(|user| user.username.clone()),
+            command,
+            arguments: (!arguments.is_empty()).then(|| arguments.to_string()),
+            target: cmd.target().map(str::to_string),
+            result,
+        }
+    }
+}
+
+impl std::fmt::Display for AuditEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.actor_username, self.actor_id) {
+            (Some(username), Some(actor_id)) => write!(f, "@{} ({})", username, actor_id)?,
+            (None, Some(actor_id)) => write!(f, "{}", actor_id)?,
+            _ => write!(f, "?")?,
+        }
+        write!(f, ": {}", self.command)?;
+        if let Some(arguments) = &self.arguments {
+            write!(f, " {}", arguments)?;
+        }
+        write!(f, " → {}", self.result)
+    }
+}
+
+// An entry read back for /auditlog
+pub struct AuditRecord {
+    pub id: i64,
+    pub created_at: String,
+    pub entry: AuditEntry,
+}
+
+// Add an entry and copy it to the admin channel if one is configured. A failing copy is only logged,
+// the entry in the database is what counts.
+pub async fn record(bot: &Bot, pool: &DbPool, entry: &AuditEntry) -> Result<(), RusqliteError> {
+    {
+        let conn = pool.get().expect("Failed to get connection from pool");
+        conn.execute(
+            "INSERT INTO audit_log (actor_id, actor_username, command, arguments, target, result, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, CURRENT_TIMESTAMP)",
+            params![entry.actor_id, entry.actor_username, entry.command, entry.arguments, entry.target, entry.result],
+        )?;
+    }
+
+    if let Some(channel_id) = admin_channel() {
+        if let Err(e) = send_long_message(bot, channel_id, &format!("🛡 {}", entry)).await {
+            log::error!("Failed to copy an audit log entry to the admin channel: {}", e);
+        }
+    }
+    Ok(())
+}
+
+// The private admin channel, if configured. Channel ids are configured without their minus sign.
+pub fn admin_channel() -> Option<ChatId> {
+    config::get().channels.admin_channel_id.as_ref().and_then(|id| id.parse::<i64>().ok()).map(|id| ChatId(-id))
+}
+
+// The latest `limit` entries, newest first. The filter matches the command, arguments, target,
+// actor and result anywhere in the text.
+pub async fn recent_entries(pool: &DbPool, limit: i64, filter: Option<&str>) -> Result<Vec<AuditRecord>, RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    let mut stmt = conn.prepare(
+        "SELECT id, created_at, actor_id, actor_username, command, arguments, target, result
+         FROM audit_log
+         WHERE ?2 IS NULL
+            OR command LIKE '%' || ?2 || '%'
+            OR arguments LIKE '%' || ?2 || '%'
+            OR target LIKE '%' || ?2 || '%'
+            OR actor_username LIKE '%' || ?2 || '%'
+            OR CAST(actor_id AS TEXT) = ?2
+            OR result LIKE '%' || ?2 || '%'
+         ORDER BY id DESC
+         LIMIT ?1",
+    )?;
+    let records = stmt
+        .query_map(params![limit, filter], |row| {
+            Ok(AuditRecord {
+                id: row.get(0)?,
+                created_at: row.get(1)?,
+                entry: AuditEntry {
+                    actor_id: row.get(2)?,
+                    actor_username: row.get(3)?,
+                    command: row.get(4)?,
+                    arguments: row.get(5)?,
+                    target: row.get(6)?,
+                    result: row.get(7)?,
+                },
+            })
+        })?
+        .collect::<Result<Vec<AuditRecord>, RusqliteError>>()?;
+
+    Ok(records)
+}
+
+// Reply to a command that was turned down, and say why in its audit log entry. Handlers that refuse
+// a request reply and return Ok, without this the entry would read "ok".
+pub async fn refuse(bot: &Bot, chat_id: ChatId, reason: impl Into<String>) -> Result<(), RequestError> {
+    let reason = reason.into();
+    set_outcome(format!("refused: {}", reason));
+    bot.send_message(chat_id, reason).await?;
+    Ok(())
+}
+
+// Reply to a command that failed but was handled, and record the failure in its audit log entry
+pub async fn fail(bot: &Bot, chat_id: ChatId, error: impl Into<String>) -> Result<(), RequestError> {
+    let error = error.into();
+    set_outcome(format!("error: {}", error));
+    bot.send_message(chat_id, error).await?;
+    Ok(())
+}
+
+// The result for the audit log entry of the command being handled. Outside audit_trail,
+// e.g. for a player command, there is no entry to write to.
+pub fn set_outcome(result: String) {
+    let _ = OUTCOME.try_with(|outcome| *outcome.borrow_mut() = Some(result));
+}
+
+// Runs the command handlers and records every command that needs more than the player role, with
+// how it turned out: "ok", "refused: ..." when the handler turned it down (see refuse), or "error: ...".
+pub fn audit_trail() -> UpdateHandler<Box<dyn Error + Send + Sync + 'static>> {
+    dptree::from_fn(|deps: DependencyMap, cont| async move {
+        let (handled, outcome) = OUTCOME
+            .scope(RefCell::new(None), async {
+                let handled = cont(deps.clone()).await;
+                (handled, OUTCOME.with(|outcome| outcome.take()))
+            })
+            .await;
+
+        let cmd: Arc<Command> = deps.get();
+        if let (ControlFlow::Break(output), true) = (&handled, cmd.is_audited()) {
+            let result = match output {
+                Ok(()) => outcome.unwrap_or_else(|| "ok".to_string()),
+                Err(e) => format!("error: {}", e),
+            };
+            let msg: Arc<Message> = deps.get();
+            let bot: Arc<Bot> = deps.get();
+            let db_pool: Arc<Arc<DbPool>> = deps.get();
+            if let Err(e) = record(&bot, &db_pool, &AuditEntry::from_command(&msg, &cmd, result)).await {
+                log::error!("Failed to write the audit log: {}", e);
+            }
+        }
+
+        handled
+    })
+}
@@ -4,43 +4,62 @@ use teloxide::{prelude::*};
 use std::{error::Error, sync::Arc};
 
 use crate::admin::{
-    is_authorized_sender, 
-    list_admins, 
-    is_admin, 
-    remove_admin, 
-    add_admin
+    list_roles,
+    Role,
 };
 
 use crate::database::{
     DbPool
 };
 
+use crate::enums::{SeasonPhase, SeasonFormat};
+use crate::audit::{fail, refuse};
+
 use crate::commands::season::{
     start_new_season, 
+    season_format,
+    SeasonOptions,
     stop_current_season, 
     current_active_season, 
     current_active_season_id, 
     current_active_season_details,
-    start_signup_phase,
-    stop_signup_phase,
-    start_gaming_phase,
-    stop_gaming_phase,
+    transition,
     get_next_round_number,
     start_new_round,
-    end_current_round,
-    play_empty_hands_for_players,
+    set_season_allows_hand_change,
+    season_uses_commit_reveal,
+    set_season_commit_reveal,
+    season_best_of,
+    set_season_hand_set,
+    season_hand_set,
 };
+use crate::hands::HandSet;
 
 use crate::commands::playing_commands::{
-     get_current_round_id,
-     get_player_hands,
-     random_match_players,
-     evaluate_matches,
-     update_player_score,
-     announce_results,
+     close_current_round,
+     notify_empty_hands,
+};
+
+use crate::commands::schedule_commands::{
+    get_schedule,
+    format_duration,
+    ScheduleStatus,
+};
+
+use crate::commands::commit_reveal_commands::open_reveal_window;
+use crate::commands::series_commands::start_series_matches;
+use crate::commands::basic_commands::season_rules_text;
+use crate::scoring::{season_scoring_rules, set_season_scoring_rules, ScoringRules};
+
+use crate::commands::knockout_commands::{
+    ensure_bracket,
+    get_bracket,
+    render_bracket,
 };
 
 use crate::commands::playing_commands::fetch_leaderboard;
+use crate::messages::send_long_message;
+use crate::commands::role_commands::{resolve_user, grant_request, revoke_request, RoleRequest};
  use crate::commands::playing_commands::prepare_leaderboard_string;
 
 
@@ -51,69 +70,49 @@ use crate::commands::playing_commands::fetch_leaderboard;
 
 
 pub async fn add_admin_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>, username: String) -> Result<(), Box<dyn Error + Send + Sync>> {
-        if !is_authorized_sender(&msg, db_pool) {
-        return Ok(());  // Early return if the sender is not authorized
-    }
-
     let username = username.trim();
     if username.is_empty() {
-        bot.send_message(msg.chat.id, "Please provide a non-empty username.").await?;
+        refuse(&bot, msg.chat.id, "Please provide a non-empty username or user id.").await?;
+        return Ok(());
     } else if username.split_whitespace().count() != 1 {
-        bot.send_message(msg.chat.id, "Only one username please, no spaces.").await?;
-    } else if is_admin(db_pool, &username)? {
-        bot.send_message(msg.chat.id, format!("@{} is already an admin.", username)).await?;
-    } else {
-        //ignoring for now. Might handle Err properly later.
-        let _ = add_admin(db_pool, username);
-        bot.send_message(msg.chat.id, format!("Added @{} to admin list.", username)).await?;
+        refuse(&bot, msg.chat.id, "Only one username please, no spaces.").await?;
+        return Ok(());
     }
-    Ok(())
+
+    // The same as /grant <user> admin
+    let (user_id, username) = resolve_user(db_pool, username).await?;
+    grant_request(bot, msg, db_pool, RoleRequest { user_id, username, role: Role::Admin, season: None }).await
 }
 
 pub async fn remove_admin_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>, username: String) -> Result<(), Box<dyn Error + Send + Sync>> {
-        if !is_authorized_sender(&msg, db_pool) {
-        return Ok(());  // Early return if the sender is not authorized
-    }
-
     let username = username.trim();
-    if username.is_empty() || !is_admin(db_pool, &username).unwrap_or(false) {
-        if username.is_empty() {
-            bot.send_message(msg.chat.id, "Your command is empty, we need 1 username here.").await?;
-        } else {
-            bot.send_message(msg.chat.id, format!("User @{} is not in the admin list.", username)).await?;
-        }
-    } else {
-        //ignoring for now. Might handle Err properly later.
-        let _ = remove_admin(db_pool, &username); 
-        bot.send_message(msg.chat.id, format!("Removed @{} from admin list.", username)).await?;
+    if username.is_empty() {
+        refuse(&bot, msg.chat.id, "Your command is empty, we need 1 username or user id here.").await?;
+        return Ok(());
     }
-    Ok(())
+
+    // The same as /revoke <user> admin, with the same safeguards
+    let (user_id, username) = resolve_user(db_pool, username).await?;
+    revoke_request(bot, msg, db_pool, RoleRequest { user_id, username, role: Role::Admin, season: None }).await
 }
 
 pub async fn list_admins_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
-        if !is_authorized_sender(&msg, db_pool) {
-        return Ok(());  // Early return if the sender is not authorized
-    }
-    let admins = list_admins(db_pool)?;
-    let mut response = String::from("Admins:\n");
-    for admin in admins {
-    response.push_str(&format!("@{}\n", admin));
+    let grants = list_roles(db_pool)?;
+    let mut response = String::from("Roles:\n");
+    for grant in grants {
+        response.push_str(&format!("{}\n", grant));
     }
 
-    bot.send_message(msg.chat.id, response).await?;
+    send_long_message(&bot, msg.chat.id, &response).await?;
     Ok(())
 }
 
 
 
 pub async fn start_new_season_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>, season_info: String) -> Result<(), Box<dyn Error + Send + Sync>> {
-        if !is_authorized_sender(&msg, db_pool) {
-        return Ok(());  // Early return if the sender is not authorized
-    }
-    
     let season_info = season_info.trim();
-    if season_info.split_whitespace().count() != 2 {
-        bot.send_message(msg.chat.id, "The command should be used like this '/startnewseason <Title> <Number of Players>'.").await?;
+    if season_info.split_whitespace().count() < 2 {
+        refuse(&bot, msg.chat.id, format!("The command should be used like this '/startnewseason <Title> <Number of Players> {}'.", SeasonOptions::USAGE)).await?;
         return Ok(());
     }
 
@@ -123,7 +122,7 @@ pub async fn start_new_season_command(bot: Bot, msg: Message, db_pool: &Arc<DbPo
     let season_name = match parts.next() {
         Some(name) => name.to_string(),  // Convert the &str slice to a String
         None => {
-            bot.send_message(msg.chat.id, "Please provide a season name.").await?;
+            refuse(&bot, msg.chat.id, "Please provide a season name.").await?;
             return Ok(());
         }
     };
@@ -133,12 +132,21 @@ pub async fn start_new_season_command(bot: Bot, msg: Message, db_pool: &Arc<DbPo
         Some(num_str) => match num_str.parse() {
             Ok(num) => num,
             Err(_) => {
-                bot.send_message(msg.chat.id, "Invalid number format for max players.").await?;
+                refuse(&bot, msg.chat.id, "Invalid number format for max players.").await?;
                 return Ok(());
             }
         },
         None => {
-            bot.send_message(msg.chat.id, "Please provide the maximum number of players.").await?;
+            refuse(&bot, msg.chat.id, "Please provide the maximum number of players.").await?;
+            return Ok(());
+        }
+    };
+
+    // Anything after max_players is a key=value option
+    let options = match SeasonOptions::parse(&parts.collect::<Vec<&str>>()) {
+        Ok(options) => options,
+        Err(e) => {
+            refuse(&bot, msg.chat.id, format!("{}. Available options: {}", e, SeasonOptions::USAGE)).await?;
             return Ok(());
         }
     };
@@ -146,24 +154,32 @@ pub async fn start_new_season_command(bot: Bot, msg: Message, db_pool: &Arc<DbPo
     match current_active_season(db_pool).await {
         Ok(Some(active_season)) => {
             // There is an active season, send a message
-            bot.send_message(msg.chat.id, format!("A season is already in progress: '{}'. Another season cannot be started until the current one is concluded.", active_season)).await?;
+            refuse(&bot, msg.chat.id, format!("A season is already in progress: '{}'. Another season cannot be started until the current one is concluded.", active_season)).await?;
         },
         Ok(None) => {
             // No active season, proceed to start a new one
-            match start_new_season(db_pool, &season_name, max_players).await {
+            match start_new_season(db_pool, &season_name, max_players, &options).await {
                 Ok(_) => {
                     // Successfully started a new season
-                    bot.send_message(msg.chat.id, format!("A new rock-paper-scissors season '{}' has started! Maximum players allowed: {}. Let the games begin.", season_name, max_players)).await?;
+                    let mut play_format = match options.format {
+                        SeasonFormat::League => format!("league, pairing: {}", options.pairing),
+                        SeasonFormat::Knockout => "knockout".to_string(),
+                    };
+                    if options.best_of > 1 {
+                        play_format.push_str(&format!(", matches best of {}", options.best_of));
+                    }
+                    play_format.push_str(&format!(", hands: {}", options.hands.name));
+                    bot.send_message(msg.chat.id, format!("A new rock-paper-scissors season '{}' has started! Maximum players allowed: {}, format: {}. Let the games begin.", season_name, max_players, play_format)).await?;
                 },
                 Err(e) => {
                     // Error in starting a new season
-                    bot.send_message(msg.chat.id, format!("Failed to start new season '{}': {}", season_name, e)).await?;
+                    fail(&bot, msg.chat.id, format!("Failed to start new season '{}': {}", season_name, e)).await?;
                 }
             }
         },
         Err(e) => {
             // Error in checking for an active season
-            bot.send_message(msg.chat.id, format!("Error checking for active season: {}", e)).await?;
+            fail(&bot, msg.chat.id, format!("Error checking for active season: {}", e)).await?;
         }
     }
 
@@ -171,14 +187,11 @@ pub async fn start_new_season_command(bot: Bot, msg: Message, db_pool: &Arc<DbPo
 }
 
 pub async fn stop_new_season_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
-        if !is_authorized_sender(&msg, db_pool) {
-        return Ok(());  // Early return if the sender is not authorized
-    }
     // Check if there is an active season
     match current_active_season_details(db_pool).await {
-        Ok(Some((season_id,season_name, _, _, _))) => {
+        Ok(Some((season_id,season_name, _, _, status))) => {
             // Stop the active season
-            match stop_current_season(db_pool).await {
+            match stop_current_season(db_pool, status).await {
                 Ok(_) => {
                     // Successfully stopped the season
                     bot.send_message(msg.chat.id, format!("The season '{}' has been successfully concluded.", season_name)).await?;
@@ -191,7 +204,7 @@ pub async fn stop_new_season_command(bot: Bot, msg: Message, db_pool: &Arc<DbPoo
                             if let Some(end_of_first_line) = response.find('\n') {
                                 response.replace_range(..end_of_first_line, &format!("🏆 Final {} Leaderboard 🏆", season_name));
                             }
-                            bot.send_message(msg.chat.id, &response).await?;
+                            send_long_message(&bot, msg.chat.id, &response).await?;
                         },
                         Err(_) => {
                             bot.send_message(msg.chat.id, "Failed to fetch the final leaderboard.").await?;
@@ -200,17 +213,17 @@ pub async fn stop_new_season_command(bot: Bot, msg: Message, db_pool: &Arc<DbPoo
                 }
                 Err(e) => {
                     // Error in stopping the season
-                    bot.send_message(msg.chat.id, format!("Failed to conclude the season '{}': {}", season_name, e)).await?;
+                    fail(&bot, msg.chat.id, format!("Failed to conclude the season '{}': {}", season_name, e)).await?;
                 }
             }
         }
         Ok(None) => {
             // No active season
-            bot.send_message(msg.chat.id, "There is no active season to conclude.").await?;
+            refuse(&bot, msg.chat.id, "There is no active season to conclude.").await?;
         }
         Err(e) => {
             // Error in getting season details
-            bot.send_message(msg.chat.id, format!("Failed to get details of the current season: {}", e)).await?;
+            fail(&bot, msg.chat.id, format!("Failed to get details of the current season: {}", e)).await?;
         }
     }
 
@@ -220,17 +233,26 @@ pub async fn stop_new_season_command(bot: Bot, msg: Message, db_pool: &Arc<DbPoo
 
 
 pub async fn current_season_status_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
-    if !is_authorized_sender(&msg, db_pool) {
-        return Ok(());  // Early return if the sender is not authorized
-    }
-
     // Check if there is an active season
     match current_active_season_details(db_pool).await {
-        Ok(Some((_, name, start_date, max_players, status))) => {
-            let message = format!(
+        Ok(Some((season_id, name, start_date, max_players, status))) => {
+            let mut message = format!(
                 "Current active season: '{}'\nStarted on: {}\nMax players: {}\nStatus: {}",
                 name, start_date, max_players, status
             );
+            message.push_str(&season_rules_text(db_pool, season_id).await?);
+            if let Some(schedule) = get_schedule(db_pool, season_id).await? {
+                message.push_str(&format!(
+                    "\nRound schedule: {}, {} rounds left ({} each, {} apart)",
+                    schedule.status.as_str(),
+                    schedule.rounds_remaining,
+                    format_duration(schedule.round_duration_secs),
+                    format_duration(schedule.gap_secs)
+                ));
+                if let Some(next_start) = schedule.next_start.filter(|_| schedule.status == ScheduleStatus::Running && schedule.rounds_remaining > 0) {
+                    message.push_str(&format!("\nNext scheduled round: {} UTC", next_start));
+                }
+            }
             bot.send_message(msg.chat.id, message).await?;
         },
         Ok(None) => {
@@ -248,46 +270,45 @@ pub async fn current_season_status_command(bot: Bot, msg: Message, db_pool: &Arc
 
 
 pub async fn startsignupphase_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
-        if !is_authorized_sender(&msg, db_pool) {
-        return Ok(());  // Early return if the sender is not authorized
-    }
-
     // Attempt to start the signup phase
     // Check the current season's status
     match current_active_season_details(db_pool).await {
         Ok(Some((_, name, _, _, status))) => {
-            match status.as_str() {
-                "start_signup" => {
-                    bot.send_message(msg.chat.id, "Signup has already started.").await?;
+            match status {
+                SeasonPhase::StartSignup => {
+                    refuse(&bot, msg.chat.id, "Signup has already started.").await?;
+                },
+                SeasonPhase::RoundOngoing => {
+                    refuse(&bot, msg.chat.id, "A round is going on, you cannot start signing up.").await?;
                 },
-                "round_ongoing" => {
-                    bot.send_message(msg.chat.id, "A round is going on, you cannot start signing up.").await?;
+                SeasonPhase::RoundRevealing => {
+                    refuse(&bot, msg.chat.id, "A round is revealing its hands, you cannot start signing up.").await?;
                 },
-                "start_gaming" => {
-                    bot.send_message(msg.chat.id, "The game has already started. We cannot open the signup now. Let's be fair.").await?;
+                SeasonPhase::StartGaming => {
+                    refuse(&bot, msg.chat.id, "The game has already started. We cannot open the signup now. Let's be fair.").await?;
                 },
-                "stopped_gaming" => {
-                    bot.send_message(msg.chat.id, "The game already started. And seems it also ended. Not a time to open sign-ups.").await?;
+                SeasonPhase::StoppedGaming => {
+                    refuse(&bot, msg.chat.id, "The game already started. And seems it also ended. Not a time to open sign-ups.").await?;
                 },
                 _ => {
                     // If none of the above, attempt to start the signup phase
-                    match start_signup_phase(db_pool).await {
+                    match transition(db_pool, status, SeasonPhase::StartSignup).await {
                         Ok(_) => {
                             bot.send_message(msg.chat.id, format!("The signup phase for the new rock-paper-scissors season '{}' is now open. Interested players can register.", name)).await?;
                         },
                         Err(e) => {
                             log::info!("Failed to start the signup phase: {}", e);
-                            bot.send_message(msg.chat.id, format!("Failed to start the signup phase: {}", e)).await?;
+                            fail(&bot, msg.chat.id, format!("Failed to start the signup phase: {}", e)).await?;
                         }
                     }
                 }
             }
         },
         Ok(None) => {
-            bot.send_message(msg.chat.id, "There is no active season currently.").await?;
+            refuse(&bot, msg.chat.id, "There is no active season currently.").await?;
         },
         Err(e) => {
-            bot.send_message(msg.chat.id, format!("Failed to get current season status: {}", e)).await?;
+            fail(&bot, msg.chat.id, format!("Failed to get current season status: {}", e)).await?;
         }
     }
 
@@ -295,45 +316,44 @@ pub async fn startsignupphase_command(bot: Bot, msg: Message, db_pool: &Arc<DbPo
 }
 
 pub async fn stopsignupphase_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
-        if !is_authorized_sender(&msg, db_pool) {
-        return Ok(());  // Early return if the sender is not authorized
-    }
-
     // Check the current season's status
     match current_active_season_details(db_pool).await {
         Ok(Some((_, name, _, _, status))) => {
-            match status.as_str() {
-                "stopped_signup" => {
-                    bot.send_message(msg.chat.id, "Signup has already stopped!").await?;
+            match status {
+                SeasonPhase::StoppedSignup => {
+                    refuse(&bot, msg.chat.id, "Signup has already stopped!").await?;
+                },
+                SeasonPhase::RoundOngoing => {
+                    refuse(&bot, msg.chat.id, "A round is going on, you cannot stop signing up.").await?;
                 },
-                "round_ongoing" => {
-                    bot.send_message(msg.chat.id, "A round is going on, you cannot stop signing up.").await?;
+                SeasonPhase::RoundRevealing => {
+                    refuse(&bot, msg.chat.id, "A round is revealing its hands, you cannot stop signing up.").await?;
                 },
-                "start_gaming" => {
-                    bot.send_message(msg.chat.id, "The game has already started. This command is not valid.").await?;
+                SeasonPhase::StartGaming => {
+                    refuse(&bot, msg.chat.id, "The game has already started. This command is not valid.").await?;
                 },
-                "stopped_gaming" => {
-                    bot.send_message(msg.chat.id, "The game already started. And seems it also ended. This command is not valid.").await?;
+                SeasonPhase::StoppedGaming => {
+                    refuse(&bot, msg.chat.id, "The game already started. And seems it also ended. This command is not valid.").await?;
                 },
                 _ => {
-                    // If none of the above, attempt to start the signup phase
-                    match stop_signup_phase(db_pool).await {
+                    // If none of the above, attempt to stop the signup phase
+                    match transition(db_pool, status, SeasonPhase::StoppedSignup).await {
                         Ok(_) => {
                             bot.send_message(msg.chat.id, format!("The signup phase is now closed. Preparations for the '{}' game will now commence.", name)).await?;
                         },
                         Err(e) => {
                             log::info!("Failed to stop the signup phase: {}", e);
-                            bot.send_message(msg.chat.id, format!("Failed to stop the signup phase: {}", e)).await?;
+                            fail(&bot, msg.chat.id, format!("Failed to stop the signup phase: {}", e)).await?;
                         }
                     }
                 }
             }
         },
         Ok(None) => {
-            bot.send_message(msg.chat.id, "There is no active season currently.").await?;
+            refuse(&bot, msg.chat.id, "There is no active season currently.").await?;
         },
         Err(e) => {
-            bot.send_message(msg.chat.id, format!("Failed to get current season status: {}", e)).await?;
+            fail(&bot, msg.chat.id, format!("Failed to get current season status: {}", e)).await?;
         }
     }
 
@@ -342,44 +362,55 @@ pub async fn stopsignupphase_command(bot: Bot, msg: Message, db_pool: &Arc<DbPoo
 
 
 pub async fn startgamingphase_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
-        if !is_authorized_sender(&msg, db_pool) {
-        return Ok(());  // Early return if the sender is not authorized
-    }
     // Check the current season's status
     match current_active_season_details(db_pool).await {
-        Ok(Some((_, name, _, _, status))) => {
-            match status.as_str() {
-                "start_gaming" => {
-                    bot.send_message(msg.chat.id, "The game has already started!").await?;
+        Ok(Some((season_id, name, _, _, status))) => {
+            match status {
+                SeasonPhase::StartGaming => {
+                    refuse(&bot, msg.chat.id, "The game has already started!").await?;
+                },
+                SeasonPhase::RoundOngoing => {
+                    refuse(&bot, msg.chat.id, "A round is going on, you cannot start gaming phase.").await?;
                 },
-                "round_ongoing" => {
-                    bot.send_message(msg.chat.id, "A round is going on, you cannot start gaming phase.").await?;
+                SeasonPhase::RoundRevealing => {
+                    refuse(&bot, msg.chat.id, "A round is revealing its hands, you cannot start gaming phase.").await?;
                 },
-                "start_signup" => {
-                    bot.send_message(msg.chat.id, "The signup phase has not been completed. Please finish it first.").await?;
+                SeasonPhase::StartSignup => {
+                    refuse(&bot, msg.chat.id, "The signup phase has not been completed. Please finish it first.").await?;
                 },
-                "initial" => {
-                    bot.send_message(msg.chat.id, "The season just started. Please start the signup phase first, we need players.").await?;
+                SeasonPhase::Initial => {
+                    refuse(&bot, msg.chat.id, "The season just started. Please start the signup phase first, we need players.").await?;
                 },
                 _ => {
-                    // If none of the above, attempt to start the signup phase
-                    match start_gaming_phase(db_pool).await {
+                    // Knockout seasons need their bracket before the first round
+                    let knockout = season_format(db_pool, season_id).await? == SeasonFormat::Knockout;
+                    if knockout && !ensure_bracket(db_pool, season_id).await? {
+                        refuse(&bot, msg.chat.id, "A knockout season needs at least 2 approved players to draw the bracket.").await?;
+                        return Ok(());
+                    }
+
+                    // If none of the above, attempt to start the gaming phase
+                    match transition(db_pool, status, SeasonPhase::StartGaming).await {
                         Ok(_) => {
                             bot.send_message(msg.chat.id, format!("The gaming phase has begun! Welcome to '{}'.Players, get ready to challenge each other.", name)).await?;
+                            if knockout {
+                                let bracket = render_bracket(db_pool, &name, &get_bracket(db_pool, season_id).await?).await?;
+                                bot.send_message(msg.chat.id, bracket).await?;
+                            }
                         },
                         Err(e) => {
-                            log::info!("Failed to start the signup phase: {}", e);
-                            bot.send_message(msg.chat.id, format!("Failed to start the signup phase: {}", e)).await?;
+                            log::info!("Failed to start the gaming phase: {}", e);
+                            fail(&bot, msg.chat.id, format!("Failed to start the gaming phase: {}", e)).await?;
                         }
                     }
                 }
             }
         },
         Ok(None) => {
-            bot.send_message(msg.chat.id, "There is no active season currently.").await?;
+            refuse(&bot, msg.chat.id, "There is no active season currently.").await?;
         },
         Err(e) => {
-            bot.send_message(msg.chat.id, format!("Failed to get current season status: {}", e)).await?;
+            fail(&bot, msg.chat.id, format!("Failed to get current season status: {}", e)).await?;
         }
     }
 
@@ -387,67 +418,195 @@ pub async fn startgamingphase_command(bot: Bot, msg: Message, db_pool: &Arc<DbPo
 }
 
 pub async fn stopgamingphase_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
-        if !is_authorized_sender(&msg, db_pool) {
-        return Ok(());  // Early return if the sender is not authorized
-    }
     // Check the current season's status
     match current_active_season_details(db_pool).await {
         Ok(Some((_, _, _, _, status))) => {
-            match status.as_str() {
-                "stopped_gaming" => {
-                    bot.send_message(msg.chat.id, "The game has already started!").await?;
+            match status {
+                SeasonPhase::StoppedGaming => {
+                    refuse(&bot, msg.chat.id, "The gaming phase has already been stopped!").await?;
+                },
+                SeasonPhase::RoundOngoing => {
+                    refuse(&bot, msg.chat.id, "A round is going on, you cannot stop gaming phase.").await?;
                 },
-                "round_ongoing" => {
-                    bot.send_message(msg.chat.id, "A round is going on, you cannot stop gaming phase.").await?;
+                SeasonPhase::RoundRevealing => {
+                    refuse(&bot, msg.chat.id, "The reveal window of a round is open. Close the round with /stopround first, then stop the gaming phase.").await?;
                 },
-                "stopped_signup" => {
-                    bot.send_message(msg.chat.id, "Oh, the signup is closed, however, the game hasn't started yet. Start a game to close it.").await?;
+                SeasonPhase::StoppedSignup => {
+                    refuse(&bot, msg.chat.id, "Oh, the signup is closed, however, the game hasn't started yet. Start a game to close it.").await?;
                 },
-                "start_signup" => {
-                    bot.send_message(msg.chat.id, "The signup phase has not been completed. Please finish it first, and then start the gaming phase.").await?;
+                SeasonPhase::StartSignup => {
+                    refuse(&bot, msg.chat.id, "The signup phase has not been completed. Please finish it first, and then start the gaming phase.").await?;
                 },
-                "initial" => {
-                    bot.send_message(msg.chat.id, "The season just started. Please start the signup phase first, stop it, start the game phase. Then we can talk about closing the game.").await?;
+                SeasonPhase::Initial => {
+                    refuse(&bot, msg.chat.id, "The season just started. Please start the signup phase first, stop it, start the game phase. Then we can talk about closing the game.").await?;
                 },
                 _ => {
-                    // If none of the above, attempt to start the signup phase
-                    match stop_gaming_phase(db_pool).await {
+                    // If none of the above, attempt to stop the gaming phase
+                    match transition(db_pool, status, SeasonPhase::StoppedGaming).await {
                         Ok(_) => {
                             bot.send_message(msg.chat.id, format!("The gaming phase has ended. Thank you to all participants! Remember to /stopnewseason when you're done.")).await?;
                         },
                         Err(e) => {
                             log::info!("Failed to stop the gaming phase: {}", e);
-                            bot.send_message(msg.chat.id, format!("Failed to stop the gaming phase: {}", e)).await?;
+                            fail(&bot, msg.chat.id, format!("Failed to stop the gaming phase: {}", e)).await?;
                         }
                     }
                 }
             }
         },
         Ok(None) => {
-            bot.send_message(msg.chat.id, "There is no active season currently.").await?;
+            refuse(&bot, msg.chat.id, "There is no active season currently.").await?;
         },
         Err(e) => {
-            bot.send_message(msg.chat.id, format!("Failed to get current season status: {}", e)).await?;
+            fail(&bot, msg.chat.id, format!("Failed to get current season status: {}", e)).await?;
         }
     }    
 
     Ok(())
 }
 
-pub async fn start_round_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
-    if !is_authorized_sender(&msg, db_pool) {
-        return Ok(());  // Early return if the sender is not authorized
+pub async fn allow_hand_change_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>, setting: String) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let allow = match setting.trim().to_lowercase().as_str() {
+        "on" => true,
+        "off" => false,
+        _ => {
+            refuse(&bot, msg.chat.id, "The command should be used like this '/allowhandchange on' or '/allowhandchange off'.").await?;
+            return Ok(());
+        }
+    };
+
+    match current_active_season_id(db_pool).await? {
+        Some(season_id) => {
+            set_season_allows_hand_change(db_pool, season_id, allow).await?;
+            let response = if allow {
+                "Players can now change their hand until the round closes. The last choice counts."
+            } else {
+                "Hands are final again once played."
+            };
+            bot.send_message(msg.chat.id, response).await?;
+        }
+        None => {
+            refuse(&bot, msg.chat.id, "No active season found.").await?;
+        }
     }
 
+    Ok(())
+}
+
+pub async fn commit_reveal_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>, setting: String) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let enabled = match setting.trim().to_lowercase().as_str() {
+        "on" => true,
+        "off" => false,
+        _ => {
+            refuse(&bot, msg.chat.id, "The command should be used like this '/commitreveal on' or '/commitreveal off'.").await?;
+            return Ok(());
+        }
+    };
+
+    match current_active_season_details(db_pool).await? {
+        Some((_, _, _, _, SeasonPhase::RoundOngoing | SeasonPhase::RoundRevealing)) => {
+            refuse(&bot, msg.chat.id, "A round is in progress. Change the mode between rounds.").await?;
+        }
+        // Each throw of a best-of match is played and scored right away, there is nothing to hide
+        Some((season_id, _, _, _, _)) if enabled && season_best_of(db_pool, season_id).await? > 1 => {
+            refuse(&bot, msg.chat.id, "Commit–reveal only works with single-throw matches, this season plays best-of matches.").await?;
+        }
+        Some((season_id, _, _, _, _)) => {
+            set_season_commit_reveal(db_pool, season_id, enabled).await?;
+            let response = if enabled {
+                "Commit–reveal is on. From the next round players /commit to a hash of their hand and /reveal it after the round closes."
+            } else {
+                "Commit–reveal is off. Players play their hands with /play again."
+            };
+            bot.send_message(msg.chat.id, response).await?;
+        }
+        None => {
+            refuse(&bot, msg.chat.id, "No active season found.").await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn set_scoring_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>, rules: String) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (season_id, status) = match current_active_season_details(db_pool).await? {
+        Some((season_id, _, _, _, status)) => (season_id, status),
+        None => {
+            refuse(&bot, msg.chat.id, "No active season found.").await?;
+            return Ok(());
+        }
+    };
+    let current_rules = season_scoring_rules(db_pool, season_id).await?;
+
+    let options: Vec<&str> = rules.split_whitespace().collect();
+    if options.is_empty() {
+        bot.send_message(msg.chat.id, format!("Scoring: {}\nChange it like this '/setscoring {}'.", current_rules, ScoringRules::USAGE)).await?;
+        return Ok(());
+    }
+
+    // The rules apply when a round closes, so they can't change under a round that is being played
+    if status == SeasonPhase::RoundOngoing || status == SeasonPhase::RoundRevealing {
+        refuse(&bot, msg.chat.id, "A round is in progress. Change the scoring between rounds.").await?;
+        return Ok(());
+    }
+
+    match current_rules.parse(&options) {
+        Ok(new_rules) => {
+            set_season_scoring_rules(db_pool, season_id, &new_rules).await?;
+            bot.send_message(msg.chat.id, format!("Scoring from the next round: {}", new_rules)).await?;
+        }
+        Err(e) => {
+            refuse(&bot, msg.chat.id, format!("{}. Available rules: {}", e, ScoringRules::USAGE)).await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn set_hands_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>, spec: String) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (season_id, status) = match current_active_season_details(db_pool).await? {
+        Some((season_id, _, _, _, status)) => (season_id, status),
+        None => {
+            refuse(&bot, msg.chat.id, "No active season found.").await?;
+            return Ok(());
+        }
+    };
+
+    if spec.trim().is_empty() {
+        let hand_set = season_hand_set(db_pool, season_id).await?;
+        bot.send_message(msg.chat.id, format!("Hands: {}\nChange them like this '/sethands {}'.", hand_set, HandSet::USAGE)).await?;
+        return Ok(());
+    }
+
+    // Hands already played or committed to must stay valid until the round is scored
+    if status == SeasonPhase::RoundOngoing || status == SeasonPhase::RoundRevealing {
+        refuse(&bot, msg.chat.id, "A round is in progress. Change the hands between rounds.").await?;
+        return Ok(());
+    }
+
+    match HandSet::parse(&spec) {
+        Ok(hand_set) => {
+            set_season_hand_set(db_pool, season_id, &hand_set).await?;
+            bot.send_message(msg.chat.id, format!("From the next round the hands are {}", hand_set)).await?;
+        }
+        Err(e) => {
+            refuse(&bot, msg.chat.id, format!("{}.\nUsage: /sethands {}", e, HandSet::USAGE)).await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn start_round_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
     // Check the current season's status
     let season_details = current_active_season_details(db_pool).await?;
     if let Some((_, _, _, _, status)) = season_details {
-        if status != "start_gaming" {
-            bot.send_message(msg.chat.id, "There is no active season in the 'start_gaming' phase.").await?;
+        if status != SeasonPhase::StartGaming {
+            refuse(&bot, msg.chat.id, "There is no active season in the 'start_gaming' phase.").await?;
             return Ok(());
         }
     } else {
-        bot.send_message(msg.chat.id, "No active season found.").await?;
+        refuse(&bot, msg.chat.id, "No active season found.").await?;
         return Ok(());
     }
 
@@ -458,82 +617,59 @@ pub async fn start_round_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>)
         let next_round_number = get_next_round_number(db_pool, &season_id_str).await?;
 
         // Start the new round
-        start_new_round(db_pool, season_id_str, next_round_number).await?;
+        start_new_round(db_pool, season_id_str, next_round_number, None).await?;
         bot.send_message(msg.chat.id, "Finally, let us start the round!").await?;
+
+        // Best-of matches are paired now, the players get their opponent by DM
+        start_series_matches(&bot, db_pool, season_id_str).await?;
     } else {
-        bot.send_message(msg.chat.id, "No active season ID found.").await?;
+        refuse(&bot, msg.chat.id, "No active season ID found.").await?;
     }
 
     Ok(())
 }
 
 pub async fn stop_round_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
-    if !is_authorized_sender(&msg, db_pool) {
-        return Ok(());  // Early return if the sender is not authorized
-    }
-
     // Check the current season's status
-    let season_details = current_active_season_details(db_pool).await?;
-    if season_details.is_none() || season_details.unwrap().4 != "round_ongoing" {
-        bot.send_message(msg.chat.id, "There is no active season in the 'round_ongoing' phase.").await?;
-        return Ok(());
-    }
+    let status = match current_active_season_details(db_pool).await? {
+        Some((_, _, _, _, status)) if status == SeasonPhase::RoundOngoing || status == SeasonPhase::RoundRevealing => status,
+        _ => {
+            refuse(&bot, msg.chat.id, "There is no active season in the 'round_ongoing' or 'round_revealing' phase.").await?;
+            return Ok(());
+        }
+    };
 
     // Get the current active round's ID
     let current_season_id = current_active_season_id(db_pool).await?;
     if let Some(season_id) = current_season_id {
-        
-        // Get the current round ID
-        let current_round_id = get_current_round_id(db_pool, season_id)
-            .await?
-            .expect("No current round ID found");
-        
-        // Handle players who haven't played
-        let players_without_moves = play_empty_hands_for_players(db_pool, season_id, current_round_id).await?;
-
-        // Notify players who haven't played
-        for player_id in players_without_moves {
-            // Here, send a message to each player. You'll need to convert `player_id` to a ChatId or UserId that Bot can use
-            let chat_id = ChatId(player_id as i64); // Assuming player_id can be directly used as chat_id
-            bot.send_message(chat_id, "Your hand was empty for this round.").await?;
+        // Commit–reveal rounds close for commitments first, the second /stopround scores them
+        if status == SeasonPhase::RoundOngoing && season_uses_commit_reveal(db_pool, season_id).await? {
+            let announcement = open_reveal_window(&bot, db_pool, season_id).await?;
+            send_long_message(&bot, msg.chat.id, &format!("{} Use /stopround again to score the round before the window closes.", announcement)).await?;
+            return Ok(());
         }
 
-        // ====
-        // ==== GAME MAKING LOGIC HERE ===
-        // ====
-        // Step 1: Retrieve players and hands
-        let player_hands = get_player_hands(db_pool, current_round_id, season_id).await?;
-        println!("Player hands: {:?}",player_hands);
 
-        // Step 2: Randomly match players
-        let matched_pairs = random_match_players(player_hands).await;
-        println!("Matched pairs: {:?}",matched_pairs);
-
-        // Step 3: Evaluate matches
-        let match_results = evaluate_matches(db_pool, matched_pairs, current_round_id).await?;
-        println!("Match results: {:?}", match_results);
-
-        // Step 4: Update player scores and RoundDetailsTable
-        update_player_score(db_pool, match_results.clone(), season_id).await?;
-
-        // End of game-making logic
+        // Play empty hands, match, score and end the round
+        let closed_round = match close_current_round(db_pool, season_id).await? {
+            Some(closed_round) => closed_round,
+            None => {
+                refuse(&bot, msg.chat.id, "The round was already closed.").await?;
+                return Ok(());
+            }
+        };
 
-        // End the current round
-        end_current_round(db_pool, season_id).await?;
+        // Notify players who haven't played
+        notify_empty_hands(&bot, &closed_round.players_without_moves).await;
 
         bot.send_message(msg.chat.id, "Round is over, everyone back to their corner!").await?;
 
         // post game announcements:
-        // Generate announcement for match results
-        let announcement = announce_results(db_pool, match_results).await?;
-        if !announcement.is_empty() {
-            bot.send_message(msg.chat.id, &announcement).await?;
+        if !closed_round.announcement.is_empty() {
+            send_long_message(&bot, msg.chat.id, &closed_round.announcement).await?;
         }
-
-
-
     } else {
-        bot.send_message(msg.chat.id, "No active season ID found.").await?;
+        refuse(&bot, msg.chat.id, "No active season ID found.").await?;
     }
 
     Ok(())
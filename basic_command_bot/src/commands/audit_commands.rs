@@ -0,0 +1,46 @@
+/// audit_commands.rs
+
+use teloxide::prelude::*;
+use std::{error::Error, sync::Arc};
+use crate::audit::recent_entries;
+use crate::database::DbPool;
+use crate::messages::send_long_message;
+
+// Entries shown by /auditlog without a count, and the most it shows at once
+const DEFAULT_ENTRIES: i64 = 20;
+const MAX_ENTRIES: i64 = 200;
+
+pub async fn audit_log_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>, args: String) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let args = args.trim();
+    let (first, rest) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+    let (limit, filter) = match first.parse::<i64>() {
+        Ok(count) if count > 0 => (count.min(MAX_ENTRIES), rest.trim()),
+        Ok(_) => {
+            bot.send_message(msg.chat.id, "The command should be used like this '/auditlog [count] [text]', with a count of at least 1.").await?;
+            return Ok(());
+        }
+        Err(_) => (DEFAULT_ENTRIES, args),
+    };
+    let filter = (!filter.is_empty()).then_some(filter);
+
+    let records = recent_entries(db_pool, limit, filter).await?;
+    if records.is_empty() {
+        let response = match filter {
+            Some(filter) => format!("No audit log entries mention '{}'.", filter),
+            None => "The audit log is empty.".to_string(),
+        };
+        bot.send_message(msg.chat.id, response).await?;
+        return Ok(());
+    }
+
+    let mut response = match filter {
+        Some(filter) => format!("🛡 Latest {} audit log entries mentioning '{}' 🛡\n\n", records.len(), filter),
+        None => format!("🛡 Latest {} audit log entries 🛡\n\n", records.len()),
+    };
+    for record in records {
+        response.push_str(&format!("#{} {} {}\n", record.id, record.created_at, record.entry));
+    }
+    send_long_message(&bot, msg.chat.id, &response).await?;
+
+    Ok(())
+}
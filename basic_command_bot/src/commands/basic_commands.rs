@@ -1,28 +1,44 @@
 /// basic_commands.rs
 
 use teloxide::{prelude::*, utils::command::BotCommands};
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
 use std::{error::Error, sync::Arc};
-use crate::enums::{Command, AdminCommand, DevCommand};
-use crate::admin::{is_authorized_dev, is_authorized_sender};
+use crate::enums::{Command, AdminCommand, DevCommand, SeasonPhase, SeasonFormat};
+use crate::admin::{has_role, Role};
 
 use crate::database::{DbPool};
 
 use crate::commands::season::{
     current_active_season_details,
-    current_active_season_id,
+    season_allows_hand_change,
+    season_uses_commit_reveal,
+    season_pairing,
+    season_format,
+    season_best_of,
+    season_plays_series,
+    season_hand_set,
 };
-use rusqlite::{params };
+use crate::hands::HandSet;
+use rusqlite::{params, Error as RusqliteError};
 
 
 use crate::commands::playing_commands::{
-    insert_player_hand_choice,
     current_game_status_and_season_id,
-    check_player_in_game,
+    play_hand,
     get_current_round_id,
-    fetch_leaderboard,
-    prepare_leaderboard_string,
+    get_player_hand,
+    count_hand_submissions,
+    PlayOutcome,
 };
 
+use crate::commands::callback_commands::PLAY_CALLBACK_PREFIX;
+use crate::commands::commit_reveal_commands::get_commitment;
+use crate::commands::series_commands::series_status;
+use crate::commands::profile_commands::upsert_player;
+use crate::commands::leaderboard_commands::leaderboard_page;
+use crate::messages::send_page;
+use crate::scoring::season_scoring_rules;
+
 
 //
 //
@@ -32,10 +48,11 @@ use crate::commands::playing_commands::{
 
 
 pub async fn help(bot: Bot, msg: Message, db_pool: Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
-    if is_authorized_dev(&msg) {
+    let has = |role: Role| msg.from().is_some_and(|user| has_role(&db_pool, user, role));
+    if has(Role::Dev) {
         bot.send_message(msg.chat.id, DevCommand::descriptions().to_string()).await?;
     }
-    if is_authorized_sender(&msg, &db_pool) {
+    if has(Role::Moderator) {
         bot.send_message(msg.chat.id, AdminCommand::descriptions().to_string()).await?;
     }
     bot.send_message(msg.chat.id, Command::descriptions().to_string()).await?;
@@ -46,15 +63,16 @@ pub async fn signup_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Re
     // Check if there is an active season in the "start_signup" phase
     let season_details = current_active_season_details(db_pool).await?;
     if let Some((season_id, _, _, _, status)) = season_details {
-        if status == "start_signup" {
-            // Extract player details
+        if status == SeasonPhase::StartSignup {
+            // Extract player details. Players are known by their user id, admins approve them by username.
             let player_id = msg.from().expect("Message has no sender").id.0;
-            let player_username = msg.from()
-                                     .expect("Message has no sender")
-                                     .username
-                                     .as_ref() // Borrow the contents of the Option
-                                     .unwrap_or(&"unknown".to_string())
-                                     .to_string();
+            let player_username = match msg.from().and_then(|user| user.username.clone()) {
+                Some(username) => username,
+                None => {
+                    bot.send_message(msg.chat.id, "Please set a Telegram username first, the admins approve players by username.").await?;
+                    return Ok(());
+                }
+            };
 
             // Insert player into MasterCandidateTable
             let conn = db_pool.get().expect("Failed to get DB connection");
@@ -76,6 +94,7 @@ pub async fn signup_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Re
                 "INSERT INTO MasterCandidateTable (season_id, player_id, player_username, player_wallet, player_status) VALUES (?1, ?2, ?3, '', 'pending')",
                 params![season_id, player_id, player_username],
             )?;
+            upsert_player(&conn, player_id as i64, &player_username)?;
 
             bot.send_message(msg.chat.id, "You have successfully signed up to the waitinglist for the game!").await?;
         } else {
@@ -97,15 +116,14 @@ pub async fn version_command(bot: Bot, msg: Message) -> Result<(), Box<dyn Error
 
 // TODO
 pub async fn viewleaderboard_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let current_season_id = current_active_season_id(db_pool).await?;
-
-    // Check if a current season ID is present and fetch the leaderboard accordingly
-    match current_season_id {
-        Some(season_id) => {
-            match fetch_leaderboard(db_pool, season_id).await {
-                Ok(leaderboard) => {
-                    let response = prepare_leaderboard_string(leaderboard).await;
-                    bot.send_message(msg.chat.id, &response).await?;
+    let current_season = current_active_season_details(db_pool).await?;
+
+    // Check if a current season is present and fetch the leaderboard accordingly
+    match current_season {
+        Some((season_id, name, _, _, _)) => {
+            match leaderboard_page(db_pool, season_id, &name, 0).await {
+                Ok(page) => {
+                    send_page(&bot, msg.chat.id, page).await?;
                 },
                 Err(_) => {
                     bot.send_message(msg.chat.id, "Failed to fetch the leaderboard.").await?;
@@ -113,142 +131,156 @@ pub async fn viewleaderboard_command(bot: Bot, msg: Message, db_pool: &Arc<DbPoo
             }
         },
         None => {
-            bot.send_message(msg.chat.id, "Leaderboards only work during active games. Check the main channel for logs").await?;
+            bot.send_message(msg.chat.id, "There is no active season. Use /seasons and '/leaderboard <season>' for the standings of past seasons.").await?;
         }
     }
     Ok(())
 }
 
 
+// The old per-hand commands are kept as aliases of /play
 pub async fn playrock_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
-    // Check if there is an active season in the "round_ongoing" phase and get the season_id
-    let game_status_and_season_id = current_game_status_and_season_id(db_pool).await?;
-    if let Some((game_status, season_id)) = game_status_and_season_id {
-        if game_status != "round_ongoing" {
-            bot.send_message(msg.chat.id, "There is no round currently ongoing.").await?;
-            return Ok(());
-        }
+    play_hand_command(bot, msg, db_pool, "rock").await
+}
 
-        // Extract player details
-        let player_id = msg.from().expect("Message has no sender").id.0;
+pub async fn playpaper_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    play_hand_command(bot, msg, db_pool, "paper").await
+}
 
-        // Check if the player is in the current game
-        let player_in_game = check_player_in_game(db_pool, player_id.try_into().unwrap(), season_id).await?;
-        if !player_in_game {
-            bot.send_message(msg.chat.id, "You are not part of the current game.").await?;
-            return Ok(());
-        }
+pub async fn playscissors_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    play_hand_command(bot, msg, db_pool, "scissors").await
+}
 
-        // Get the current round ID
-        let round_id = get_current_round_id(db_pool, season_id).await?;
-        if let Some(current_round_id) = round_id {
-            // Insert the player's choice into the RoundDetailsTable
-            let success = insert_player_hand_choice(db_pool, current_round_id, player_id.try_into().unwrap(), "rock").await?;
-            if success {
-                bot.send_message(msg.chat.id, "Playing the rock hand 🪨.").await?;
-            } else {
-                bot.send_message(msg.chat.id, "You have already played this round.").await?;
-            }
-        } else {
-            bot.send_message(msg.chat.id, "No active round found.").await?;
-        }
-    } else {
-        bot.send_message(msg.chat.id, "No active season found.").await?;
-    }
+async fn play_hand_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>, hand: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    // Extract player details
+    let player_id = msg.from().expect("Message has no sender").id.0 as i64;
+
+    let outcome = play_hand(&bot, db_pool, player_id, hand).await?;
+    bot.send_message(msg.chat.id, outcome.message()).await?;
 
     Ok(())
 }
 
+pub async fn myhand_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let player_id = msg.from().expect("Message has no sender").id.0 as i64;
 
-pub async fn playpaper_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
-    // Check if there is an active season in the "round_ongoing" phase and get the season_id
-    let game_status_and_season_id = current_game_status_and_season_id(db_pool).await?;
-    if let Some((game_status, season_id)) = game_status_and_season_id {
-        if game_status != "round_ongoing" {
-            bot.send_message(msg.chat.id, "There is no round currently ongoing.").await?;
+    let season_id = match current_game_status_and_season_id(db_pool).await? {
+        Some((SeasonPhase::RoundOngoing, season_id)) => season_id,
+        Some(_) => {
+            bot.send_message(msg.chat.id, PlayOutcome::NoRoundOngoing.message()).await?;
             return Ok(());
         }
-
-        // Extract player details
-        let player_id = msg.from().expect("Message has no sender").id.0;
-
-        // Check if the player is in the current game
-        let player_in_game = check_player_in_game(db_pool, player_id.try_into().unwrap(), season_id).await?;
-        if !player_in_game {
-            bot.send_message(msg.chat.id, "You are not part of the current game.").await?;
+        None => {
+            bot.send_message(msg.chat.id, PlayOutcome::NoActiveSeason.message()).await?;
+            return Ok(());
+        }
+    };
+    let round_id = match get_current_round_id(db_pool, season_id).await? {
+        Some(round_id) => round_id,
+        None => {
+            bot.send_message(msg.chat.id, PlayOutcome::NoActiveRound.message()).await?;
             return Ok(());
         }
+    };
+
+    if season_uses_commit_reveal(db_pool, season_id).await? {
+        let response = match get_commitment(db_pool, round_id, player_id).await? {
+            Some(commitment) => format!("Your commitment for this round: {}\nReveal the matching hand and nonce with /reveal once the round closes.", commitment),
+            None => "You haven't committed to a hand this round yet. Send /commit to see how.".to_string(),
+        };
+        bot.send_message(msg.chat.id, response).await?;
+        return Ok(());
+    }
+
+    let hand_set = season_hand_set(db_pool, season_id).await?;
+    if season_plays_series(db_pool, season_id).await? {
+        let best_of = season_best_of(db_pool, season_id).await?;
+        bot.send_message(msg.chat.id, series_status(db_pool, &hand_set, round_id, player_id, best_of).await?).await?;
+        return Ok(());
+    }
 
-        // Get the current round ID
-        let round_id = get_current_round_id(db_pool, season_id).await?;
-        if let Some(current_round_id) = round_id {
-            // Insert the player's choice into the RoundDetailsTable
-            let success = insert_player_hand_choice(db_pool, current_round_id, player_id.try_into().unwrap(), "paper").await?;
-            if success {
-                bot.send_message(msg.chat.id, "Playing the paper hand 📜.").await?;
-            } else {
-                bot.send_message(msg.chat.id, "You have already played this round.").await?;
+    let response = match get_player_hand(db_pool, round_id, player_id).await? {
+        Some(hand) => {
+            let submissions = count_hand_submissions(db_pool, round_id, player_id).await?;
+            let mut response = format!("Your hand for this round: {}", hand_set.label(&hand));
+            if submissions > 1 {
+                response.push_str(&format!(" (changed {} times)", submissions - 1));
             }
-        } else {
-            bot.send_message(msg.chat.id, "No active round found.").await?;
+            if season_allows_hand_change(db_pool, season_id).await? {
+                response.push_str("\nYou can still change it with /play until the round closes.");
+            }
+            response
         }
-    } else {
-        bot.send_message(msg.chat.id, "No active season found.").await?;
-    }
+        None => "You haven't played a hand this round yet. Use /play.".to_string(),
+    };
+    bot.send_message(msg.chat.id, response).await?;
 
     Ok(())
 }
 
-
-pub async fn playscissors_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
-    // Check if there is an active season in the "round_ongoing" phase and get the season_id
-    let game_status_and_season_id = current_game_status_and_season_id(db_pool).await?;
-    if let Some((game_status, season_id)) = game_status_and_season_id {
-        if game_status != "round_ongoing" {
-            bot.send_message(msg.chat.id, "There is no round currently ongoing.").await?;
-            return Ok(());
+// Send the hand picker. The choice comes back as a callback query, see callback_commands.rs
+pub async fn play_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    match current_game_status_and_season_id(db_pool).await? {
+        Some((SeasonPhase::RoundOngoing, season_id)) if season_uses_commit_reveal(db_pool, season_id).await? => {
+            bot.send_message(msg.chat.id, PlayOutcome::CommitRevealOnly.message()).await?;
         }
-
-        // Extract player details
-        let player_id = msg.from().expect("Message has no sender").id.0;
-
-        // Check if the player is in the current game
-        let player_in_game = check_player_in_game(db_pool, player_id.try_into().unwrap(), season_id).await?;
-        if !player_in_game {
-            bot.send_message(msg.chat.id, "You are not part of the current game.").await?;
-            return Ok(());
+        Some((SeasonPhase::RoundOngoing, season_id)) => {
+            let hand_set = season_hand_set(db_pool, season_id).await?;
+            bot.send_message(msg.chat.id, "Pick your hand:")
+                .reply_markup(hand_keyboard(&hand_set))
+                .await?;
         }
-
-        // Get the current round ID
-        let round_id = get_current_round_id(db_pool, season_id).await?;
-        if let Some(current_round_id) = round_id {
-            // Insert the player's choice into the RoundDetailsTable
-            let success = insert_player_hand_choice(db_pool, current_round_id, player_id.try_into().unwrap(), "scissors").await?;
-            if success {
-                bot.send_message(msg.chat.id, "Playing the scissors hand ✂.").await?;
-            } else {
-                bot.send_message(msg.chat.id, "You have already played this round.").await?;
-            }
-        } else {
-            bot.send_message(msg.chat.id, "No active round found.").await?;
+        Some(_) => {
+            bot.send_message(msg.chat.id, PlayOutcome::NoRoundOngoing.message()).await?;
+        }
+        None => {
+            bot.send_message(msg.chat.id, PlayOutcome::NoActiveSeason.message()).await?;
         }
-    } else {
-        bot.send_message(msg.chat.id, "No active season found.").await?;
     }
 
     Ok(())
 }
 
+// One button per hand of the season, at most 5 to a row
+pub fn hand_keyboard(hand_set: &HandSet) -> InlineKeyboardMarkup {
+    let buttons = hand_set
+        .hands
+        .iter()
+        .map(|hand| InlineKeyboardButton::callback(hand.emoji.clone(), format!("{}{}", PLAY_CALLBACK_PREFIX, hand.name)))
+        .collect::<Vec<_>>();
+    InlineKeyboardMarkup::new(buttons.chunks(5).map(|row| row.to_vec()))
+}
+
+// How the season is played, for /status and /currentseasonstatus
+pub async fn season_rules_text(db_pool: &DbPool, season_id: i32) -> Result<String, RusqliteError> {
+    let mut text = match season_format(db_pool, season_id).await? {
+        SeasonFormat::League => format!("\nFormat: league, pairing: {}", season_pairing(db_pool, season_id).await?),
+        SeasonFormat::Knockout => "\nFormat: knockout, see /bracket".to_string(),
+    };
+    text.push_str(&format!("\nHands: {}", season_hand_set(db_pool, season_id).await?));
+    let best_of = season_best_of(db_pool, season_id).await?;
+    if best_of > 1 {
+        text.push_str(&format!("\nMatches are best of {}: the bot DMs you your opponent and asks for each throw.", best_of));
+    } else if season_plays_series(db_pool, season_id).await? {
+        text.push_str("\nThe bot DMs you your opponent and asks for your throw, a draw is thrown again.");
+    }
+    if season_uses_commit_reveal(db_pool, season_id).await? {
+        text.push_str("\nHands are played with /commit and revealed with /reveal after each round.");
+    }
+    text.push_str(&format!("\nScoring: {}", season_scoring_rules(db_pool, season_id).await?));
+    Ok(text)
+}
 
 pub async fn status_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
 
     // Check if there is an active season
     match current_active_season_details(db_pool).await {
-        Ok(Some((_, name, start_date, max_players, status))) => {
-            let message = format!(
+        Ok(Some((season_id, name, start_date, max_players, status))) => {
+            let mut message = format!(
                 "Current active season: '{}'\nStarted on: {}\nMax players: {}\nStatus: {}",
                 name, start_date, max_players, status
             );
+            message.push_str(&season_rules_text(db_pool, season_id).await?);
             bot.send_message(msg.chat.id, message).await?;
         },
         Ok(None) => {
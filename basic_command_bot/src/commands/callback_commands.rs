@@ -0,0 +1,48 @@
+/// callback_commands.rs
+
+use teloxide::{prelude::* };
+use std::{error::Error, sync::Arc};
+use crate::database::{DbPool};
+
+use crate::commands::playing_commands::play_hand;
+use crate::commands::history_commands::{history_callback, HISTORY_CALLBACK_PREFIX};
+use crate::commands::leaderboard_commands::{leaderboard_callback, LEADERBOARD_CALLBACK_PREFIX};
+use crate::commands::role_commands::{revoke_callback, REVOKE_CALLBACK_PREFIX};
+
+// Callback data is "<prefix><value>", e.g. "play:rock"
+pub const PLAY_CALLBACK_PREFIX: &str = "play:";
+
+// Entry point for every inline keyboard button press
+pub async fn handle_callback_query(bot: Bot, q: CallbackQuery, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let data = q.data.clone().unwrap_or_default();
+
+    if let Some(hand) = data.strip_prefix(PLAY_CALLBACK_PREFIX) {
+        play_hand_callback(bot, q, db_pool, hand.to_string()).await
+    } else if let Some(history) = data.strip_prefix(HISTORY_CALLBACK_PREFIX) {
+        history_callback(bot, q, db_pool, history).await
+    } else if let Some(leaderboard) = data.strip_prefix(LEADERBOARD_CALLBACK_PREFIX) {
+        leaderboard_callback(bot, q, db_pool, leaderboard).await
+    } else if let Some(revoke) = data.strip_prefix(REVOKE_CALLBACK_PREFIX) {
+        revoke_callback(bot, q, db_pool, revoke).await
+    } else {
+        log::warn!("Unknown callback data '{}' from {}", data, q.from.id);
+        bot.answer_callback_query(q.id).await?;
+        Ok(())
+    }
+}
+
+// play_hand checks the hand against the season's set, the keyboard may be from an older season
+async fn play_hand_callback(bot: Bot, q: CallbackQuery, db_pool: &DbPool, hand: String) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let player_id = q.from.id.0 as i64;
+    let response = play_hand(&bot, db_pool, player_id, &hand).await?.message();
+
+    bot.answer_callback_query(q.id).text(response.clone()).await?;
+
+    // In a private chat the keyboard is replaced with the confirmation, so the buttons can't be pressed again.
+    // A group keyboard is shared by every player, it stays and the toast is the only answer.
+    if let Some(message) = q.message.filter(|message| message.chat.is_private()) {
+        bot.edit_message_text(message.chat.id, message.id, response).await?;
+    }
+
+    Ok(())
+}
@@ -3,8 +3,7 @@ use std::error::Error;
 use std::env;
 use std::path::PathBuf;
 use teloxide::{prelude::* };
-
-const MAX_MESSAGE_LENGTH: usize = 4096;
+use crate::messages::{split_message, MAX_MESSAGE_LENGTH};
 
 pub async fn send_changelog(bot: Bot, msg: Message) -> Result<(), Box<dyn Error + Send + Sync>> {
     let mut changelog_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -26,11 +25,10 @@ pub async fn send_changelog(bot: Bot, msg: Message) -> Result<(), Box<dyn Error
         escaped_string
     }
 
-    // Split the changelog into chunks of MAX_MESSAGE_LENGTH
-    for chunk in changelog.as_bytes().chunks(MAX_MESSAGE_LENGTH) {
-        let message = String::from_utf8_lossy(chunk);
-        let escaped_message = escape_characters(&message, characters_to_escape);
-        bot.send_message(msg.chat.id, escaped_message.to_string())
+    // Escape first, escaping makes the text longer. Then split between lines into messages that fit.
+    let escaped_changelog = escape_characters(&changelog, characters_to_escape);
+    for escaped_message in split_message(&escaped_changelog, MAX_MESSAGE_LENGTH) {
+        bot.send_message(msg.chat.id, escaped_message)
             .parse_mode(teloxide::types::ParseMode::MarkdownV2)
             .await?;
     }
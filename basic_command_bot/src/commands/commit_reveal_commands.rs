@@ -0,0 +1,314 @@
+/// commit_reveal_commands.rs
+
+use teloxide::{prelude::* };
+use std::{error::Error, sync::Arc};
+use crate::database::{DbPool};
+use crate::enums::SeasonPhase;
+use crate::config;
+use rusqlite::{params, OptionalExtension, Error as RusqliteError};
+use sha2::{Digest, Sha256};
+
+use crate::commands::season::{
+    season_allows_hand_change,
+    season_uses_commit_reveal,
+    season_hand_set,
+    start_reveal_phase,
+};
+
+use crate::commands::playing_commands::{
+    current_game_status_and_season_id,
+    check_player_in_game,
+    get_current_round_id,
+    HandChoice,
+    PlayOutcome,
+};
+use crate::hands::HandSet;
+
+use crate::commands::schedule_commands::format_duration;
+
+const COMMIT_USAGE: &str = "This season uses commit–reveal: nobody, admins included, can see your hand before the round closes.
+
+1. Pick a hand and a secret nonce, e.g. rock and some-long-random-text
+2. Send /commit <hash>, where the hash is the sha256 of <hand>:<nonce>, e.g. printf 'rock:some-long-random-text' | sha256sum
+3. Once the round closes, send /reveal rock some-long-random-text
+
+A missing or wrong reveal counts as a forfeit.";
+
+// The commitment for a hand is the hex sha256 of "<hand>:<nonce>"
+pub fn hand_commitment(hand: &str, nonce: &str) -> String {
+    format!("{:x}", Sha256::digest(format!("{}:{}", hand, nonce).as_bytes()))
+}
+
+// A player's commitment for a round, and what they revealed
+pub struct Commitment {
+    pub player_id: i64,
+    pub commitment: String,
+    pub revealed_hand: Option<String>,
+    pub nonce: Option<String>,
+}
+
+impl Commitment {
+    // The revealed hand, but only if it is a hand of the season and hashes to the commitment
+    pub fn verified_hand(&self, hand_set: &HandSet) -> Option<&str> {
+        match (&self.revealed_hand, &self.nonce) {
+            (Some(hand), Some(nonce)) if hand_set.contains(hand) && hand_commitment(hand, nonce) == self.commitment => Some(hand),
+            _ => None,
+        }
+    }
+}
+
+pub async fn commit_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>, commitment: String) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let player_id = msg.from().expect("Message has no sender").id.0 as i64;
+
+    let season_id = match current_game_status_and_season_id(db_pool).await? {
+        Some((_, season_id)) if !season_uses_commit_reveal(db_pool, season_id).await? => {
+            bot.send_message(msg.chat.id, "This season doesn't use commit–reveal, play your hand with /play.").await?;
+            return Ok(());
+        }
+        Some((SeasonPhase::RoundOngoing, season_id)) => season_id,
+        Some(_) => {
+            bot.send_message(msg.chat.id, PlayOutcome::NoRoundOngoing.message()).await?;
+            return Ok(());
+        }
+        None => {
+            bot.send_message(msg.chat.id, PlayOutcome::NoActiveSeason.message()).await?;
+            return Ok(());
+        }
+    };
+
+    let commitment = commitment.trim().to_lowercase();
+    if commitment.is_empty() {
+        bot.send_message(msg.chat.id, COMMIT_USAGE).await?;
+        return Ok(());
+    }
+    if commitment.len() != 64 || !commitment.chars().all(|c| c.is_ascii_hexdigit()) {
+        bot.send_message(msg.chat.id, format!("That doesn't look like a sha256 hash, it should be 64 hex characters.\n\n{}", COMMIT_USAGE)).await?;
+        return Ok(());
+    }
+
+    if !check_player_in_game(db_pool, player_id, season_id).await? {
+        bot.send_message(msg.chat.id, PlayOutcome::NotInGame.message()).await?;
+        return Ok(());
+    }
+    let round_id = match get_current_round_id(db_pool, season_id).await? {
+        Some(round_id) => round_id,
+        None => {
+            bot.send_message(msg.chat.id, PlayOutcome::NoActiveRound.message()).await?;
+            return Ok(());
+        }
+    };
+
+    let allow_change = season_allows_hand_change(db_pool, season_id).await?;
+    let response = match insert_commitment(db_pool, round_id, player_id, &commitment, allow_change).await? {
+        HandChoice::Inserted => format!("Commitment received: {}\nKeep your hand and nonce, you need them to /reveal once the round closes.", commitment),
+        HandChoice::Changed => format!("Commitment replaced: {}\nOnly this one counts, keep the matching hand and nonce.", commitment),
+        HandChoice::Rejected => "You have already committed to a hand this round.".to_string(),
+    };
+    bot.send_message(msg.chat.id, response).await?;
+
+    Ok(())
+}
+
+pub async fn reveal_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>, args: String) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let player_id = msg.from().expect("Message has no sender").id.0 as i64;
+
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    if parts.len() != 2 {
+        bot.send_message(msg.chat.id, "The command should be used like this '/reveal <hand> <nonce>', e.g. '/reveal rock some-long-random-text'.").await?;
+        return Ok(());
+    }
+    let hand = parts[0].to_lowercase();
+    let nonce = parts[1];
+
+    let season_id = match current_game_status_and_season_id(db_pool).await? {
+        Some((SeasonPhase::RoundRevealing, season_id)) => season_id,
+        Some((SeasonPhase::RoundOngoing, _)) => {
+            bot.send_message(msg.chat.id, "The round is still open. Reveal your hand once it closes.").await?;
+            return Ok(());
+        }
+        Some(_) => {
+            bot.send_message(msg.chat.id, "There is no round waiting for reveals.").await?;
+            return Ok(());
+        }
+        None => {
+            bot.send_message(msg.chat.id, PlayOutcome::NoActiveSeason.message()).await?;
+            return Ok(());
+        }
+    };
+    let round_id = match get_current_round_id(db_pool, season_id).await? {
+        Some(round_id) => round_id,
+        None => {
+            bot.send_message(msg.chat.id, PlayOutcome::NoActiveRound.message()).await?;
+            return Ok(());
+        }
+    };
+
+    let commitment = match get_commitment(db_pool, round_id, player_id).await? {
+        Some(commitment) => commitment,
+        None => {
+            bot.send_message(msg.chat.id, "You didn't commit to a hand this round.").await?;
+            return Ok(());
+        }
+    };
+
+    let hand_set = season_hand_set(db_pool, season_id).await?;
+    let response = if !hand_set.contains(&hand) {
+        format!("'{}' is not a hand, pick one of {}.", hand, hand_set.names().join(", "))
+    } else if hand_commitment(&hand, nonce) != commitment {
+        format!("{}:{} does not match your commitment {}. Check the hand and nonce and try again before the reveal window closes.", hand, nonce, commitment)
+    } else {
+        record_reveal(db_pool, round_id, player_id, &hand, nonce).await?;
+        format!("Revealed {}, it matches your commitment ✅", hand_set.label(&hand))
+    };
+    bot.send_message(msg.chat.id, response).await?;
+
+    Ok(())
+}
+
+// Close a commit–reveal round for new commitments and ask everyone who committed to reveal.
+// Returns the announcement for the group.
+pub async fn open_reveal_window(bot: &Bot, db_pool: &DbPool, season_id: i32) -> Result<String, Box<dyn Error + Send + Sync>> {
+    let round_id = get_current_round_id(db_pool, season_id)
+        .await?
+        .ok_or("No current round ID found")?;
+
+    let reveal_secs = config::get().commit_reveal.reveal_window_secs;
+    start_reveal_phase(db_pool, season_id, reveal_secs).await?;
+
+    let window = format_duration(reveal_secs);
+    for commitment in get_round_commitments(db_pool, round_id).await? {
+        let request = format!("🔓 The round is closed. Reveal your hand within {} with /reveal <hand> <nonce>, otherwise it counts as a forfeit.", window);
+        if let Err(e) = bot.send_message(ChatId(commitment.player_id), request).await {
+            log::warn!("Could not ask player {} to reveal: {}", commitment.player_id, e);
+        }
+    }
+
+    Ok(format!("Round is closed for commitments! Players have {} to /reveal their hands.", window))
+}
+
+// Turn the reveals into hands in RoundDetailsTable. A missing or mismatched reveal becomes an
+// empty hand, which evaluate_matches scores as a forfeit. The hash is checked again here, so a
+// reveal edited in the database doesn't count either.
+// Returns the forfeiting players and the commitments to publish with the results.
+// All hands go in together on one connection, or none do.
+pub async fn apply_reveals(db_pool: &DbPool, hand_set: &HandSet, round_id: i32) -> Result<(Vec<i64>, String), RusqliteError> {
+    let commitments = get_round_commitments(db_pool, round_id).await?;
+    let mut forfeited = Vec::new();
+    let mut report = String::new();
+    if commitments.is_empty() {
+        return Ok((forfeited, report));
+    }
+
+    report.push_str("🔐 Commitments, check them with sha256(<hand>:<nonce>):\n");
+    let mut conn = db_pool.get().expect("Failed to get DB connection");
+    let tx = conn.transaction()?;
+    for commitment in &commitments {
+        let hand = commitment.verified_hand(hand_set).unwrap_or("");
+        tx.execute(
+            "INSERT INTO RoundDetailsTable (round_id, player_id, player_hand, timestamp) VALUES (?1, ?2, ?3, CURRENT_TIMESTAMP)",
+            params![round_id, commitment.player_id, hand],
+        )?;
+
+        let username: String = tx.query_row(
+            "SELECT player_username FROM PlayerDetailsTable WHERE player_id = ?1",
+            params![commitment.player_id],
+            |row| row.get(0),
+        )?;
+        let line = match commitment.verified_hand(hand_set) {
+            Some(hand) => format!("@{} {} ✅ {}:{}", username, commitment.commitment, hand, commitment.nonce.as_deref().unwrap_or_default()),
+            None => {
+                forfeited.push(commitment.player_id);
+                if commitment.revealed_hand.is_some() {
+                    format!("@{} {} ❌ reveal does not match, forfeit", username, commitment.commitment)
+                } else {
+                    format!("@{} {} ❌ not revealed, forfeit", username, commitment.commitment)
+                }
+            }
+        };
+        report.push_str(&line);
+        report.push('\n');
+    }
+    tx.commit()?;
+
+    Ok((forfeited, report))
+}
+
+
+// Store a commitment. A second one replaces the first only if allow_change is set.
+pub async fn insert_commitment(db_pool: &DbPool, round_id: i32, player_id: i64, commitment: &str, allow_change: bool) -> Result<HandChoice, RusqliteError> {
+    let mut conn = db_pool.get().expect("Failed to get DB connection");
+    let tx = conn.transaction()?;
+
+    let exists: i64 = tx.query_row(
+        "SELECT COUNT(*) FROM hand_commitments WHERE round_id = ?1 AND player_id = ?2",
+        params![round_id, player_id],
+        |row| row.get(0),
+    )?;
+
+    let choice = if exists == 0 {
+        tx.execute(
+            "INSERT INTO hand_commitments (round_id, player_id, commitment, committed_at) VALUES (?1, ?2, ?3, CURRENT_TIMESTAMP)",
+            params![round_id, player_id, commitment],
+        )?;
+        HandChoice::Inserted
+    } else if allow_change {
+        tx.execute(
+            "UPDATE hand_commitments SET commitment = ?3, committed_at = CURRENT_TIMESTAMP WHERE round_id = ?1 AND player_id = ?2",
+            params![round_id, player_id, commitment],
+        )?;
+        HandChoice::Changed
+    } else {
+        return Ok(HandChoice::Rejected);
+    };
+
+    tx.commit()?;
+    Ok(choice)
+}
+
+pub async fn get_commitment(db_pool: &DbPool, round_id: i32, player_id: i64) -> Result<Option<String>, RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    conn.query_row(
+        "SELECT commitment FROM hand_commitments WHERE round_id = ?1 AND player_id = ?2",
+        params![round_id, player_id],
+        |row| row.get(0),
+    ).optional()
+}
+
+pub async fn record_reveal(db_pool: &DbPool, round_id: i32, player_id: i64, hand: &str, nonce: &str) -> Result<(), RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    conn.execute(
+        "UPDATE hand_commitments SET revealed_hand = ?3, nonce = ?4, revealed_at = CURRENT_TIMESTAMP WHERE round_id = ?1 AND player_id = ?2",
+        params![round_id, player_id, hand, nonce],
+    )?;
+    Ok(())
+}
+
+pub async fn get_round_commitments(db_pool: &DbPool, round_id: i32) -> Result<Vec<Commitment>, RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    let mut stmt = conn.prepare(
+        "SELECT player_id, commitment, revealed_hand, nonce FROM hand_commitments WHERE round_id = ?1 ORDER BY committed_at",
+    )?;
+    let commitments = stmt.query_map(params![round_id], |row| {
+        Ok(Commitment {
+            player_id: row.get(0)?,
+            commitment: row.get(1)?,
+            revealed_hand: row.get(2)?,
+            nonce: row.get(3)?,
+        })
+    })?
+    .collect::<Result<Vec<Commitment>, RusqliteError>>()?;
+
+    Ok(commitments)
+}
+
+// The round of a season whose reveal window has passed, if any
+pub async fn get_expired_reveal_round_id(db_pool: &DbPool, season_id: i32) -> Result<Option<i32>, RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    conn.query_row(
+        "SELECT id FROM MasterRoundTable
+         WHERE season_id = ?1 AND end_time IS NULL AND reveal_deadline IS NOT NULL AND reveal_deadline <= CURRENT_TIMESTAMP
+         ORDER BY id DESC LIMIT 1",
+        params![season_id],
+        |row| row.get(0),
+    ).optional()
+}
@@ -2,8 +2,8 @@
 
 use teloxide::{prelude::*};
 use std::{error::Error};
-use crate::admin::{is_authorized_dev};
 use crate::database::{DbPool, write_to_db, read_from_db};
+use crate::audit::fail;
 use rusqlite::{ Error as RusqliteError};
 
 use std::sync::Arc;
@@ -17,9 +17,6 @@ use std::sync::Arc;
 
 
 pub async fn username_command(bot: Bot, msg: Message) -> Result<(), Box<dyn Error + Send + Sync>> {
-    if !is_authorized_dev(&msg) { return Ok(());} //check is dev
-                                                  //
-                                                  //
     if let Some(username) = msg.from().and_then(|user| user.username.clone()) {
         bot.send_message(msg.chat.id, format!("Your username is @{}.", username)).await?;
     } else {
@@ -29,15 +26,9 @@ pub async fn username_command(bot: Bot, msg: Message) -> Result<(), Box<dyn Erro
 }
 
 pub async fn username_and_age_command(bot: Bot, msg: Message) -> Result<(), Box<dyn Error + Send + Sync>> {
-    if !is_authorized_dev(&msg) { return Ok(());} //check is dev
-                                                  //
-                                                  //
-    if let Some(requester_username) = msg.from().and_then(|user| user.username.clone()) {
-        if requester_username != "juno0x153" {
-            bot.send_message(msg.chat.id, "You are not authorized to use this command.").await?;
-        } else {
-            bot.send_message(msg.chat.id, "Your username is valid.").await?;
-        }
+    // Only devs get this far, see Command::min_role
+    if msg.from().is_some() {
+        bot.send_message(msg.chat.id, "Your username is valid.").await?;
     } else {
         bot.send_message(msg.chat.id, "Unable to retrieve your username.").await?;
     }
@@ -45,9 +36,6 @@ pub async fn username_and_age_command(bot: Bot, msg: Message) -> Result<(), Box<
 }
 
 pub async fn write_sql_command(bot: Bot, msg: Message, db_pool: Arc<DbPool>, value: String) -> Result<(), Box<dyn Error + Send + Sync>> {
-    if !is_authorized_dev(&msg) { return Ok(());} //check is dev
-                                                  //
-                                                  //
     match write_to_db(&db_pool, &value).await {
         Ok(_) => {
             if let Err(e) = bot.send_message(msg.chat.id, "Successfully written to database").await {
@@ -55,7 +43,7 @@ pub async fn write_sql_command(bot: Bot, msg: Message, db_pool: Arc<DbPool>, val
             }
         },
         Err(e) => {
-            if let Err(e) = bot.send_message(msg.chat.id, format!("Error writing to database: {}", e)).await {
+            if let Err(e) = fail(&bot, msg.chat.id, format!("Error writing to database: {}", e)).await {
                 log::error!("Failed to send message: {}", e);
             }
         },
@@ -64,9 +52,6 @@ pub async fn write_sql_command(bot: Bot, msg: Message, db_pool: Arc<DbPool>, val
 }
 
 pub async fn read_sql_command(bot: Bot, msg: Message, db_pool: Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
-    if !is_authorized_dev(&msg) { return Ok(());} //check is dev
-                                                  //
-                                                  //
     match read_from_db(&db_pool).await {
         Ok(value) => {
             // Handle the case when reading from the database succeeds
@@ -88,7 +73,6 @@ pub async fn read_sql_command(bot: Bot, msg: Message, db_pool: Arc<DbPool>) -> R
 
 
 pub async fn admin_reset_players_command(bot: Bot, msg: Message, db_pool: Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
-    if !is_authorized_dev(&msg) { return Ok(());} //check is dev
     // Call the function to clear or drop the PlayerDetailsTable
     clear_player_details_table(&db_pool).await?;
     // or use drop_player_details_table(db_pool).await?; to drop the table
@@ -99,8 +83,6 @@ pub async fn admin_reset_players_command(bot: Bot, msg: Message, db_pool: Arc<Db
 }
 
 pub async fn admin_reset_candidate_command(bot: Bot, msg: Message, db_pool: Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
-    if !is_authorized_dev(&msg) { return Ok(());} //check is dev
-
     // Call the function to clear or drop the PlayerDetailsTable
     clear_master_candidate_table(&db_pool).await?;
     // or use drop_player_details_table(db_pool).await?; to drop the table
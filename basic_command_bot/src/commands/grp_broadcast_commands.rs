@@ -3,64 +3,50 @@
 use teloxide::{prelude::* };
 use std::{error::Error, sync::Arc};
 use crate::database::{DbPool};
+use crate::audit::{fail, refuse};
 use rusqlite::{Error as RusqliteError};
 
-use crate::admin::{
-    is_authorized_sender, 
-};
-
 
 
 // Add the necessary imports and any additional dependencies you might need
 
 pub async fn set_broadcast_channel_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>, channel_id: String) -> Result<(), Box<dyn Error + Send + Sync>> {
-    // Check if the user is authorized
-    if !is_authorized_sender(&msg, db_pool) {
-        return Ok(());
-    }
-
     let channel_id = channel_id.trim();
     if channel_id.split_whitespace().count() != 1 || !channel_id.chars().all(char::is_numeric) {
-        bot.send_message(msg.chat.id, "The command should be used like this '/setbroadcastchannel <ChannelID>'. Make sure the ChannelID is a numeric value. Contact the dev if you need help.").await?;
+        refuse(&bot, msg.chat.id, "The command should be used like this '/setbroadcastchannel <ChannelID>'. Make sure the ChannelID is a numeric value. Contact the dev if you need help.").await?;
         return Ok(());
     }
 
     // Call the database function to set the channel ID
     match set_broadcast_channel_id(db_pool, &channel_id).await {
-        Ok(_) => bot.send_message(msg.chat.id, format!("Broadcast channel set to: {}", channel_id)).await?,
-        Err(e) => bot.send_message(msg.chat.id, format!("Failed to set broadcast channel: {}", e)).await?,
+        Ok(_) => {
+            bot.send_message(msg.chat.id, format!("Broadcast channel set to: {}", channel_id)).await?;
+        }
+        Err(e) => fail(&bot, msg.chat.id, format!("Failed to set broadcast channel: {}", e)).await?,
     };
 
     Ok(())
 }
 
 pub async fn set_group_channel_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>, channel_id: String) -> Result<(), Box<dyn Error + Send + Sync>> {
-    // Check if the user is authorized
-    if !is_authorized_sender(&msg, db_pool) {
-        return Ok(());
-    }
-
     let channel_id = channel_id.trim();
     if channel_id.split_whitespace().count() != 1 || !channel_id.chars().all(char::is_numeric) {
-        bot.send_message(msg.chat.id, "The command should be used like this '/setgroupchannel <ChannelID>'. Make sure the ChannelID is a numeric value. Contact the dev if you need help.").await?;
+        refuse(&bot, msg.chat.id, "The command should be used like this '/setgroupchannel <ChannelID>'. Make sure the ChannelID is a numeric value. Contact the dev if you need help.").await?;
         return Ok(());
     }
 
     // Call the database function to set the channel ID
     match set_group_channel_id(db_pool, &channel_id).await {
-        Ok(_) => bot.send_message(msg.chat.id, format!("Group channel set to: {}", channel_id)).await?,
-        Err(e) => bot.send_message(msg.chat.id, format!("Failed to set group channel: {}", e)).await?,
+        Ok(_) => {
+            bot.send_message(msg.chat.id, format!("Group channel set to: {}", channel_id)).await?;
+        }
+        Err(e) => fail(&bot, msg.chat.id, format!("Failed to set group channel: {}", e)).await?,
     };
 
     Ok(())
 }
 
 pub async fn get_group_broadcast_id_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
-    // Check if the user is authorized
-    if !is_authorized_sender(&msg, db_pool) {
-        return Ok(());
-    }
-
     // Retrieve the channel IDs from the database
     match get_group_broadcast_ids(db_pool).await {
         Ok((broadcast_id, group_id)) => {
@@ -72,7 +58,7 @@ pub async fn get_group_broadcast_id_command(bot: Bot, msg: Message, db_pool: &Ar
             bot.send_message(msg.chat.id, message).await?;
         },
         Err(e) => {
-            bot.send_message(msg.chat.id, format!("Failed to retrieve channel IDs: {}", e)).await?;
+            fail(&bot, msg.chat.id, format!("Failed to retrieve channel IDs: {}", e)).await?;
         }
     }
 
@@ -80,18 +66,13 @@ pub async fn get_group_broadcast_id_command(bot: Bot, msg: Message, db_pool: &Ar
 }
 
 pub async fn reset_group_broadcast_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
-    // Check if the user is authorized
-    if !is_authorized_sender(&msg, db_pool) {
-        return Ok(());
-    }
-
     // Reset the channel settings in the database
     match reset_group_broadcast(db_pool).await {
         Ok(_) => {
             bot.send_message(msg.chat.id, "Group and broadcast channel settings have been reset.").await?;
         },
         Err(e) => {
-            bot.send_message(msg.chat.id, format!("Failed to reset channel settings: {}", e)).await?;
+            fail(&bot, msg.chat.id, format!("Failed to reset channel settings: {}", e)).await?;
         }
     }
 
@@ -128,6 +109,13 @@ pub async fn get_group_broadcast_ids(pool: &DbPool) -> Result<(Option<String>,Op
     }
 }
 
+// The broadcast and group channels as chat ids. Channel ids are stored without their minus sign.
+pub async fn get_group_broadcast_chat_ids(pool: &DbPool) -> Result<(Option<ChatId>, Option<ChatId>), RusqliteError> {
+    let (broadcast_id, group_id) = get_group_broadcast_ids(pool).await?;
+    let to_chat_id = |channel_id: Option<String>| channel_id.and_then(|id| id.parse::<i64>().ok()).map(|id| ChatId(-id));
+    Ok((to_chat_id(broadcast_id), to_chat_id(group_id)))
+}
+
 // Function to reset the group and broadcast channel settings
 pub async fn reset_group_broadcast(pool: &DbPool) -> Result<(), RusqliteError> {
     let conn = pool.get().expect("Failed to get connection from pool");
@@ -136,9 +124,6 @@ pub async fn reset_group_broadcast(pool: &DbPool) -> Result<(), RusqliteError> {
 }
 
 pub async fn msg_broadcastchannel_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>, message_text: String) -> Result<(), Box<dyn Error + Send + Sync>> {
-        if !is_authorized_sender(&msg, db_pool) {
-        return Ok(());  // Early return if the sender is not authorized
-    }
     // TODO no safety here. Doesn't say if it does not work. 
     let (broadcast_channel_id, _) = get_group_broadcast_ids(db_pool).await?;
     if let Some(channel_id_str) = broadcast_channel_id {
@@ -147,16 +132,13 @@ pub async fn msg_broadcastchannel_command(bot: Bot, msg: Message, db_pool: &Arc<
         bot.send_message(ChatId(channel_id), message_text).await?;
         bot.send_message(msg.chat.id, "Sends a message to the broadcast channel").await?;
     } else {
-        bot.send_message(msg.chat.id, "Broadcast channel is not set.").await?;
+        refuse(&bot, msg.chat.id, "Broadcast channel is not set.").await?;
     }
 
     Ok(())
 }
 
 pub async fn msg_group_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>, message_text: String) -> Result<(), Box<dyn Error + Send + Sync>> {
-    if !is_authorized_sender(&msg, db_pool) {
-        return Ok(());  // Early return if the sender is not authorized
-    }
     // Retrieve the group channel ID from the database
     let (_, group_channel_id) = get_group_broadcast_ids(db_pool).await?;
     
@@ -169,7 +151,7 @@ pub async fn msg_group_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>, me
         bot.send_message(ChatId(channel_id), message_text.clone()).await?;
         bot.send_message(msg.chat.id, "Sent the message to the group channel").await?;
     } else {
-        bot.send_message(msg.chat.id, "Group channel is not set.").await?;
+        refuse(&bot, msg.chat.id, "Group channel is not set.").await?;
     }
 
     Ok(())
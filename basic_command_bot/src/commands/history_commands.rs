@@ -0,0 +1,205 @@
+/// history_commands.rs
+
+use teloxide::prelude::*;
+use std::collections::HashMap;
+use std::{error::Error, sync::Arc};
+use rusqlite::{params, OptionalExtension, Error as RusqliteError};
+use crate::database::DbPool;
+use crate::hands::HandSet;
+use crate::messages::{edit_page, page, paginate, send_page, Page};
+use crate::commands::season::{find_season, season_hand_set};
+
+// Callback data is "history:<player_id>:<season_id>:<page>"
+pub const HISTORY_CALLBACK_PREFIX: &str = "history:";
+
+// Rounds per page, keeps the message short enough to read on a phone
+const HISTORY_PAGE_SIZE: usize = 10;
+
+// One scored round of a player
+pub struct HistoryEntry {
+    pub round_number: i32,
+    pub player_hand: String,
+    pub opponent_username: Option<String>,
+    pub opponent_hand: Option<String>,
+    pub game_status: String,
+    // NULL for rounds scored before points were stored per round
+    pub points: Option<i32>,
+}
+
+pub async fn history_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>, season: String) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let player_id = match msg.from() {
+        Some(user) => user.id.0 as i64,
+        None => return Ok(()),
+    };
+
+    let (season_id, season_name) = match find_season(db_pool, season.trim()).await? {
+        Some(season) => season,
+        None if season.trim().is_empty() => {
+            bot.send_message(msg.chat.id, "There are no seasons yet.").await?;
+            return Ok(());
+        }
+        None => {
+            bot.send_message(msg.chat.id, format!("No season '{}' found. Use '/history' for the current season or '/history <season name or id>'.", season.trim())).await?;
+            return Ok(());
+        }
+    };
+
+    let page = history_page(db_pool, player_id, season_id, &season_name, 0).await?;
+    send_page(&bot, msg.chat.id, page).await?;
+
+    Ok(())
+}
+
+// The previous/next buttons under a history message. Only the player whose history it is can page through it.
+pub async fn history_callback(bot: Bot, q: CallbackQuery, db_pool: &DbPool, data: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let parts: Vec<&str> = data.split(':').collect();
+    let (player_id, season_id, page) = match parts.as_slice() {
+        [player_id, season_id, page] => match (player_id.parse::<i64>(), season_id.parse::<i32>(), page.parse::<usize>()) {
+            (Ok(player_id), Ok(season_id), Ok(page)) => (player_id, season_id, page),
+            _ => {
+                bot.answer_callback_query(q.id).await?;
+                return Ok(());
+            }
+        },
+        _ => {
+            bot.answer_callback_query(q.id).await?;
+            return Ok(());
+        }
+    };
+
+    if q.from.id.0 as i64 != player_id {
+        bot.answer_callback_query(q.id).text("This is not your history, use /history to see yours.").await?;
+        return Ok(());
+    }
+
+    let season_name = match season_name(db_pool, season_id).await? {
+        Some(name) => name,
+        None => {
+            bot.answer_callback_query(q.id).text("That season no longer exists.").await?;
+            return Ok(());
+        }
+    };
+
+    let page = history_page(db_pool, player_id, season_id, &season_name, page).await?;
+    bot.answer_callback_query(q.id.clone()).await?;
+    edit_page(&bot, &q, page).await?;
+
+    Ok(())
+}
+
+// The text of one page and the buttons to the pages around it
+async fn history_page(
+    db_pool: &DbPool,
+    player_id: i64,
+    season_id: i32,
+    season_name: &str,
+    page_number: usize,
+) -> Result<Page, RusqliteError> {
+    let entries = get_player_history(db_pool, season_id, player_id).await?;
+    if entries.is_empty() {
+        return Ok(Page { text: format!("You have no scored rounds in season {}.", season_name), keyboard: None });
+    }
+
+    let hand_set = season_hand_set(db_pool, season_id).await?;
+    let header = format!("Your history in season {}\n{}\n", season_name, history_summary(&hand_set, &entries));
+    let lines: Vec<String> = entries.iter().map(|entry| history_line(&hand_set, entry)).collect();
+    let pages = paginate(&header, &lines, HISTORY_PAGE_SIZE);
+
+    Ok(page(&pages, page_number, |page_number| history_callback_data(player_id, season_id, page_number)))
+}
+
+fn history_callback_data(player_id: i64, season_id: i32, page: usize) -> String {
+    format!("{}{}:{}:{}", HISTORY_CALLBACK_PREFIX, player_id, season_id, page)
+}
+
+// "Round 3: rock 🪨 vs @bob's scissors ✂️, won (+2)"
+fn history_line(hand_set: &HandSet, entry: &HistoryEntry) -> String {
+    let points = entry.points.map(|points| format!(" ({:+})", points)).unwrap_or_default();
+    if entry.game_status == "bye" {
+        return format!("Round {}: bye{}", entry.round_number, points);
+    }
+
+    let player_hand = if entry.player_hand.is_empty() { "no hand 🚫".to_string() } else { hand_set.label(&entry.player_hand) };
+    let opponent_hand = match entry.opponent_hand.as_deref() {
+        None | Some("") => "no hand 🚫".to_string(),
+        Some(hand) => hand_set.label(hand),
+    };
+    let opponent = entry.opponent_username.as_deref().unwrap_or("unknown");
+    format!("Round {}: {} vs @{}'s {}, {}{}", entry.round_number, player_hand, opponent, opponent_hand, entry.game_status, points)
+}
+
+// Totals over all the rounds of the season, not just the current page
+fn history_summary(hand_set: &HandSet, entries: &[HistoryEntry]) -> String {
+    let count = |status: &str| entries.iter().filter(|entry| entry.game_status == status).count();
+    let (wins, draws, losses) = (count("won"), count("draw"), count("lost"));
+    let matches = wins + draws + losses;
+
+    let mut summary = format!("Won {}, drew {}, lost {}", wins, draws, losses);
+    let byes = count("bye");
+    if byes > 0 {
+        summary.push_str(&format!(", {} bye{}", byes, if byes == 1 { "" } else { "s" }));
+    }
+    if let Some(win_rate) = (wins * 100).checked_div(matches) {
+        summary.push_str(&format!("\nWin rate: {}%", win_rate));
+    }
+
+    // Ties go to the hand played first, so the answer doesn't change between calls
+    let mut hand_counts: HashMap<&str, usize> = HashMap::new();
+    for entry in entries.iter().filter(|entry| !entry.player_hand.is_empty()) {
+        *hand_counts.entry(entry.player_hand.as_str()).or_insert(0) += 1;
+    }
+    let most_played = entries
+        .iter()
+        .filter(|entry| !entry.player_hand.is_empty())
+        .map(|entry| entry.player_hand.as_str())
+        .fold(None, |best: Option<&str>, hand| match best {
+            Some(best) if hand_counts[best] >= hand_counts[hand] => Some(best),
+            _ => Some(hand),
+        });
+    if let Some(hand) = most_played {
+        summary.push_str(&format!("\nMost played hand: {} ({}x)", hand_set.label(hand), hand_counts[hand]));
+    }
+
+    if entries.iter().any(|entry| entry.points.is_some()) {
+        let points: i32 = entries.iter().filter_map(|entry| entry.points).sum();
+        summary.push_str(&format!("\nPoints from rounds: {}", points));
+    }
+    summary.push('\n');
+
+    summary
+}
+
+async fn season_name(db_pool: &DbPool, season_id: i32) -> Result<Option<String>, RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    conn.query_row(
+        "SELECT name FROM seasons WHERE id = ?1",
+        params![season_id],
+        |row| row.get(0),
+    ).optional()
+}
+
+// The scored rounds of a player in a season, oldest first. Rounds still being played have no game_status yet.
+pub async fn get_player_history(db_pool: &DbPool, season_id: i32, player_id: i64) -> Result<Vec<HistoryEntry>, RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    let mut stmt = conn.prepare(
+        "SELECT mrt.round_number, rdt.player_hand, rdt.opponent_username, rdt.opponent_hand, rdt.game_status, rdt.points
+         FROM RoundDetailsTable rdt
+         JOIN MasterRoundTable mrt ON rdt.round_id = mrt.id
+         WHERE mrt.season_id = ?1 AND rdt.player_id = ?2 AND rdt.game_status IS NOT NULL
+         ORDER BY mrt.round_number",
+    )?;
+    let entries = stmt
+        .query_map(params![season_id, player_id], |row| {
+            Ok(HistoryEntry {
+                round_number: row.get(0)?,
+                player_hand: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                opponent_username: row.get(2)?,
+                opponent_hand: row.get(3)?,
+                game_status: row.get(4)?,
+                points: row.get(5)?,
+            })
+        })?
+        .collect::<Result<Vec<HistoryEntry>, RusqliteError>>()?;
+
+    Ok(entries)
+}
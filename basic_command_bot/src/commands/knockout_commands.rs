@@ -0,0 +1,299 @@
+/// knockout_commands.rs
+
+use teloxide::{prelude::* };
+use std::{collections::{hash_map::Entry, HashMap}, error::Error, sync::Arc};
+use crate::database::{DbPool};
+use crate::enums::SeasonFormat;
+use rusqlite::{params, Connection, Error as RusqliteError};
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::commands::season::{
+    current_active_season_details,
+    season_format,
+};
+
+use crate::commands::playing_commands::get_username;
+use crate::pairing::PlayerHand;
+use crate::rating::player_rating;
+
+// One match of the bracket. Stage 1 is the first round, the last stage is the final.
+pub struct BracketMatch {
+    pub stage: i32,
+    pub position: i32,
+    pub player_one: Option<i64>,
+    pub player_two: Option<i64>,
+    pub winner: Option<i64>,
+}
+
+// What closing a knockout round did to the bracket
+pub struct KnockoutUpdate {
+    pub champion: Option<i64>,
+    pub report: String,
+}
+
+pub async fn bracket_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let response = match current_active_season_details(db_pool).await? {
+        Some((season_id, name, _, _, _)) => {
+            if season_format(db_pool, season_id).await? != SeasonFormat::Knockout {
+                "This season is a league, there is no bracket. Check /viewleaderboard instead.".to_string()
+            } else {
+                let matches = get_bracket(db_pool, season_id).await?;
+                if matches.is_empty() {
+                    "The bracket is drawn when the gaming phase starts.".to_string()
+                } else {
+                    render_bracket(db_pool, &name, &matches).await?
+                }
+            }
+        }
+        None => "There is no active season currently.".to_string(),
+    };
+    bot.send_message(msg.chat.id, response).await?;
+
+    Ok(())
+}
+
+// Draw the bracket of a knockout season from its approved players, unless it already exists.
+// Returns false if there are not enough players for a bracket.
+pub async fn ensure_bracket(db_pool: &DbPool, season_id: i32) -> Result<bool, RusqliteError> {
+    if !get_bracket(db_pool, season_id).await?.is_empty() {
+        return Ok(true);
+    }
+
+    let mut players = get_season_player_ids(db_pool, season_id).await?;
+    if players.len() < 2 {
+        return Ok(false);
+    }
+
+    // Seeded by rating, players with the same rating (like newcomers) are drawn at random
+    players.shuffle(&mut rand::thread_rng());
+    let mut ratings = HashMap::new();
+    for &player_id in &players {
+        ratings.insert(player_id, player_rating(db_pool, player_id).await?);
+    }
+    players.sort_by(|a, b| ratings[b].total_cmp(&ratings[a]));
+    create_bracket(db_pool, season_id, &players).await?;
+    Ok(true)
+}
+
+// Create the first stage from players in seed order. The bracket is filled up to a power of two
+// with byes, which go to the top seeds and send them straight to the second stage.
+pub async fn create_bracket(db_pool: &DbPool, season_id: i32, seeded_players: &[i64]) -> Result<(), RusqliteError> {
+    let size = seeded_players.len().next_power_of_two();
+    let final_stage = size.trailing_zeros() as i32;
+
+    let mut conn = db_pool.get().expect("Failed to get DB connection");
+    let tx = conn.transaction()?;
+
+    for (position, seeds) in seed_order(size).chunks(2).enumerate() {
+        let position = position as i32;
+        let player_one = seeded_players.get(seeds[0] - 1).copied();
+        let player_two = seeded_players.get(seeds[1] - 1).copied();
+        tx.execute(
+            "INSERT INTO bracket_matches (season_id, stage, position, player_one, player_two) VALUES (?1, 1, ?2, ?3, ?4)",
+            params![season_id, position, player_one, player_two],
+        )?;
+
+        if let (Some(player_one), None) = (player_one, player_two) {
+            set_winner_on(&tx, season_id, 1, position, player_one, final_stage)?;
+        }
+    }
+
+    tx.commit()?;
+    Ok(())
+}
+
+// Seeds in bracket order, so that seed 1 and 2 can only meet in the final. For 8: 1 8 4 5 2 7 3 6
+fn seed_order(size: usize) -> Vec<usize> {
+    let mut order = vec![1];
+    while order.len() < size {
+        let round_size = order.len() * 2;
+        order = order.iter().flat_map(|&seed| [seed, round_size + 1 - seed]).collect();
+    }
+    order
+}
+
+// Record the winner of a match and move them to their slot in the next stage
+fn set_winner_on(conn: &Connection, season_id: i32, stage: i32, position: i32, winner: i64, final_stage: i32) -> Result<(), RusqliteError> {
+    conn.execute(
+        "UPDATE bracket_matches SET winner = ?4 WHERE season_id = ?1 AND stage = ?2 AND position = ?3",
+        params![season_id, stage, position, winner],
+    )?;
+
+    if stage < final_stage {
+        conn.execute(
+            "INSERT INTO bracket_matches (season_id, stage, position) VALUES (?1, ?2, ?3) ON CONFLICT(season_id, stage, position) DO NOTHING",
+            params![season_id, stage + 1, position / 2],
+        )?;
+        let slot = if position % 2 == 0 { "player_one" } else { "player_two" };
+        conn.execute(
+            &format!("UPDATE bracket_matches SET {} = ?4 WHERE season_id = ?1 AND stage = ?2 AND position = ?3", slot),
+            params![season_id, stage + 1, position / 2, winner],
+        )?;
+    }
+    Ok(())
+}
+
+// The pairings for a knockout round: every match with both players known and no winner yet.
+// Draws leave the match open. Knockout matches replay a drawn throw within the round (see series_commands.rs),
+// so only a commit–reveal draw, or a draw left when the round closes, is replayed in the next round.
+pub async fn knockout_pairs(db_pool: &DbPool, season_id: i32, player_hands: Vec<PlayerHand>) -> Result<Vec<(PlayerHand, PlayerHand)>, RusqliteError> {
+    let hands: HashMap<i64, String> = player_hands.into_iter().collect();
+    let pairs = get_bracket(db_pool, season_id)
+        .await?
+        .into_iter()
+        .filter(|m| m.winner.is_none())
+        .filter_map(|m| match (m.player_one, m.player_two) {
+            (Some(player_one), Some(player_two)) => Some((
+                (player_one, hands.get(&player_one).cloned().unwrap_or_default()),
+                (player_two, hands.get(&player_two).cloned().unwrap_or_default()),
+            )),
+            _ => None,
+        })
+        .collect();
+    Ok(pairs)
+}
+
+// Move the winners of a knockout round through the bracket. A draw is replayed, unless neither
+// player played a hand, then a coin flip decides so the bracket can't stall.
+pub async fn record_knockout_results(
+    db_pool: &DbPool,
+    season_id: i32,
+    match_results: &[(i64, String, i64, String, String, i32)],
+) -> Result<KnockoutUpdate, RusqliteError> {
+    let matches = get_bracket(db_pool, season_id).await?;
+    let final_stage = final_stage(&matches);
+
+    let mut usernames = HashMap::new();
+    for (player_id, _, opponent_id, _, _, _) in match_results {
+        for id in [*player_id, *opponent_id] {
+            if let Entry::Vacant(entry) = usernames.entry(id) {
+                entry.insert(get_username(db_pool, id).await?);
+            }
+        }
+    }
+
+    let mut champion = None;
+    let mut report = String::new();
+    let mut conn = db_pool.get().expect("Failed to get DB connection");
+    let tx = conn.transaction()?;
+
+    for (player_id, player_hand, opponent_id, opponent_hand, game_status, _round_id) in match_results {
+        let bracket_match = match matches.iter().find(|m| {
+            m.winner.is_none()
+                && ((m.player_one == Some(*player_id) && m.player_two == Some(*opponent_id))
+                    || (m.player_one == Some(*opponent_id) && m.player_two == Some(*player_id)))
+        }) {
+            Some(bracket_match) => bracket_match,
+            None => continue,
+        };
+
+        let (winner, loser) = match game_status.as_str() {
+            "won" => (*player_id, *opponent_id),
+            "lost" => (*opponent_id, *player_id),
+            _ if player_hand.is_empty() && opponent_hand.is_empty() => {
+                let (winner, loser) = if rand::thread_rng().gen_bool(0.5) { (*player_id, *opponent_id) } else { (*opponent_id, *player_id) };
+                report.push_str(&format!("Neither @{} nor @{} played, a coin flip sends @{} through.\n", usernames[player_id], usernames[opponent_id], usernames[&winner]));
+                (winner, loser)
+            }
+            _ => {
+                report.push_str(&format!("@{} and @{} drew, they replay next round.\n", usernames[player_id], usernames[opponent_id]));
+                continue;
+            }
+        };
+
+        set_winner_on(&tx, season_id, bracket_match.stage, bracket_match.position, winner, final_stage)?;
+        report.push_str(&format!("@{} is out of the tournament.\n", usernames[&loser]));
+        if bracket_match.stage == final_stage {
+            champion = Some(winner);
+        }
+    }
+
+    tx.commit()?;
+    Ok(KnockoutUpdate { champion, report })
+}
+
+pub async fn get_season_player_ids(db_pool: &DbPool, season_id: i32) -> Result<Vec<i64>, RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    let mut stmt = conn.prepare("SELECT player_id FROM PlayerDetailsTable WHERE season_id = ?1")?;
+    let player_ids = stmt.query_map(params![season_id], |row| row.get(0))?
+        .collect::<Result<Vec<i64>, RusqliteError>>()?;
+
+    Ok(player_ids)
+}
+
+pub async fn get_bracket(db_pool: &DbPool, season_id: i32) -> Result<Vec<BracketMatch>, RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    let mut stmt = conn.prepare(
+        "SELECT stage, position, player_one, player_two, winner FROM bracket_matches WHERE season_id = ?1 ORDER BY stage, position",
+    )?;
+    let matches = stmt.query_map(params![season_id], |row| {
+        Ok(BracketMatch {
+            stage: row.get(0)?,
+            position: row.get(1)?,
+            player_one: row.get(2)?,
+            player_two: row.get(3)?,
+            winner: row.get(4)?,
+        })
+    })?
+    .collect::<Result<Vec<BracketMatch>, RusqliteError>>()?;
+
+    Ok(matches)
+}
+
+// The winner of the final, once it has been played
+pub async fn bracket_champion(db_pool: &DbPool, season_id: i32) -> Result<Option<i64>, RusqliteError> {
+    let matches = get_bracket(db_pool, season_id).await?;
+    if matches.is_empty() {
+        return Ok(None);
+    }
+    let final_stage = final_stage(&matches);
+    Ok(matches.iter().find(|m| m.stage == final_stage).and_then(|m| m.winner))
+}
+
+// The first stage has half as many matches as the bracket has slots
+fn final_stage(matches: &[BracketMatch]) -> i32 {
+    let first_stage_matches = matches.iter().filter(|m| m.stage == 1).count().max(1);
+    (first_stage_matches * 2).trailing_zeros() as i32
+}
+
+fn stage_name(stage: i32, final_stage: i32) -> String {
+    match final_stage - stage {
+        0 => "Final".to_string(),
+        1 => "Semi-finals".to_string(),
+        2 => "Quarter-finals".to_string(),
+        n => format!("Round of {}", 1 << (n + 1)),
+    }
+}
+
+pub async fn render_bracket(db_pool: &DbPool, season_name: &str, matches: &[BracketMatch]) -> Result<String, RusqliteError> {
+    let final_stage = final_stage(matches);
+    let mut usernames: HashMap<i64, String> = HashMap::new();
+    let player_ids: Vec<i64> = matches.iter().flat_map(|m| [m.player_one, m.player_two]).flatten().collect();
+    for id in player_ids {
+        if let Entry::Vacant(entry) = usernames.entry(id) {
+            entry.insert(get_username(db_pool, id).await?);
+        }
+    }
+    let name = |player: Option<i64>| player.map(|id| format!("@{}", usernames[&id])).unwrap_or_else(|| "TBD".to_string());
+
+    let mut response = format!("🏆 Bracket for '{}' 🏆\n", season_name);
+    for stage in 1..=final_stage {
+        response.push_str(&format!("\n{}\n", stage_name(stage, final_stage)));
+        let stage_matches: Vec<&BracketMatch> = matches.iter().filter(|m| m.stage == stage).collect();
+        if stage_matches.is_empty() {
+            response.push_str("TBD\n");
+        }
+        for m in stage_matches {
+            let line = match (m.stage, m.player_two, m.winner) {
+                (1, None, _) => format!("{} has a bye", name(m.player_one)),
+                (_, _, Some(winner)) => format!("{} vs {} → {}", name(m.player_one), name(m.player_two), name(Some(winner))),
+                _ => format!("{} vs {}", name(m.player_one), name(m.player_two)),
+            };
+            response.push_str(&line);
+            response.push('\n');
+        }
+    }
+
+    Ok(response)
+}
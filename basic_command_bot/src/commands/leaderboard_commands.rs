@@ -0,0 +1,238 @@
+/// leaderboard_commands.rs
+
+use teloxide::prelude::*;
+use std::{collections::HashMap, error::Error, sync::Arc};
+use rusqlite::{params, Connection, OptionalExtension, Error as RusqliteError};
+use crate::database::DbPool;
+use crate::enums::{SeasonPhase, SeasonFormat};
+use crate::messages::{edit_page, page, paginate, send_long_message, send_page, Page};
+
+use crate::commands::season::{find_season, season_format};
+use crate::commands::playing_commands::{
+    fetch_leaderboard,
+    leaderboard_lines,
+    tie_breaker_note,
+    get_username,
+};
+use crate::commands::knockout_commands::bracket_champion;
+
+// Callback data is "leaderboard:<season_id>:<page>"
+pub const LEADERBOARD_CALLBACK_PREFIX: &str = "leaderboard:";
+
+// Players per leaderboard page
+const LEADERBOARD_PAGE_SIZE: usize = 25;
+
+// A season as listed by /seasons
+pub struct SeasonSummary {
+    pub id: i32,
+    pub name: String,
+    pub start_date: String,
+    pub stop_date: Option<String>,
+    pub is_active: bool,
+    pub status: SeasonPhase,
+    pub format: SeasonFormat,
+    // Closed seasons have their winners in season_winners, except those closed before it existed
+    pub winners_recorded: bool,
+}
+
+pub async fn seasons_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let seasons = list_seasons(db_pool).await?;
+    if seasons.is_empty() {
+        bot.send_message(msg.chat.id, "There are no seasons yet.").await?;
+        return Ok(());
+    }
+
+    // Seasons closed before their winners were stored get them once, the rest come in one query
+    for season in seasons.iter().filter(|season| !season.is_active && !season.winners_recorded) {
+        record_season_winners(db_pool, season.id).await?;
+    }
+    let mut season_winners = stored_season_winners(db_pool).await?;
+
+    let mut response = String::from("📅 Seasons 📅\n\n");
+    for season in &seasons {
+        let dates = match &season.stop_date {
+            Some(stop_date) => format!("{} – {}", date_only(&season.start_date), date_only(stop_date)),
+            None => format!("since {}", date_only(&season.start_date)),
+        };
+        let outcome = if !season.is_active {
+            match season_winners.remove(&season.id).unwrap_or_default() {
+                winners if winners.is_empty() => "no winner".to_string(),
+                winners => format!("🏆 {}", winners.iter().map(|winner| format!("@{}", winner)).collect::<Vec<String>>().join(" & ")),
+            }
+        } else {
+            format!("running ({})", season.status)
+        };
+        response.push_str(&format!("#{} {} ({}), {}, {}\n", season.id, season.name, season.format, dates, outcome));
+    }
+    response.push_str("\nSee the standings of a season with '/leaderboard <season name or id>'.");
+    send_long_message(&bot, msg.chat.id, &response).await?;
+
+    Ok(())
+}
+
+pub async fn leaderboard_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>, season: String) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (season_id, name) = match find_season(db_pool, season.trim()).await? {
+        Some(season) => season,
+        None if season.trim().is_empty() => {
+            bot.send_message(msg.chat.id, "There are no seasons yet.").await?;
+            return Ok(());
+        }
+        None => {
+            bot.send_message(msg.chat.id, format!("No season '{}' found. Use /seasons to see them all.", season.trim())).await?;
+            return Ok(());
+        }
+    };
+    let page = leaderboard_page(db_pool, season_id, &name, 0).await?;
+    send_page(&bot, msg.chat.id, page).await?;
+
+    Ok(())
+}
+
+// The previous/next buttons under a leaderboard
+pub async fn leaderboard_callback(bot: Bot, q: CallbackQuery, db_pool: &DbPool, data: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (season_id, page_number) = match data.split_once(':').map(|(season_id, page)| (season_id.parse::<i32>(), page.parse::<usize>())) {
+        Some((Ok(season_id), Ok(page_number))) => (season_id, page_number),
+        _ => {
+            bot.answer_callback_query(q.id).await?;
+            return Ok(());
+        }
+    };
+    let name = match get_season_summary(db_pool, season_id).await? {
+        Some(season) => season.name,
+        None => {
+            bot.answer_callback_query(q.id).text("That season no longer exists.").await?;
+            return Ok(());
+        }
+    };
+
+    let page = leaderboard_page(db_pool, season_id, &name, page_number).await?;
+    bot.answer_callback_query(q.id.clone()).await?;
+    edit_page(&bot, &q, page).await?;
+
+    Ok(())
+}
+
+// One page of the standings of a season, with the champion of a knockout on top
+pub async fn leaderboard_page(db_pool: &DbPool, season_id: i32, name: &str, page_number: usize) -> Result<Page, RusqliteError> {
+    let summary = match get_season_summary(db_pool, season_id).await? {
+        Some(summary) => summary,
+        None => return Ok(Page { text: format!("No season '{}' found.", name), keyboard: None }),
+    };
+
+    let leaderboard = fetch_leaderboard(db_pool, season_id).await?;
+    let mut header = if !summary.is_active {
+        format!("Final standings of season {}\n", name)
+    } else {
+        format!("Standings of season {} so far\n", name)
+    };
+    if leaderboard.is_empty() {
+        header.push_str("\nNobody played in this season.");
+        return Ok(Page { text: header, keyboard: None });
+    }
+    if summary.format == SeasonFormat::Knockout {
+        if let Some(champion) = bracket_champion(db_pool, season_id).await? {
+            header.push_str(&format!("Champion: @{}\n", get_username(db_pool, champion).await?));
+        }
+    }
+    if let Some(note) = tie_breaker_note(&leaderboard) {
+        header.push_str(&format!("{}\n", note));
+    }
+    header.push_str("\n🏆 Leaderboard 🏆\n\n");
+
+    let pages = paginate(&header, &leaderboard_lines(&leaderboard), LEADERBOARD_PAGE_SIZE);
+    Ok(page(&pages, page_number, |page_number| format!("{}{}:{}", LEADERBOARD_CALLBACK_PREFIX, season_id, page_number)))
+}
+
+// The champion of a knockout season, the players ranked first in a league, as (player_id, username)
+pub async fn rank_season_winners(db_pool: &DbPool, season_id: i32) -> Result<Vec<(i64, String)>, RusqliteError> {
+    if season_format(db_pool, season_id).await? == SeasonFormat::Knockout {
+        return match bracket_champion(db_pool, season_id).await? {
+            Some(champion) => Ok(vec![(champion, get_username(db_pool, champion).await?)]),
+            None => Ok(Vec::new()),
+        };
+    }
+
+    Ok(fetch_leaderboard(db_pool, season_id)
+        .await?
+        .into_iter()
+        .filter(|standing| standing.rank == 1)
+        .map(|standing| (standing.player_id, standing.username))
+        .collect())
+}
+
+// Store the winners of a closing season. Takes the caller's connection, so it can go in the same
+// transaction as the close.
+pub fn record_season_winners_on(conn: &Connection, season_id: i32, winners: &[(i64, String)]) -> Result<(), RusqliteError> {
+    for (player_id, username) in winners {
+        conn.execute(
+            "INSERT OR IGNORE INTO season_winners (season_id, player_id, username) VALUES (?1, ?2, ?3)",
+            params![season_id, player_id, username],
+        )?;
+    }
+    conn.execute("UPDATE seasons SET winners_recorded = 1 WHERE id = ?1", params![season_id])?;
+    Ok(())
+}
+
+// Rank and store the winners of a season closed before they were stored at the close
+async fn record_season_winners(db_pool: &DbPool, season_id: i32) -> Result<(), RusqliteError> {
+    let winners = rank_season_winners(db_pool, season_id).await?;
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    record_season_winners_on(&conn, season_id, &winners)
+}
+
+// The stored winners of every closed season, in the order they were ranked
+async fn stored_season_winners(db_pool: &DbPool) -> Result<HashMap<i32, Vec<String>>, RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    let mut stmt = conn.prepare("SELECT season_id, username FROM season_winners ORDER BY season_id, rowid")?;
+    let rows = stmt
+        .query_map(params![], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<Result<Vec<(i32, String)>, RusqliteError>>()?;
+
+    let mut winners: HashMap<i32, Vec<String>> = HashMap::new();
+    for (season_id, username) in rows {
+        winners.entry(season_id).or_default().push(username);
+    }
+    Ok(winners)
+}
+
+// "2024-01-31 18:00:00" -> "2024-01-31"
+fn date_only(timestamp: &str) -> &str {
+    timestamp.split_whitespace().next().unwrap_or(timestamp)
+}
+
+const SEASON_SUMMARY_COLUMNS: &str = "id, name, start_date, stop_date, is_active, status, format, winners_recorded";
+
+fn season_summary(row: &rusqlite::Row) -> Result<SeasonSummary, RusqliteError> {
+    Ok(SeasonSummary {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        start_date: row.get(2)?,
+        stop_date: row.get(3)?,
+        is_active: row.get(4)?,
+        status: row.get(5)?,
+        format: row.get(6)?,
+        winners_recorded: row.get(7)?,
+    })
+}
+
+// Every season, the newest first
+pub async fn list_seasons(db_pool: &DbPool) -> Result<Vec<SeasonSummary>, RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM seasons ORDER BY id DESC", SEASON_SUMMARY_COLUMNS))?;
+    let seasons = stmt
+        .query_map(params![], season_summary)?
+        .collect::<Result<Vec<SeasonSummary>, RusqliteError>>()?;
+
+    Ok(seasons)
+}
+
+// A single season, for the leaderboard pages
+pub async fn get_season_summary(db_pool: &DbPool, season_id: i32) -> Result<Option<SeasonSummary>, RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    conn.query_row(
+        &format!("SELECT {} FROM seasons WHERE id = ?1", SEASON_SUMMARY_COLUMNS),
+        params![season_id],
+        season_summary,
+    )
+    .optional()
+}
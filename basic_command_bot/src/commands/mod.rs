@@ -9,5 +9,17 @@ pub mod changelogread;
 pub mod grp_broadcast_commands;
 pub mod registration_commands;
 pub mod playing_commands;
+pub mod schedule_commands;
+pub mod reminder_commands;
+pub mod callback_commands;
+pub mod commit_reveal_commands;
+pub mod knockout_commands;
+pub mod series_commands;
+pub mod history_commands;
+pub mod profile_commands;
+pub mod leaderboard_commands;
+pub mod rating_commands;
+pub mod role_commands;
+pub mod audit_commands;
 // Add other modules if they exist
 
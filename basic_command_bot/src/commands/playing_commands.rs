@@ -1,54 +1,218 @@
 /// playing_commands.rs
 
-//use teloxide::{prelude::* };
+use teloxide::{prelude::* };
 //use std::{error::Error, sync::Arc};
 use crate::database::{DbPool};
+use crate::enums::{SeasonPhase, SeasonFormat};
+use crate::scoring::{season_scoring_rules, win_streak};
+use crate::ranking::{season_standings, Standing};
+use crate::rating::rate_match;
+use crate::config;
 use rusqlite::{params, OptionalExtension, Error as RusqliteError};
 
 //use crate::admin::{is_authorized_sender };
 
 use crate::commands::season:: {
-    current_active_season_details
+    current_active_season_details,
+    season_allows_hand_change,
+    season_uses_commit_reveal,
+    season_pairing,
+    season_format,
+    season_best_of,
+    season_plays_series,
+    season_hand_set,
+    play_empty_hands_for_players,
+    claim_round,
+    release_round,
+    end_current_round,
+    stop_current_season,
 };
-use rand::seq::SliceRandom;
-use rand::Rng;
+use crate::commands::schedule_commands::{
+    reschedule_after_round,
+    get_schedule,
+    set_schedule_status,
+    ScheduleStatus,
+};
+use crate::commands::knockout_commands::{
+    knockout_pairs,
+    record_knockout_results,
+};
+use crate::commands::commit_reveal_commands::apply_reveals;
+use crate::commands::series_commands::{
+    play_series_throw,
+    settle_series,
+};
+use crate::pairing::load_pairing_context;
+use crate::hands::HandSet;
+use std::error::Error;
+
+// What happened to a hand submission
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandChoice {
+    Inserted,
+    Changed,
+    Rejected,
+}
 
-pub async fn insert_player_hand_choice(db_pool: &DbPool, round_id: i32, player_id: i64, player_hand: &str) -> Result<bool, RusqliteError> {
-    let conn = db_pool.get().expect("Failed to get DB connection");
+// Record a hand for a player. A second submission replaces the first only if allow_change is set.
+// Every accepted submission also goes into hand_changes.
+pub async fn insert_player_hand_choice(db_pool: &DbPool, round_id: i32, player_id: i64, player_hand: &str, allow_change: bool) -> Result<HandChoice, RusqliteError> {
+    let mut conn = db_pool.get().expect("Failed to get DB connection");
+    let tx = conn.transaction()?;
 
     // Check if the player has already played in this round
-    let exists: i64 = conn.query_row(
+    let exists: i64 = tx.query_row(
         "SELECT COUNT(*) FROM RoundDetailsTable WHERE round_id = ?1 AND player_id = ?2",
         params![round_id, player_id],
         |row| row.get(0),
-    ).unwrap_or(0);
+    )?;
 
-    // If the player has already played, return false
-    if exists > 0 {
-        return Ok(false);
-    }
+    let choice = if exists == 0 {
+        // Insert the player's hand choice
+        tx.execute(
+            "INSERT INTO RoundDetailsTable (round_id, player_id, player_hand, timestamp) VALUES (?1, ?2, ?3, CURRENT_TIMESTAMP)",
+            params![round_id, player_id, player_hand],
+        )?;
+        HandChoice::Inserted
+    } else if allow_change {
+        // Last choice wins
+        tx.execute(
+            "UPDATE RoundDetailsTable SET player_hand = ?3, timestamp = CURRENT_TIMESTAMP WHERE round_id = ?1 AND player_id = ?2",
+            params![round_id, player_id, player_hand],
+        )?;
+        HandChoice::Changed
+    } else {
+        return Ok(HandChoice::Rejected);
+    };
 
-    // Insert the player's hand choice
-    let rows_affected = conn.execute(
-        "INSERT INTO RoundDetailsTable (round_id, player_id, player_hand, timestamp) VALUES (?1, ?2, ?3, CURRENT_TIMESTAMP)",
+    tx.execute(
+        "INSERT INTO hand_changes (round_id, player_id, player_hand, timestamp) VALUES (?1, ?2, ?3, CURRENT_TIMESTAMP)",
         params![round_id, player_id, player_hand],
     )?;
+    tx.commit()?;
+
+    Ok(choice)
+}
+
+// The hand a player currently has locked in for a round
+pub async fn get_player_hand(db_pool: &DbPool, round_id: i32, player_id: i64) -> Result<Option<String>, RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    conn.query_row(
+        "SELECT player_hand FROM RoundDetailsTable WHERE round_id = ?1 AND player_id = ?2",
+        params![round_id, player_id],
+        |row| row.get(0),
+    ).optional()
+}
+
+// How many times a player submitted a hand in a round
+pub async fn count_hand_submissions(db_pool: &DbPool, round_id: i32, player_id: i64) -> Result<i64, RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    conn.query_row(
+        "SELECT COUNT(*) FROM hand_changes WHERE round_id = ?1 AND player_id = ?2",
+        params![round_id, player_id],
+        |row| row.get(0),
+    )
+}
 
-    // Return true if the row was successfully inserted
-    Ok(rows_affected > 0)
+// What happened when a player tried to play a hand. The hands are labels like "rock 🪨".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlayOutcome {
+    Played(String),
+    Changed(String),
+    // The hands of the season, for a hand that isn't one of them
+    UnknownHand(String),
+    AlreadyPlayed,
+    NoRoundOngoing,
+    NotInGame,
+    NoActiveRound,
+    NoActiveSeason,
+    CommitRevealOnly,
+    Thrown(String),
+    SeriesBye,
+    SeriesDecided,
+}
+
+impl PlayOutcome {
+    pub fn message(&self) -> String {
+        match self {
+            PlayOutcome::Played(hand) => format!("Playing the {} hand.", hand),
+            PlayOutcome::Changed(hand) => format!("Changed your hand to {}. You can change it until the round closes.", hand),
+            PlayOutcome::UnknownHand(hands) => format!("That hand is not played this season, pick one of {}.", hands),
+            PlayOutcome::AlreadyPlayed => "You have already played this round.".to_string(),
+            PlayOutcome::NoRoundOngoing => "There is no round currently ongoing.".to_string(),
+            PlayOutcome::NotInGame => "You are not part of the current game.".to_string(),
+            PlayOutcome::NoActiveRound => "No active round found.".to_string(),
+            PlayOutcome::NoActiveSeason => "No active season found.".to_string(),
+            PlayOutcome::CommitRevealOnly => "This season uses commit–reveal, so hands are played with /commit and /reveal. Send /commit to see how.".to_string(),
+            PlayOutcome::Thrown(hand) => format!("Throw locked in: {}.", hand),
+            PlayOutcome::SeriesBye => "You have a bye this round, there is nothing to play.".to_string(),
+            PlayOutcome::SeriesDecided => "Your match is already decided for this round.".to_string(),
+        }
+    }
+}
+
+// Play a hand for a player in the ongoing round. Every way of playing a hand goes through here.
+// In best-of seasons the hand is the player's next throw, and the bot DMs both players once a throw is complete.
+pub async fn play_hand(bot: &Bot, db_pool: &DbPool, player_id: i64, hand: &str) -> Result<PlayOutcome, Box<dyn Error + Send + Sync>> {
+    // Check if there is an active season in the "round_ongoing" phase and get the season_id
+    let season_id = match current_game_status_and_season_id(db_pool).await? {
+        Some((SeasonPhase::RoundOngoing, season_id)) => season_id,
+        Some(_) => return Ok(PlayOutcome::NoRoundOngoing),
+        None => return Ok(PlayOutcome::NoActiveSeason),
+    };
+
+    // Plain hands would be visible in the database before the round closes
+    if season_uses_commit_reveal(db_pool, season_id).await? {
+        return Ok(PlayOutcome::CommitRevealOnly);
+    }
+
+    let hand_set = season_hand_set(db_pool, season_id).await?;
+    if !hand_set.contains(hand) {
+        return Ok(PlayOutcome::UnknownHand(hand_set.names().join(", ")));
+    }
+
+    // Check if the player is in the current game
+    if !check_player_in_game(db_pool, player_id, season_id).await? {
+        return Ok(PlayOutcome::NotInGame);
+    }
+
+    // Get the current round ID
+    let round_id = match get_current_round_id(db_pool, season_id).await? {
+        Some(round_id) => round_id,
+        None => return Ok(PlayOutcome::NoActiveRound),
+    };
+
+    if season_plays_series(db_pool, season_id).await? {
+        let best_of = season_best_of(db_pool, season_id).await?;
+        return play_series_throw(bot, db_pool, &hand_set, round_id, player_id, hand, best_of).await;
+    }
+
+    // Insert the player's choice into the RoundDetailsTable
+    let allow_change = season_allows_hand_change(db_pool, season_id).await?;
+    match insert_player_hand_choice(db_pool, round_id, player_id, hand, allow_change).await? {
+        HandChoice::Inserted => Ok(PlayOutcome::Played(hand_set.label(hand))),
+        HandChoice::Changed => Ok(PlayOutcome::Changed(hand_set.label(hand))),
+        HandChoice::Rejected => Ok(PlayOutcome::AlreadyPlayed),
+    }
 }
 
 // Retrieve the current game status and season_id
-pub async fn current_game_status_and_season_id(db_pool: &DbPool) -> Result<Option<(String, i32)>, RusqliteError> {
+pub async fn current_game_status_and_season_id(db_pool: &DbPool) -> Result<Option<(SeasonPhase, i32)>, RusqliteError> {
     let season_details = current_active_season_details(db_pool).await?;
     Ok(season_details.map(|(season_id, _,  _, _, status)| (status, season_id)))
 }
 
-// Check if the player is in the current game
+// Check if the player is in the current game. In a knockout season that means having an open match.
 pub async fn check_player_in_game(db_pool: &DbPool, player_id: i64, season_id: i32) -> Result<bool, RusqliteError> {
     let conn = db_pool.get().expect("Failed to get DB connection");
     let count: i64 = conn.query_row(
-        "SELECT COUNT(*) FROM PlayerDetailsTable WHERE player_id = ?1 AND season_id = ?2",
+        "SELECT COUNT(*) FROM PlayerDetailsTable pd
+         JOIN seasons s ON s.id = pd.season_id
+         WHERE pd.player_id = ?1 AND pd.season_id = ?2
+           AND (s.format != 'knockout' OR EXISTS (
+               SELECT 1 FROM bracket_matches bm
+               WHERE bm.season_id = pd.season_id AND bm.winner IS NULL AND bm.player_one IS NOT NULL AND bm.player_two IS NOT NULL
+                 AND pd.player_id IN (bm.player_one, bm.player_two)))",
         params![player_id, season_id],
         |row| row.get(0),
     )?;
@@ -83,52 +247,146 @@ pub async fn get_player_hands(db_pool: &DbPool, round_id: i32, season_id: i32) -
     Ok(player_hands)
 }
 
-pub async fn random_match_players(player_hands: Vec<(i64, String)>) -> Vec<((i64, String), (i64, String))> {
-    let mut rng = rand::thread_rng();
-    let mut shuffled_hands = player_hands.clone();
-    shuffled_hands.shuffle(&mut rng);
+// What came out of closing a round
+pub struct ClosedRound {
+    pub players_without_moves: Vec<i64>,
+    pub announcement: String,
+}
+
+// Close the ongoing round of a season: play empty hands for whoever didn't play,
+// match players, score them and end the round. Shared by /stopround and the scheduler.
+// Commit–reveal rounds get here once their reveal window is over.
+// Returns None if the round was already closed, or is being closed by the other one.
+pub async fn close_current_round(db_pool: &DbPool, season_id: i32) -> Result<Option<ClosedRound>, Box<dyn Error + Send + Sync>> {
+    let (phase, _) = current_game_status_and_season_id(db_pool)
+        .await?
+        .ok_or("No active season found")?;
+
+    // Get the current round ID and claim it, so the round is scored once
+    let current_round_id = match get_current_round_id(db_pool, season_id).await? {
+        Some(round_id) => round_id,
+        None => return Ok(None),
+    };
+    if !claim_round(db_pool, current_round_id).await? {
+        return Ok(None);
+    }
+
+    match score_claimed_round(db_pool, season_id, phase, current_round_id).await {
+        Ok(closed_round) => Ok(Some(closed_round)),
+        Err(e) => {
+            // Give the round back while the season is still in it, so /stopround or the scheduler can retry
+            log::error!("Failed to close round {} of season {}: {}", current_round_id, season_id, e);
+            release_round(db_pool, season_id, current_round_id).await?;
+            Err(e)
+        }
+    }
+}
+
+async fn score_claimed_round(db_pool: &DbPool, season_id: i32, phase: SeasonPhase, current_round_id: i32) -> Result<ClosedRound, Box<dyn Error + Send + Sync>> {
+    let hand_set = season_hand_set(db_pool, season_id).await?;
+
+    // Commit–reveal rounds: the revealed hands go in first, whoever didn't reveal forfeits
+    let (mut players_without_moves, commitments_report) = if phase == SeasonPhase::RoundRevealing {
+        apply_reveals(db_pool, &hand_set, current_round_id).await?
+    } else {
+        (Vec::new(), String::new())
+    };
+
+    // Handle players who haven't played
+    players_without_moves.extend(play_empty_hands_for_players(db_pool, season_id, current_round_id).await?);
+
+    // ====
+    // ==== GAME MAKING LOGIC HERE ===
+    // ====
+    let format = season_format(db_pool, season_id).await?;
+    let series = season_plays_series(db_pool, season_id).await?;
+    let (match_results, series_report) = if series {
+        // Best-of and knockout matches were paired when the round started, their throws decide them
+        settle_series(db_pool, &hand_set, current_round_id).await?
+    } else {
+        // Step 1: Retrieve players and hands
+        let player_hands = get_player_hands(db_pool, current_round_id, season_id).await?;
+        log::debug!("Player hands: {:?}", player_hands);
+
+        // Step 2: Pair players the way the season asks for. Knockout seasons play their open bracket matches.
+        let (matched_pairs, bye) = if format == SeasonFormat::Knockout {
+            (knockout_pairs(db_pool, season_id, player_hands).await?, None)
+        } else {
+            let pairing = season_pairing(db_pool, season_id).await?;
+            let pairing_context = load_pairing_context(db_pool, season_id, current_round_id).await?;
+            let pairings = pairing.strategy().pair(player_hands, &pairing_context);
+            (pairings.pairs, pairings.unpaired)
+        };
+        log::debug!("Matched pairs ({}): {:?}, bye: {:?}", format, matched_pairs, bye);
+
+        // Step 3: Evaluate matches
+        let mut match_results = evaluate_matches(db_pool, &hand_set, matched_pairs, current_round_id).await?;
+        // With an odd number of players the one left over has a bye. There is no opponent,
+        // the player's own id only fills the slot.
+        if let Some((player_id, player_hand)) = bye {
+            match_results.push((player_id, player_hand, player_id, String::new(), "bye".to_string(), current_round_id));
+        }
+        (match_results, String::new())
+    };
+    log::debug!("Match results: {:?}", match_results);
+
+    // Step 4: Update player scores and RoundDetailsTable
+    update_player_score(db_pool, match_results.clone(), season_id, series).await?;
+
+    // Knockout seasons move the winners through the bracket
+    let knockout_update = if format == SeasonFormat::Knockout {
+        Some(record_knockout_results(db_pool, season_id, &match_results).await?)
+    } else {
+        None
+    };
 
-    let mut matched_pairs = Vec::new();
+    // End of game-making logic
 
-    if shuffled_hands.len() % 2 != 0 {
-        println!("Uneven number of players triggered");
-        // If there's an odd number of players, select a random player to play twice
-        let random_index = rng.gen_range(0..shuffled_hands.len());
-        let player_to_play_twice = shuffled_hands.remove(random_index);
-        matched_pairs.push((player_to_play_twice.clone(), player_to_play_twice.clone()));
+    // End the current round, and let the schedule know when the next one may start
+    end_current_round(db_pool, season_id, phase).await?;
+    reschedule_after_round(db_pool, season_id).await?;
+
+    // Generate announcement for match results, with the commitments so anyone can check them
+    let mut announcement = if series { series_report } else { announce_results(db_pool, &hand_set, match_results).await? };
+    if !commitments_report.is_empty() {
+        announcement.push('\n');
+        announcement.push_str(&commitments_report);
     }
 
-    while let Some(player_one) = shuffled_hands.pop() {
-        if let Some(player_two) = shuffled_hands.pop() {
-            matched_pairs.push((player_one, player_two));
+    if let Some(knockout_update) = knockout_update {
+        announcement.push_str(&knockout_update.report);
+        if let Some(champion) = knockout_update.champion {
+            // The bracket is decided, so is the season
+            stop_current_season(db_pool, SeasonPhase::StartGaming).await?;
+            if get_schedule(db_pool, season_id).await?.is_some() {
+                set_schedule_status(db_pool, season_id, ScheduleStatus::Finished).await?;
+            }
+            announcement.push_str(&format!("\n🏆 @{} is the champion! The season is closed.\n", get_username(db_pool, champion).await?));
         }
     }
 
-    matched_pairs
+    Ok(ClosedRound { players_without_moves, announcement })
+}
+
+// DM the players whose hand was played empty. Players who never opened a DM with the bot are only logged.
+pub async fn notify_empty_hands(bot: &Bot, player_ids: &[i64]) {
+    for &player_id in player_ids {
+        if let Err(e) = bot.send_message(ChatId(player_id), "Your hand was empty for this round.").await {
+            log::warn!("Could not notify player {} about their empty hand: {}", player_id, e);
+        }
+    }
 }
 
 pub async fn evaluate_matches(
     _db_pool: &DbPool,
+    hand_set: &HandSet,
     matches: Vec<((i64, String), (i64, String))>,
     round_id: i32
 ) -> Result<Vec<(i64, String, i64, String, String, i32)>, RusqliteError> {
     let mut results = Vec::new();
 
     for ((player_id, player_hand), (opponent_id, opponent_hand)) in matches {
-        let game_status = match (player_hand.as_str(), opponent_hand.as_str()) {
-            // Both players have empty hands
-            ("", "") => "draw",
-            // Player wins when opponent has an empty hand and player doesn't
-            (_, "") => "won",
-            // Player loses when their hand is empty and opponent's isn't
-            ("", _) => "lost",
-            // Existing logic for non-empty hands
-            ("rock", "scissors") | ("scissors", "paper") | ("paper", "rock") => "won",
-            ("scissors", "rock") | ("paper", "scissors") | ("rock", "paper") => "lost",
-            // Draw when both hands are the same (excluding both empty, handled above)
-            _ => "draw",
-        };
-
+        let game_status = hand_set.result(&player_hand, &opponent_hand);
         results.push((player_id, player_hand, opponent_id, opponent_hand, game_status.to_string(), round_id));
     }
 
@@ -138,35 +396,59 @@ pub async fn evaluate_matches(
 pub async fn update_player_score(
     db_pool: &DbPool,
     match_results: Vec<(i64, String, i64, String, String, i32)>,
-    season_id: i32
+    season_id: i32,
+    series: bool
 ) -> Result<(), RusqliteError> {
+    let rules = season_scoring_rules(db_pool, season_id).await?;
     let mut conn = db_pool.get().expect("Failed to get DB connection");
     let tx = conn.transaction()?;
 
-    for (player_id, _player_hand, opponent_id, opponent_hand, game_status, round_id) in match_results {
+    for (player_id, player_hand, opponent_id, opponent_hand, game_status, round_id) in match_results {
         // Fetch player username
-        let player_conn = db_pool.get().expect("Failed to get DB connection");
-        let player_username: String = player_conn.query_row(
+        let player_username: String = tx.query_row(
             "SELECT player_username FROM PlayerDetailsTable WHERE player_id = ?1 AND season_id = ?2",
             params![player_id, season_id],
             |row| row.get(0),
         )?;
 
-        // Use a separate connection to fetch opponent username
-        let opponent_conn = db_pool.get().expect("Failed to get DB connection");
-        let opponent_username: String = opponent_conn.query_row(
+        // A bye only has the player's row. In best-of seasons (`series`) the bye is known when the
+        // round starts and there is nothing to play. Otherwise it goes to the player left over, who
+        // still had to play a hand to earn the bye points.
+        if game_status == "bye" {
+            let points = rules.points("bye", series || !player_hand.is_empty(), false);
+            tx.execute(
+                "UPDATE PlayerDetailsTable SET score = score + ? WHERE player_id = ? AND season_id = ?",
+                params![points, player_id, season_id],
+            )?;
+            tx.execute(
+                "UPDATE RoundDetailsTable SET opponent = NULL, opponent_hand = NULL, game_status = 'bye', player_username = ?, opponent_username = NULL, points = ? WHERE player_id = ? AND round_id = ?",
+                params![player_username, points, player_id, round_id],
+            )?;
+            continue;
+        }
+
+        // Fetch opponent username, in the same transaction so a pool of one connection is enough
+        let opponent_username: String = tx.query_row(
             "SELECT player_username FROM PlayerDetailsTable WHERE player_id = ?1 AND season_id = ?2",
             params![opponent_id, season_id],
             |row| row.get(0),
         )?;
 
 
-        let score_increment = match game_status.as_str() {
-            "won" => 2,
-            "draw" => 1,
-            _ => 0,
+        // An empty hand means the player didn't play, the season's rules decide what that costs
+        let played = !player_hand.is_empty();
+        let opponent_played = !opponent_hand.is_empty();
+        let opponent_status = match game_status.as_str() {
+            "won" => "lost",
+            "lost" => "won",
+            _ => "draw",
         };
 
+        let mut score_increment = rules.points(&game_status, played, opponent_played);
+        if game_status == "won" {
+            score_increment += rules.streak_points(win_streak(&tx, season_id, player_id, round_id)? + 1);
+        }
+
         // Update PlayerDetailsTable for the current season for the first player
         tx.execute(
             "UPDATE PlayerDetailsTable SET score = score + ? WHERE player_id = ? AND season_id = ?",
@@ -175,16 +457,15 @@ pub async fn update_player_score(
 
         // Update RoundDetailsTable for the current round for the first player
         tx.execute(
-            "UPDATE RoundDetailsTable SET opponent = ?, opponent_hand = ?, game_status = ?, player_username = ?, opponent_username = ? WHERE player_id = ? AND round_id = ?",
-            params![opponent_id, opponent_hand, game_status, player_username, opponent_username, player_id, round_id],
+            "UPDATE RoundDetailsTable SET opponent = ?, opponent_hand = ?, game_status = ?, player_username = ?, opponent_username = ?, points = ? WHERE player_id = ? AND round_id = ?",
+            params![opponent_id, opponent_hand, game_status, player_username, opponent_username, score_increment, player_id, round_id],
         )?;
 
         // Determine score increment for the opponent
-        let opponent_score_increment = match game_status.as_str() {
-            "lost" => 2,  // Opponent won
-            "draw" => 1,  // Draw
-            _ => 0,       // Opponent lost
-        };
+        let mut opponent_score_increment = rules.points(opponent_status, opponent_played, played);
+        if opponent_status == "won" {
+            opponent_score_increment += rules.streak_points(win_streak(&tx, season_id, opponent_id, round_id)? + 1);
+        }
 
         // Update PlayerDetailsTable for the opponent
         tx.execute(
@@ -194,22 +475,24 @@ pub async fn update_player_score(
 
         // Update RoundDetailsTable for the opponent
         tx.execute(
-            "UPDATE RoundDetailsTable SET opponent = ?, opponent_hand = ?, opponent_username = ?, player_username = ?, game_status = ? WHERE player_id = ? AND round_id = ?",
+            "UPDATE RoundDetailsTable SET opponent = ?, opponent_hand = ?, opponent_username = ?, player_username = ?, game_status = ?, points = ? WHERE player_id = ? AND round_id = ?",
             params![
                 player_id, 
-                _player_hand,
+                player_hand,
                 player_username,
                 opponent_username,
-                match game_status.as_str() {
-                    "won" => "lost",
-                    "lost" => "won",
-                    _ => "draw",
-                }, 
+                opponent_status,
+                opponent_score_increment,
                 opponent_id,
                 round_id
             ],
         )?;
 
+        // Ratings measure skill, so only matches where both players played a hand count
+        if played && opponent_played {
+            rate_match(&tx, round_id, player_id, opponent_id, &game_status)?;
+        }
+
 
     }
 
@@ -219,6 +502,7 @@ pub async fn update_player_score(
 
 pub async fn announce_results(
     db_pool: &DbPool,
+    hand_set: &HandSet,
     match_results: Vec<(i64, String, i64, String, String, i32)>,
 ) -> Result<String, RusqliteError> {
     let mut announcement = String::new();
@@ -226,11 +510,15 @@ pub async fn announce_results(
     for (player_id, player_hand, opponent_id, opponent_hand, game_status, _round_id) in match_results {
         // Fetch usernames for player and opponent
         let player_username = get_username(db_pool, player_id).await?;
+        if game_status == "bye" {
+            announcement.push_str(&format!("@{} has a bye.\n", player_username));
+            continue;
+        }
         let opponent_username = get_username(db_pool, opponent_id).await?;
 
         // Convert hand to emoji
-        let player_hand_emoji = hand_to_emoji(&player_hand);
-        let opponent_hand_emoji = hand_to_emoji(&opponent_hand);
+        let player_hand_emoji = hand_set.emoji(&player_hand);
+        let opponent_hand_emoji = hand_set.emoji(&opponent_hand);
 
         // Construct the result message
         let result_message = match game_status.as_str() {
@@ -247,7 +535,7 @@ pub async fn announce_results(
     Ok(announcement)
 }
 
-async fn get_username(db_pool: &DbPool, player_id: i64) -> Result<String, RusqliteError> {
+pub async fn get_username(db_pool: &DbPool, player_id: i64) -> Result<String, RusqliteError> {
     let conn = db_pool.get().expect("Failed to get DB connection");
     conn.query_row(
         "SELECT player_username FROM PlayerDetailsTable WHERE player_id = ?1",
@@ -256,43 +544,90 @@ async fn get_username(db_pool: &DbPool, player_id: i64) -> Result<String, Rusqli
     )
 }
 
-fn hand_to_emoji(hand: &str) -> &str {
-    match hand {
-        "rock" => "🪨",
-        "paper" => "📜",
-        "scissors" => "✂️",
-        "" => "🚫",
-        _ => "❓",
+// Fetch leaderboard data, ranked with the configured tie-breakers
+pub async fn fetch_leaderboard(db_pool: &DbPool, season_id: i32) -> Result<Vec<Standing>, RusqliteError> {
+    season_standings(db_pool, season_id).await
+}
+
+// Prepare leaderboard string with medals. Tied players share their place and their medal.
+pub async fn prepare_leaderboard_string(leaderboard: Vec<Standing>) -> String {
+    let mut response = String::from("🏆 Leaderboard 🏆\n\n");
+    for line in leaderboard_lines(&leaderboard) {
+        response.push_str(&line);
+        response.push('\n');
     }
+    if let Some(note) = tie_breaker_note(&leaderboard) {
+        response.push_str(&format!("\n{}\n", note));
+    }
+
+    response
 }
 
-// Fetch leaderboard data
-pub async fn fetch_leaderboard(db_pool: &DbPool, season_id: i32) -> Result<Vec<(String, i32)>, RusqliteError> {
-    let conn = db_pool.get().expect("Failed to get DB connection");
-    let mut stmt = conn.prepare(
-        "SELECT player_username, score FROM PlayerDetailsTable WHERE season_id = ?1 ORDER BY score DESC, player_username",
-    )?;
-    let leaderboard = stmt.query_map(params![season_id], |row| {
-        Ok((row.get(0)?, row.get(1)?))
-    })?
-    .collect::<Result<Vec<(String, i32)>, RusqliteError>>()?;
+// One line per player, the first three places get a medal
+pub fn leaderboard_lines(leaderboard: &[Standing]) -> Vec<String> {
+    leaderboard
+        .iter()
+        .map(|standing| {
+            let position = match standing.rank {
+                1 => "🥇",
+                2 => "🥈",
+                3 => "🥉",
+                _ => "",
+            };
+            format!("{:<3} {:>2}. @{} - {:>3} points", position, standing.rank, standing.username, standing.score)
+        })
+        .collect()
+}
 
-    Ok(leaderboard)
+// Say how equal scores were ordered, if any are equal
+pub fn tie_breaker_note(leaderboard: &[Standing]) -> Option<String> {
+    let tie_breakers = &config::get().ranking.tie_breakers;
+    let has_equal_scores = leaderboard.windows(2).any(|pair| pair[0].score == pair[1].score);
+    if !has_equal_scores || tie_breakers.is_empty() {
+        return None;
+    }
+    let names = tie_breakers.iter().map(|tie_breaker| tie_breaker.as_str().replace('_', " ")).collect::<Vec<String>>().join(", ");
+    Some(format!("Equal scores are ranked by {}.", names))
 }
 
-// Prepare leaderboard string with medals
-pub async fn prepare_leaderboard_string(leaderboard: Vec<(String, i32)>) -> String {
-    let mut response = String::from("🏆 Leaderboard 🏆\n\n");
-    for (index, (username, score)) in leaderboard.iter().enumerate() {
-        let position = match index {
-            0 => "🥇",
-            1 => "🥈",
-            2 => "🥉",
-            _ => "",
-        };
-        response.push_str(&format!("{:<3} {:>2}. @{} - {:>3} points\n", position, index + 1, username, score));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use r2d2_sqlite::SqliteConnectionManager;
+    use crate::migrations::run_migrations;
+
+    fn test_pool(name: &str) -> (DbPool, std::path::PathBuf) {
+        let path = std::env::temp_dir().join(format!("{}_{}.db", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let pool = r2d2::Pool::builder().max_size(2).build(SqliteConnectionManager::file(&path)).unwrap();
+        run_migrations(&mut pool.get().unwrap()).unwrap();
+        (pool, path)
     }
 
-    response
-}
+    #[tokio::test]
+    async fn failed_close_gives_the_round_back() {
+        let (pool, path) = test_pool("failed_close");
+        {
+            let conn = pool.get().unwrap();
+            // A hand set that doesn't parse makes scoring fail right after the claim
+            conn.execute(
+                "INSERT INTO seasons (id, name, is_active, max_players, start_date, status, hand_set) VALUES (1, 'test', true, 10, CURRENT_TIMESTAMP, 'round_ongoing', 'bogus')",
+                [],
+            ).unwrap();
+            conn.execute("INSERT INTO MasterRoundTable (id, season_id, round_number, start_time) VALUES (7, 1, 1, CURRENT_TIMESTAMP)", []).unwrap();
+        }
+
+        assert!(close_current_round(&pool, 1).await.is_err());
+        assert_eq!(get_current_round_id(&pool, 1).await.unwrap(), Some(7));
 
+        // Fixed, the next attempt closes the round
+        pool.get().unwrap().execute("UPDATE seasons SET hand_set = 'rps' WHERE id = 1", []).unwrap();
+        assert!(close_current_round(&pool, 1).await.unwrap().is_some());
+        assert_eq!(get_current_round_id(&pool, 1).await.unwrap(), None);
+        assert_eq!(current_game_status_and_season_id(&pool).await.unwrap(), Some((SeasonPhase::StartGaming, 1)));
+
+        drop(pool);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,232 @@
+/// profile_commands.rs
+
+use teloxide::prelude::*;
+use std::{error::Error, sync::Arc};
+use rusqlite::{params, Connection, OptionalExtension, Error as RusqliteError};
+use crate::database::DbPool;
+use crate::ranking::season_standings;
+
+// A player's record over every season they played
+pub struct PlayerProfile {
+    pub username: String,
+    pub first_seen: String,
+    pub seasons_played: i32,
+    pub wins: i32,
+    pub draws: i32,
+    pub losses: i32,
+    pub byes: i32,
+    // (place, number of players, season name) of the best finished season
+    pub best_finish: Option<(i32, i32, String)>,
+    // The result of the latest matches in a row, e.g. ("won", 3)
+    pub streak: Option<(String, i32)>,
+    // Hands played, most played first
+    pub hands: Vec<(String, i32)>,
+}
+
+pub async fn profile_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>, username: String) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let user = match msg.from() {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+
+    let username = username.trim().trim_start_matches('@');
+    let player_id = if username.is_empty() {
+        let player_id = user.id.0 as i64;
+        if let Some(username) = &user.username {
+            upsert_player(&db_pool.get().expect("Failed to get DB connection"), player_id, username)?;
+        }
+        player_id
+    } else {
+        match find_player_id(db_pool, username).await? {
+            Some(player_id) => player_id,
+            None => {
+                bot.send_message(msg.chat.id, format!("No player @{} found.", username)).await?;
+                return Ok(());
+            }
+        }
+    };
+
+    let response = match player_profile(db_pool, player_id).await? {
+        Some(profile) => render_profile(&profile),
+        None => "You haven't signed up for a season yet. Use /signup when signups open.".to_string(),
+    };
+    bot.send_message(msg.chat.id, response).await?;
+
+    Ok(())
+}
+
+fn render_profile(profile: &PlayerProfile) -> String {
+    let mut text = format!("Profile of @{}\n", profile.username);
+    text.push_str(&format!("Playing since: {}\n", profile.first_seen));
+    text.push_str(&format!("Seasons played: {}\n", profile.seasons_played));
+
+    let mut record = format!("Won {}, drew {}, lost {}", profile.wins, profile.draws, profile.losses);
+    if profile.byes > 0 {
+        record.push_str(&format!(", {} bye{}", profile.byes, if profile.byes == 1 { "" } else { "s" }));
+    }
+    text.push_str(&format!("Record: {}\n", record));
+    let matches = profile.wins + profile.draws + profile.losses;
+    if let Some(win_rate) = (profile.wins * 100).checked_div(matches) {
+        text.push_str(&format!("Win rate: {}%\n", win_rate));
+    }
+
+    if let Some((place, players, season)) = &profile.best_finish {
+        text.push_str(&format!("Best finish: {} of {} in {}\n", ordinal(*place), players, season));
+    }
+    if let Some((status, length)) = &profile.streak {
+        let result = match status.as_str() {
+            "won" => if *length == 1 { "win" } else { "wins" },
+            "lost" => if *length == 1 { "loss" } else { "losses" },
+            _ => if *length == 1 { "draw" } else { "draws" },
+        };
+        text.push_str(&format!("Current streak: {} {}\n", length, result));
+    }
+
+    let played: i32 = profile.hands.iter().map(|(_, count)| count).sum();
+    if played > 0 {
+        let hands = profile
+            .hands
+            .iter()
+            .map(|(hand, count)| format!("{} {}%", hand, count * 100 / played))
+            .collect::<Vec<String>>()
+            .join(", ");
+        text.push_str(&format!("Hands: {}\n", hands));
+    }
+
+    text
+}
+
+fn ordinal(place: i32) -> String {
+    let suffix = match (place % 10, place % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", place, suffix)
+}
+
+// Keep the players table up to date whenever a player shows up with their username
+pub fn upsert_player(conn: &Connection, player_id: i64, username: &str) -> Result<(), RusqliteError> {
+    conn.execute(
+        "INSERT INTO players (player_id, username, first_seen, last_seen) VALUES (?1, ?2, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+         ON CONFLICT(player_id) DO UPDATE SET username = excluded.username, last_seen = CURRENT_TIMESTAMP",
+        params![player_id, username],
+    )?;
+    Ok(())
+}
+
+pub async fn find_player_id(db_pool: &DbPool, username: &str) -> Result<Option<i64>, RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    conn.query_row(
+        "SELECT player_id FROM players WHERE username = ?1 COLLATE NOCASE ORDER BY last_seen DESC LIMIT 1",
+        params![username],
+        |row| row.get(0),
+    ).optional()
+}
+
+pub async fn get_player_username(db_pool: &DbPool, player_id: i64) -> Result<Option<String>, RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    conn.query_row(
+        "SELECT username FROM players WHERE player_id = ?1",
+        params![player_id],
+        |row| row.get(0),
+    ).optional()
+}
+
+// Everything is computed from the rounds and standings of past seasons, nothing is kept per player
+pub async fn player_profile(db_pool: &DbPool, player_id: i64) -> Result<Option<PlayerProfile>, RusqliteError> {
+    let best_finish = best_finish(db_pool, player_id).await?;
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    let player: Option<(String, String)> = conn.query_row(
+        "SELECT username, first_seen FROM players WHERE player_id = ?1",
+        params![player_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).optional()?;
+    let (username, first_seen) = match player {
+        Some(player) => player,
+        None => return Ok(None),
+    };
+
+    let seasons_played: i32 = conn.query_row(
+        "SELECT COUNT(DISTINCT season_id) FROM PlayerDetailsTable WHERE player_id = ?1",
+        params![player_id],
+        |row| row.get(0),
+    )?;
+
+    // Oldest round first, byes don't break a streak
+    let mut stmt = conn.prepare(
+        "SELECT game_status, player_hand FROM RoundDetailsTable
+         WHERE player_id = ?1 AND game_status IS NOT NULL
+         ORDER BY round_id",
+    )?;
+    let rounds = stmt
+        .query_map(params![player_id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?.unwrap_or_default())))?
+        .collect::<Result<Vec<(String, String)>, RusqliteError>>()?;
+
+    let count = |status: &str| rounds.iter().filter(|(game_status, _)| game_status == status).count() as i32;
+    let (wins, draws, losses, byes) = (count("won"), count("draw"), count("lost"), count("bye"));
+
+    let mut streak: Option<(String, i32)> = None;
+    for (game_status, _) in rounds.iter().rev().filter(|(game_status, _)| game_status != "bye") {
+        match &mut streak {
+            None => streak = Some((game_status.clone(), 1)),
+            Some((status, length)) if status == game_status => *length += 1,
+            Some(_) => break,
+        }
+    }
+
+    let mut hands: Vec<(String, i32)> = Vec::new();
+    for (_, hand) in rounds.iter().filter(|(_, hand)| !hand.is_empty()) {
+        match hands.iter_mut().find(|(name, _)| name == hand) {
+            Some((_, count)) => *count += 1,
+            None => hands.push((hand.clone(), 1)),
+        }
+    }
+    hands.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+
+    Ok(Some(PlayerProfile {
+        username,
+        first_seen,
+        seasons_played,
+        wins,
+        draws,
+        losses,
+        byes,
+        best_finish,
+        streak,
+        hands,
+    }))
+}
+
+// The best rank in a finished season, the season with more players wins a tie
+async fn best_finish(db_pool: &DbPool, player_id: i64) -> Result<Option<(i32, i32, String)>, RusqliteError> {
+    let seasons = {
+        let conn = db_pool.get().expect("Failed to get DB connection");
+        let mut stmt = conn.prepare(
+            "SELECT s.id, s.name FROM PlayerDetailsTable pdt
+             JOIN seasons s ON s.id = pdt.season_id
+             WHERE pdt.player_id = ?1 AND s.is_active = false
+             ORDER BY s.id DESC",
+        )?;
+        let seasons = stmt
+            .query_map(params![player_id], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<Result<Vec<(i32, String)>, RusqliteError>>()?;
+        seasons
+    };
+
+    let mut best: Option<(i32, i32, String)> = None;
+    for (season_id, name) in seasons {
+        let standings = season_standings(db_pool, season_id).await?;
+        let players = standings.len() as i32;
+        if let Some(standing) = standings.iter().find(|standing| standing.player_id == player_id) {
+            let place = standing.rank as i32;
+            if best.as_ref().is_none_or(|(best_place, best_players, _)| place < *best_place || (place == *best_place && players > *best_players)) {
+                best = Some((place, players, name));
+            }
+        }
+    }
+
+    Ok(best)
+}
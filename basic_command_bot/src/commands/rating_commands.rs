@@ -0,0 +1,81 @@
+/// rating_commands.rs
+
+use teloxide::prelude::*;
+use std::{error::Error, sync::Arc};
+use crate::database::DbPool;
+use crate::rating::{player_rating, rating_changes, rating_ladder};
+
+use crate::commands::profile_commands::find_player_id;
+
+// Places shown by /ratings
+const LADDER_SIZE: usize = 20;
+// Rating changes shown by /rating
+const RECENT_CHANGES: i64 = 5;
+
+pub async fn rating_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>, username: String) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let user = match msg.from() {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+
+    let username = username.trim().trim_start_matches('@');
+    let (player_id, name) = if username.is_empty() {
+        (user.id.0 as i64, user.username.clone().unwrap_or_else(|| "you".to_string()))
+    } else {
+        match find_player_id(db_pool, username).await? {
+            Some(player_id) => (player_id, username.to_string()),
+            None => {
+                bot.send_message(msg.chat.id, format!("No player @{} found.", username)).await?;
+                return Ok(());
+            }
+        }
+    };
+
+    let ladder = rating_ladder(db_pool).await?;
+    let response = match ladder.iter().position(|rated| rated.player_id == player_id) {
+        Some(index) => {
+            let rated = &ladder[index];
+            let mut response = format!(
+                "Rating of @{}: {:.0}\nPlace {} of {} on the ladder, {} rated matches\n",
+                rated.username, rated.rating, index + 1, ladder.len(), rated.rated_matches
+            );
+            let changes = rating_changes(db_pool, player_id, RECENT_CHANGES).await?;
+            if !changes.is_empty() {
+                response.push_str("\nLatest matches:\n");
+                for change in changes {
+                    response.push_str(&format!(
+                        "{} against @{}: {:.0} → {:.0} ({:+.0})\n",
+                        change.result, change.opponent_username, change.rating_before, change.rating_after, change.rating_after - change.rating_before
+                    ));
+                }
+            }
+            response
+        }
+        None => format!(
+            "@{} has no rated matches yet and starts at {:.0}. Matches count once both players played a hand.",
+            name, player_rating(db_pool, player_id).await?
+        ),
+    };
+    bot.send_message(msg.chat.id, response).await?;
+
+    Ok(())
+}
+
+pub async fn ratings_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let ladder = rating_ladder(db_pool).await?;
+    if ladder.is_empty() {
+        bot.send_message(msg.chat.id, "Nobody has a rating yet.").await?;
+        return Ok(());
+    }
+
+    let mut response = String::from("📈 Ratings 📈\n\n");
+    for (index, rated) in ladder.iter().take(LADDER_SIZE).enumerate() {
+        response.push_str(&format!("{:>2}. @{} - {:.0} ({} matches)\n", index + 1, rated.username, rated.rating, rated.rated_matches));
+    }
+    if ladder.len() > LADDER_SIZE {
+        response.push_str(&format!("\n… and {} more. See your own with /rating.", ladder.len() - LADDER_SIZE));
+    }
+    bot.send_message(msg.chat.id, response).await?;
+
+    Ok(())
+}
@@ -3,38 +3,38 @@
 use teloxide::{prelude::* };
 use std::{error::Error, sync::Arc};
 use crate::database::{DbPool};
+use crate::audit::refuse;
+use crate::enums::SeasonPhase;
 use rusqlite::{params, Error as RusqliteError};
 
-use crate::admin::{
-    is_authorized_sender, 
-};
-
 use crate::commands::season:: {
     current_active_season_id,
     current_active_season_details
 };
+use crate::commands::profile_commands::upsert_player;
+use crate::messages::send_long_message;
 
 
 
 pub async fn approveplayer_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>, player_username: String) -> Result<(), Box<dyn Error + Send + Sync>> {
-    if !is_authorized_sender(&msg, db_pool) {
-        return Ok(());  // Early return if the sender is not authorized
-    }
-
     let player_username = player_username.trim();
     if player_username.split_whitespace().count() != 1 {
-        bot.send_message(msg.chat.id, "The command should be used like this '/approveplayer <username>'. Make sure the player username is correct and pending.").await?;
+        refuse(&bot, msg.chat.id, "The command should be used like this '/approveplayer <username>'. Make sure the player username is correct and pending.").await?;
         return Ok(());
     }
 
     let season_details = current_active_season_details(db_pool).await?;
     if let Some((season_id, season_name, _, _, status)) = season_details {
-        if status != "start_signup" {
-            bot.send_message(msg.chat.id, "Approvals are only allowed during the 'start_signup' phase.").await?;
+        if status != SeasonPhase::StartSignup {
+            refuse(&bot, msg.chat.id, "Approvals are only allowed during the 'start_signup' phase.").await?;
             return Ok(());
         }
 
         let (response_message, player_id) = update_player_status_to_accepted(db_pool, season_id, &player_username).await?;
+        if player_id == 0 {
+            refuse(&bot, msg.chat.id, response_message).await?;
+            return Ok(());
+        }
 
         bot.send_message(msg.chat.id, response_message).await?;
 
@@ -56,35 +56,35 @@ pub async fn approveplayer_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>
             }
         }
     } else {
-        bot.send_message(msg.chat.id, "No active season found.").await?;
+        refuse(&bot, msg.chat.id, "No active season found.").await?;
     }
 
     Ok(())
 }
 
 pub async fn refuseplayer_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>, player_username: String) -> Result<(), Box<dyn Error + Send + Sync>> {
-        if !is_authorized_sender(&msg, db_pool) {
-        return Ok(());  // Early return if the sender is not authorized
-    }
-
     let player_username = player_username.trim();
     if player_username.split_whitespace().count() != 1 {  
-        bot.send_message(msg.chat.id, "The command should be used like this '/refuseplayer <username>'. Make sure the player username is correct and pending.").await?;
+        refuse(&bot, msg.chat.id, "The command should be used like this '/refuseplayer <username>'. Make sure the player username is correct and pending.").await?;
         return Ok(());
     }
 
     let season_details = current_active_season_details(db_pool).await?;
     if let Some((season_id, _, _, _, status)) = season_details {
-        if status != "start_signup" {
-            bot.send_message(msg.chat.id, "Refusals are only allowed during the 'start_signup' phase.").await?;
+        if status != SeasonPhase::StartSignup {
+            refuse(&bot, msg.chat.id, "Refusals are only allowed during the 'start_signup' phase.").await?;
             return Ok(());
         }
 
         // Call the new function to update player status and get the response message
-        let response_message = update_player_status_to_refused(db_pool, season_id, &player_username).await?;
-        bot.send_message(msg.chat.id, response_message).await?;
+        match update_player_status_to_refused(db_pool, season_id, &player_username).await? {
+            (response_message, true) => {
+                bot.send_message(msg.chat.id, response_message).await?;
+            }
+            (response_message, false) => refuse(&bot, msg.chat.id, response_message).await?,
+        }
     } else {
-        bot.send_message(msg.chat.id, "No active season found.").await?;
+        refuse(&bot, msg.chat.id, "No active season found.").await?;
     }
     Ok(())
 }
@@ -112,6 +112,7 @@ pub async fn update_player_status_to_accepted(db_pool: &DbPool, season_id: i32,
             "INSERT INTO PlayerDetailsTable (season_id, player_id, player_username, score) VALUES (?1, ?2, ?3, 0)",
             params![season_id, player_id, player_username],
         )?;
+        upsert_player(&tx, player_id, player_username)?;
 
         tx.commit()?;
         Ok((format!("Player '{}' has been accepted for participation.", player_username), player_id))
@@ -123,7 +124,8 @@ pub async fn update_player_status_to_accepted(db_pool: &DbPool, season_id: i32,
 
 
 
-pub async fn update_player_status_to_refused(db_pool: &DbPool, season_id: i32, player_username: &str) -> Result<String, RusqliteError> {
+// The reply, and whether a pending player was refused
+pub async fn update_player_status_to_refused(db_pool: &DbPool, season_id: i32, player_username: &str) -> Result<(String, bool), RusqliteError> {
     let conn = db_pool.get().expect("Failed to get DB connection");
     let rows_updated = conn.execute(
         "UPDATE MasterCandidateTable SET player_status = 'refused' WHERE season_id = ?1 AND player_username = ?2 AND player_status = 'pending'",
@@ -131,9 +133,9 @@ pub async fn update_player_status_to_refused(db_pool: &DbPool, season_id: i32, p
     )?;
 
     Ok(if rows_updated > 0 {
-        format!("Player '{}' has been refused participation.", player_username)
+        (format!("Player '{}' has been refused participation.", player_username), true)
     } else {
-        "No pending player found with the given username for the current season.".to_string()
+        ("No pending player found with the given username for the current season.".to_string(), false)
     })
 }
 
@@ -143,10 +145,6 @@ pub async fn update_player_status_to_refused(db_pool: &DbPool, season_id: i32, p
 
 
 pub async fn view_signuplist_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
-        if !is_authorized_sender(&msg, db_pool) {
-        return Ok(());  // Early return if the sender is not authorized
-    }
-
     // Get the current active season ID
     let current_season_id = match current_active_season_id(db_pool).await? {
         Some(id) => id,
@@ -158,16 +156,12 @@ pub async fn view_signuplist_command(bot: Bot, msg: Message, db_pool: &Arc<DbPoo
 
     let signup_list = get_signup_list_for_season(db_pool, current_season_id, "all").await?;
 
-    bot.send_message(msg.chat.id, signup_list).await?;
+    send_long_message(&bot, msg.chat.id, &signup_list).await?;
     Ok(())
 }
 
 
 pub async fn view_approved_list_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
-        if !is_authorized_sender(&msg, db_pool) {
-        return Ok(());  // Early return if the sender is not authorized
-    }
-
     // Get the current active season ID
     let current_season_id = match current_active_season_id(db_pool).await? {
         Some(id) => id,
@@ -179,7 +173,7 @@ pub async fn view_approved_list_command(bot: Bot, msg: Message, db_pool: &Arc<Db
 
     let signup_list = get_signup_list_for_season(db_pool, current_season_id, "accepted").await?;
 
-    bot.send_message(msg.chat.id, signup_list).await?;
+    send_long_message(&bot, msg.chat.id, &signup_list).await?;
     Ok(())
 }
 
@@ -187,10 +181,6 @@ pub async fn view_approved_list_command(bot: Bot, msg: Message, db_pool: &Arc<Db
 
 
 pub async fn viewrefusedlist_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
-        if !is_authorized_sender(&msg, db_pool) {
-        return Ok(());  // Early return if the sender is not authorized
-    }
-
     // Get the current active season ID
     let current_season_id = match current_active_season_id(db_pool).await? {
         Some(id) => id,
@@ -202,7 +192,7 @@ pub async fn viewrefusedlist_command(bot: Bot, msg: Message, db_pool: &Arc<DbPoo
 
     let signup_list = get_signup_list_for_season(db_pool, current_season_id, "refused").await?;
 
-    bot.send_message(msg.chat.id, signup_list).await?;
+    send_long_message(&bot, msg.chat.id, &signup_list).await?;
     Ok(())
 }
 
@@ -0,0 +1,144 @@
+/// reminder_commands.rs
+
+use teloxide::{prelude::* };
+use std::{error::Error, sync::Arc};
+use crate::database::{DbPool};
+use crate::config;
+use rusqlite::{params, OptionalExtension, Error as RusqliteError};
+
+use crate::commands::season::{
+    get_players_without_moves,
+    season_uses_commit_reveal,
+};
+
+use crate::commands::grp_broadcast_commands::get_group_broadcast_chat_ids;
+
+pub async fn reminders_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>, setting: String) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let player_id = match msg.from() {
+        Some(user) => user.id.0 as i64,
+        None => return Ok(()),
+    };
+
+    let response = match setting.trim().to_lowercase().as_str() {
+        "on" => {
+            set_reminders_enabled(db_pool, player_id, true).await?;
+            "You will get a DM before a round closes if you haven't played yet.".to_string()
+        }
+        "off" => {
+            set_reminders_enabled(db_pool, player_id, false).await?;
+            "You will no longer get round reminders. Use '/reminders on' to get them back.".to_string()
+        }
+        "" => {
+            let enabled = reminders_enabled(db_pool, player_id).await?;
+            format!("Round reminders are {}. Use '/reminders on' or '/reminders off' to change it.", if enabled { "on" } else { "off" })
+        }
+        _ => "The command should be used like this '/reminders on' or '/reminders off'.".to_string(),
+    };
+    bot.send_message(msg.chat.id, response).await?;
+
+    Ok(())
+}
+
+// Send the reminders that are due for the ongoing round. Only one reminder goes out per check,
+// so a bot that was down for a while doesn't send the 10 minute and the 1 minute reminder at once.
+pub async fn send_due_reminders(bot: &Bot, db_pool: &DbPool, season_id: i32, round_id: i32) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let remaining_secs = match get_round_seconds_remaining(db_pool, round_id).await? {
+        Some(remaining_secs) if remaining_secs > 0 => remaining_secs,
+        _ => return Ok(()),
+    };
+
+    let offsets = &config::get().reminders.offsets_secs;
+    let mut due_offsets = Vec::new();
+    for &offset in offsets {
+        if remaining_secs <= offset && !reminder_sent(db_pool, round_id, offset).await? {
+            due_offsets.push(offset);
+        }
+    }
+    if due_offsets.is_empty() {
+        return Ok(());
+    }
+    for &offset in &due_offsets {
+        mark_reminder_sent(db_pool, round_id, offset).await?;
+    }
+
+    // Round to a readable amount, the check only runs every few seconds anyway
+    let time_left = if remaining_secs >= 60 {
+        format!("{} minutes", (remaining_secs + 30) / 60)
+    } else {
+        format!("{} seconds", remaining_secs)
+    };
+
+    let play_command = if season_uses_commit_reveal(db_pool, season_id).await? { "/commit" } else { "/play" };
+    let mut reminded = 0;
+    for player_id in get_players_without_moves(db_pool, season_id, round_id).await? {
+        if !reminders_enabled(db_pool, player_id).await? {
+            continue;
+        }
+        let reminder = format!("⏰ The round closes in about {} and you haven't played your hand yet. Use {}! (Turn these off with '/reminders off'.)", time_left, play_command);
+        match bot.send_message(ChatId(player_id), reminder).await {
+            Ok(_) => reminded += 1,
+            Err(e) => log::warn!("Could not send a round reminder to player {}: {}", player_id, e),
+        }
+    }
+    log::info!("Sent {} round reminders for round {}, {} left", reminded, round_id, time_left);
+
+    let (broadcast_chat_id, _) = get_group_broadcast_chat_ids(db_pool).await?;
+    if let Some(broadcast_chat_id) = broadcast_chat_id {
+        if let Err(e) = bot.send_message(broadcast_chat_id, format!("⏳ About {} left in this round!", time_left)).await {
+            log::error!("Failed to send the round countdown: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+
+pub async fn set_reminders_enabled(pool: &DbPool, player_id: i64, enabled: bool) -> Result<(), RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    conn.execute(
+        "INSERT INTO player_settings (player_id, reminders_enabled) VALUES (?1, ?2)
+         ON CONFLICT(player_id) DO UPDATE SET reminders_enabled = excluded.reminders_enabled",
+        params![player_id, enabled],
+    )?;
+    Ok(())
+}
+
+// Reminders are on unless the player turned them off
+pub async fn reminders_enabled(pool: &DbPool, player_id: i64) -> Result<bool, RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    let enabled: Option<bool> = conn.query_row(
+        "SELECT reminders_enabled FROM player_settings WHERE player_id = ?1",
+        params![player_id],
+        |row| row.get(0),
+    ).optional()?;
+    Ok(enabled.unwrap_or(true))
+}
+
+async fn reminder_sent(pool: &DbPool, round_id: i32, offset_secs: i64) -> Result<bool, RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM round_reminders WHERE round_id = ?1 AND offset_secs = ?2",
+        params![round_id, offset_secs],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+async fn mark_reminder_sent(pool: &DbPool, round_id: i32, offset_secs: i64) -> Result<(), RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    conn.execute(
+        "INSERT INTO round_reminders (round_id, offset_secs, sent_at) VALUES (?1, ?2, CURRENT_TIMESTAMP) ON CONFLICT DO NOTHING",
+        params![round_id, offset_secs],
+    )?;
+    Ok(())
+}
+
+// Seconds until the deadline of a round, or None if it has no deadline
+pub async fn get_round_seconds_remaining(pool: &DbPool, round_id: i32) -> Result<Option<i64>, RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    conn.query_row(
+        "SELECT CAST(ROUND((julianday(deadline) - julianday('now')) * 86400) AS INTEGER) FROM MasterRoundTable WHERE id = ?1",
+        params![round_id],
+        |row| row.get(0),
+    )
+}
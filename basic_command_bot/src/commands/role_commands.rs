@@ -0,0 +1,319 @@
+/// role_commands.rs
+
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, User};
+use std::{error::Error, sync::Arc};
+use rusqlite::Error as RusqliteError;
+use crate::database::DbPool;
+use crate::admin::{display_user, grant_role, has_role, has_role_in, holds_role, remaining_holders, revoke_role, Role};
+use crate::audit::{record, refuse, set_outcome, AuditEntry};
+
+use crate::commands::profile_commands::{find_player_id, get_player_username};
+use crate::commands::season::find_season;
+
+// Confirming to take a role away from yourself: "revoke:<user id>:<role>:<season id, 0 for every season>"
+// or "revoke:<user id>:cancel"
+pub const REVOKE_CALLBACK_PREFIX: &str = "revoke:";
+
+// "/grant @juno admin", "/grant 12345 moderator Summer Cup"
+pub struct RoleRequest {
+    pub user_id: Option<i64>,
+    pub username: Option<String>,
+    pub role: Role,
+    pub season: Option<(i32, String)>,
+}
+
+impl RoleRequest {
+    fn target(&self) -> String {
+        display_user(self.user_id, self.username.as_deref())
+    }
+
+    fn scope(&self) -> String {
+        match &self.season {
+            Some((_, name)) => format!(" in season {}", name),
+            None => String::new(),
+        }
+    }
+
+    fn season_id(&self) -> Option<i32> {
+        self.season.as_ref().map(|season| season.0)
+    }
+
+    fn is_user(&self, user: &User) -> bool {
+        match self.user_id {
+            Some(user_id) => user_id == user.id.0 as i64,
+            None => self.username.as_deref().zip(user.username.as_deref()).is_some_and(|(a, b)| a.eq_ignore_ascii_case(b)),
+        }
+    }
+}
+
+pub async fn grant_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>, args: String) -> Result<(), Box<dyn Error + Send + Sync>> {
+    match parse_role_request(&bot, &msg, db_pool, &args, "grant").await? {
+        Some(request) => grant_request(bot, msg, db_pool, request).await,
+        None => Ok(()),
+    }
+}
+
+pub async fn revoke_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>, args: String) -> Result<(), Box<dyn Error + Send + Sync>> {
+    match parse_role_request(&bot, &msg, db_pool, &args, "revoke").await? {
+        Some(request) => revoke_request(bot, msg, db_pool, request).await,
+        None => Ok(()),
+    }
+}
+
+// Shared by /grant and /addadmin
+pub async fn grant_request(bot: Bot, msg: Message, db_pool: &DbPool, request: RoleRequest) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if let Some(refusal) = scope_refusal(db_pool, msg.from(), &request, "grant")? {
+        refuse(&bot, msg.chat.id, refusal).await?;
+        return Ok(());
+    }
+
+    let granted_by = msg.from().map(|user| user.id.0 as i64).unwrap_or_default();
+    let granted = match grant_role(db_pool, request.user_id, request.username.as_deref(), request.role, request.season_id(), granted_by) {
+        Ok(granted) => granted,
+        Err(e) => return Err(report_db_error(&bot, msg.chat.id, &format!("grant {} the {} role", request.target(), request.role), e).await),
+    };
+
+    if !granted {
+        refuse(&bot, msg.chat.id, format!("{} is already {}{}.", request.target(), request.role, request.scope())).await?;
+        return Ok(());
+    }
+
+    let response = if request.user_id.is_none() {
+        format!(
+            "{} is now {}{}. The bot hasn't seen this user yet, so the role is tied to their user id when they first message the bot.",
+            request.target(), request.role, request.scope()
+        )
+    } else {
+        format!("{} is now {}{}.", request.target(), request.role, request.scope())
+    };
+    bot.send_message(msg.chat.id, response).await?;
+    Ok(())
+}
+
+// Shared by /revoke and /removeadmin. Taking a role away from yourself waits for a button press, see revoke_callback.
+pub async fn revoke_request(bot: Bot, msg: Message, db_pool: &DbPool, request: RoleRequest) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let sender = match msg.from() {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+    if let Some(refusal) = scope_refusal(db_pool, Some(sender), &request, "revoke")? {
+        refuse(&bot, msg.chat.id, refusal).await?;
+        return Ok(());
+    }
+    if let Some(refusal) = revoke_refusal(db_pool, sender, &request)? {
+        refuse(&bot, msg.chat.id, refusal).await?;
+        return Ok(());
+    }
+
+    if request.is_user(sender) {
+        let confirm = format!("{}{}:{}:{}", REVOKE_CALLBACK_PREFIX, sender.id, request.role, request.season_id().unwrap_or(0));
+        let cancel = format!("{}{}:cancel", REVOKE_CALLBACK_PREFIX, sender.id);
+        let keyboard = InlineKeyboardMarkup::new(vec![vec![
+            InlineKeyboardButton::callback(format!("Yes, remove my {} role", request.role), confirm),
+            InlineKeyboardButton::callback("Cancel", cancel),
+        ]]);
+        bot.send_message(
+            msg.chat.id,
+            format!("You are about to take the {} role{} away from yourself. Only someone else can give it back.", request.role, request.scope()),
+        ).reply_markup(keyboard).await?;
+        set_outcome("waiting for confirmation".to_string());
+        return Ok(());
+    }
+
+    match revoke_now(db_pool, &request) {
+        Ok((response, true)) => {
+            bot.send_message(msg.chat.id, response).await?;
+        }
+        Ok((response, false)) => refuse(&bot, msg.chat.id, response).await?,
+        Err(e) => return Err(report_db_error(&bot, msg.chat.id, &format!("take the {} role away from {}", request.role, request.target()), e).await),
+    }
+    Ok(())
+}
+
+// The Yes/Cancel buttons of taking a role away from yourself. Only that user can press them.
+pub async fn revoke_callback(bot: Bot, q: CallbackQuery, db_pool: &DbPool, data: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let parts: Vec<&str> = data.split(':').collect();
+    let user_id = match parts.first().and_then(|user_id| user_id.parse::<i64>().ok()) {
+        Some(user_id) => user_id,
+        None => {
+            bot.answer_callback_query(q.id).await?;
+            return Ok(());
+        }
+    };
+    if q.from.id.0 as i64 != user_id {
+        bot.answer_callback_query(q.id).text("Only the one who asked can confirm this.").await?;
+        return Ok(());
+    }
+
+    let (role, season_id) = match parts.as_slice() {
+        [_, role, season_id] => match (role.parse::<Role>(), season_id.parse::<i32>()) {
+            (Ok(role), Ok(season_id)) => (role, season_id),
+            _ => {
+                bot.answer_callback_query(q.id).await?;
+                return Ok(());
+            }
+        },
+        _ => {
+            bot.answer_callback_query(q.id.clone()).text("Nothing changed.").await?;
+            if let Some(message) = q.message {
+                bot.edit_message_text(message.chat.id, message.id, "Nothing changed, you keep your role.").await?;
+            }
+            return Ok(());
+        }
+    };
+
+    let season = match season_id {
+        0 => None,
+        season_id => match find_season(db_pool, &season_id.to_string()).await? {
+            Some(season) => Some(season),
+            None => {
+                bot.answer_callback_query(q.id).text("That season no longer exists.").await?;
+                return Ok(());
+            }
+        },
+    };
+    let request = RoleRequest { user_id: Some(user_id), username: q.from.username.clone(), role, season };
+
+    // Someone else may have changed the roles since the question was asked
+    let response = match revoke_refusal(db_pool, &q.from, &request)? {
+        Some(refusal) => refusal,
+        None => {
+            let (response, revoked) = revoke_now(db_pool, &request)?;
+            let entry = AuditEntry {
+                actor_id: Some(user_id),
+                actor_username: q.from.username.clone(),
+                command: "/revoke".to_string(),
+                arguments: Some(format!("{} {}{}", user_id, role, request.scope())),
+                target: Some(user_id.to_string()),
+                result: if revoked { "ok, confirmed".to_string() } else { format!("refused: {}", response) },
+            };
+            record(&bot, db_pool, &entry).await?;
+            response
+        }
+    };
+
+    bot.answer_callback_query(q.id.clone()).await?;
+    if let Some(message) = q.message {
+        bot.edit_message_text(message.chat.id, message.id, response).await?;
+    }
+    Ok(())
+}
+
+// The reply, and whether there was a granted role to take away
+fn revoke_now(db_pool: &DbPool, request: &RoleRequest) -> Result<(String, bool), RusqliteError> {
+    if revoke_role(db_pool, request.user_id, request.username.as_deref(), request.role, request.season_id())? == 0 {
+        let response = format!(
+            "{} wasn't granted {}{}. Owners and devs from the configuration can only be removed there.",
+            request.target(), request.role, request.scope()
+        );
+        return Ok((response, false));
+    }
+    Ok((format!("{} is no longer {}{}.", request.target(), request.role, request.scope()), true))
+}
+
+// Why the sender may not hand out or take away this role, if they may not. Admins hand out admin and
+// moderator, owners everything. A role for one season only reaches as far as that season.
+fn scope_refusal(db_pool: &DbPool, sender: Option<&User>, request: &RoleRequest, command: &str) -> Result<Option<String>, RusqliteError> {
+    let sender = match sender {
+        Some(sender) => sender,
+        None => return Ok(Some(format!("You can only {} roles you hold yourself.", command))),
+    };
+    if has_role_in(db_pool, sender, request.role, request.season_id())? {
+        return Ok(None);
+    }
+
+    let refusal = if request.season.is_none() && has_role(db_pool, sender, request.role) {
+        format!(
+            "Your {} role only counts in the active season, so you can only {} it there. Add the season name, or ask an owner to {} {} in every season.",
+            request.role, command, command, request.role
+        )
+    } else {
+        format!("You can only {} roles you hold yourself, ask an owner to {} {}.", command, command, request.role)
+    };
+    Ok(Some(refusal))
+}
+
+// Why the sender may not take this role away, if they may not
+fn revoke_refusal(db_pool: &DbPool, sender: &User, request: &RoleRequest) -> Result<Option<String>, RusqliteError> {
+    // Owners answer only to owners, which protects the owners from the configuration
+    if !has_role(db_pool, sender, Role::Owner) && holds_role(db_pool, request.user_id, request.username.as_deref(), Role::Owner)? {
+        return Ok(Some(format!("{} is an owner, only owners can take their roles away.", request.target())));
+    }
+
+    // Somebody has to be left to run the bot
+    if request.season.is_none()
+        && matches!(request.role, Role::Owner | Role::Admin)
+        && remaining_holders(db_pool, request.user_id, request.username.as_deref(), request.role)? == 0
+    {
+        return Ok(Some(format!(
+            "{} is the last {}. Grant the role to someone else first, otherwise nobody could run the bot.",
+            request.target(), request.role
+        )));
+    }
+    Ok(None)
+}
+
+// Tell the sender that the database refused, not just the log. The error is passed on for the audit log.
+async fn report_db_error(bot: &Bot, chat_id: ChatId, action: &str, e: RusqliteError) -> Box<dyn Error + Send + Sync> {
+    log::error!("Failed to {}: {}", action, e);
+    if let Err(send_error) = bot.send_message(chat_id, format!("Failed to {}: {}", action, e)).await {
+        log::error!("Failed to report a database error: {}", send_error);
+    }
+    e.into()
+}
+
+// Read "<user> <role> [season]". Whether the sender may hand out that role is up to scope_refusal.
+// Tells the sender what is wrong and returns None if the request can't go ahead.
+async fn parse_role_request(bot: &Bot, msg: &Message, db_pool: &DbPool, args: &str, command: &str) -> Result<Option<RoleRequest>, Box<dyn Error + Send + Sync>> {
+    let mut parts = args.split_whitespace();
+    let (target, role) = match (parts.next(), parts.next()) {
+        (Some(target), Some(role)) => (target, role),
+        _ => {
+            refuse(
+                bot,
+                msg.chat.id,
+                format!("The command should be used like this '/{} <@username or user id> <admin|moderator|dev|owner> [season name or id]'.", command),
+            ).await?;
+            return Ok(None);
+        }
+    };
+
+    let role = match role.parse::<Role>() {
+        Ok(Role::Player) => {
+            refuse(bot, msg.chat.id, "Everyone is a player, there is nothing to grant or revoke.").await?;
+            return Ok(None);
+        }
+        Ok(role) => role,
+        Err(e) => {
+            refuse(bot, msg.chat.id, e).await?;
+            return Ok(None);
+        }
+    };
+
+    let season = parts.collect::<Vec<&str>>().join(" ");
+    let season = if season.is_empty() {
+        None
+    } else {
+        match find_season(db_pool, &season).await? {
+            Some(season) => Some(season),
+            None => {
+                refuse(bot, msg.chat.id, format!("No season {} found.", season)).await?;
+                return Ok(None);
+            }
+        }
+    };
+
+    let (user_id, username) = resolve_user(db_pool, target).await?;
+    Ok(Some(RoleRequest { user_id, username, role, season }))
+}
+
+// "@name", "name" or a numeric user id. For a username the user id is known if they ever signed up.
+pub async fn resolve_user(db_pool: &DbPool, target: &str) -> Result<(Option<i64>, Option<String>), RusqliteError> {
+    let target = target.trim_start_matches('@');
+    if let Ok(user_id) = target.parse::<i64>() {
+        let username = get_player_username(db_pool, user_id).await?;
+        return Ok((Some(user_id), username));
+    }
+    Ok((find_player_id(db_pool, target).await?, Some(target.to_string())))
+}
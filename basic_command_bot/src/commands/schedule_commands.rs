@@ -0,0 +1,312 @@
+/// schedule_commands.rs
+
+use teloxide::{prelude::* };
+use std::{error::Error, sync::Arc};
+use crate::database::{DbPool};
+use crate::audit::refuse;
+use crate::enums::SeasonPhase;
+use rusqlite::{params, OptionalExtension, Error as RusqliteError};
+
+use crate::commands::season::{
+    current_active_season_details,
+};
+
+// Rounds need to last long enough for people to actually play
+const MIN_ROUND_DURATION_SECS: i64 = 60;
+const MAX_SCHEDULED_ROUNDS: i64 = 100;
+
+// A season's round schedule, as stored in round_schedule
+#[derive(Debug, Clone)]
+pub struct RoundSchedule {
+    pub season_id: i32,
+    pub rounds_remaining: i64,
+    pub round_duration_secs: i64,
+    pub gap_secs: i64,
+    pub next_start: Option<String>,
+    pub status: ScheduleStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleStatus {
+    Running,
+    Paused,
+    Cancelled,
+    Finished,
+}
+
+impl ScheduleStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScheduleStatus::Running => "running",
+            ScheduleStatus::Paused => "paused",
+            ScheduleStatus::Cancelled => "cancelled",
+            ScheduleStatus::Finished => "finished",
+        }
+    }
+
+    fn from_db(s: &str) -> ScheduleStatus {
+        match s {
+            "running" => ScheduleStatus::Running,
+            "paused" => ScheduleStatus::Paused,
+            "finished" => ScheduleStatus::Finished,
+            _ => ScheduleStatus::Cancelled,
+        }
+    }
+}
+
+// Parse durations like "90s", "10m", "2h" or "1d" into seconds
+pub fn parse_duration(input: &str) -> Option<i64> {
+    let input = input.trim();
+    let split_at = input.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = input.split_at(split_at);
+    let amount: i64 = amount.parse().ok()?;
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return None,
+    };
+    amount.checked_mul(multiplier)
+}
+
+pub fn format_duration(secs: i64) -> String {
+    if secs % 3600 == 0 && secs >= 3600 {
+        format!("{}h", secs / 3600)
+    } else if secs % 60 == 0 && secs >= 60 {
+        format!("{}m", secs / 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+pub async fn schedule_rounds_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>, schedule_info: String) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let usage = "The command should be used like this '/schedulerounds <count> <duration> <gap>', for example '/schedulerounds 5 10m 2m'. Durations take s, m, h or d.";
+    let parts: Vec<&str> = schedule_info.split_whitespace().collect();
+    if parts.len() != 3 {
+        refuse(&bot, msg.chat.id, usage).await?;
+        return Ok(());
+    }
+
+    let count: i64 = match parts[0].parse() {
+        Ok(count) if count > 0 && count <= MAX_SCHEDULED_ROUNDS => count,
+        _ => {
+            refuse(&bot, msg.chat.id, format!("The number of rounds should be between 1 and {}.", MAX_SCHEDULED_ROUNDS)).await?;
+            return Ok(());
+        }
+    };
+    let (round_duration_secs, gap_secs) = match (parse_duration(parts[1]), parse_duration(parts[2])) {
+        (Some(duration), Some(gap)) => (duration, gap),
+        _ => {
+            refuse(&bot, msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+    if round_duration_secs < MIN_ROUND_DURATION_SECS {
+        refuse(&bot, msg.chat.id, format!("A round should last at least {}.", format_duration(MIN_ROUND_DURATION_SECS))).await?;
+        return Ok(());
+    }
+
+    // Rounds can only be scheduled once the gaming phase is on
+    let season_id = match current_active_season_details(db_pool).await? {
+        Some((season_id, _, _, _, SeasonPhase::StartGaming)) | Some((season_id, _, _, _, SeasonPhase::RoundOngoing)) => season_id,
+        Some(_) => {
+            refuse(&bot, msg.chat.id, "Rounds can only be scheduled during the gaming phase. Use /startgamingphase first.").await?;
+            return Ok(());
+        }
+        None => {
+            refuse(&bot, msg.chat.id, "No active season found.").await?;
+            return Ok(());
+        }
+    };
+
+    save_schedule(db_pool, season_id, count, round_duration_secs, gap_secs).await?;
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "Scheduled {} rounds of {} with {} between them. The first one starts as soon as no round is ongoing.",
+            count,
+            format_duration(round_duration_secs),
+            format_duration(gap_secs)
+        ),
+    ).await?;
+
+    Ok(())
+}
+
+pub async fn pause_schedule_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let response = match active_season_schedule(db_pool).await? {
+        Some(schedule) if schedule.status == ScheduleStatus::Running => {
+            set_schedule_status(db_pool, schedule.season_id, ScheduleStatus::Paused).await?;
+            "The round schedule is paused. An ongoing round still closes at its deadline. Use /resumeschedule to continue.".to_string()
+        }
+        Some(schedule) => {
+            refuse(&bot, msg.chat.id, format!("The round schedule is {}, there is nothing to pause.", schedule.status.as_str())).await?;
+            return Ok(());
+        }
+        None => {
+            refuse(&bot, msg.chat.id, "There is no round schedule for the current season.").await?;
+            return Ok(());
+        }
+    };
+    bot.send_message(msg.chat.id, response).await?;
+
+    Ok(())
+}
+
+pub async fn resume_schedule_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let response = match active_season_schedule(db_pool).await? {
+        Some(schedule) if schedule.status == ScheduleStatus::Paused => {
+            set_schedule_status(db_pool, schedule.season_id, ScheduleStatus::Running).await?;
+            format!("The round schedule is running again, {} rounds to go.", schedule.rounds_remaining)
+        }
+        Some(schedule) => {
+            refuse(&bot, msg.chat.id, format!("The round schedule is {}, there is nothing to resume.", schedule.status.as_str())).await?;
+            return Ok(());
+        }
+        None => {
+            refuse(&bot, msg.chat.id, "There is no round schedule for the current season.").await?;
+            return Ok(());
+        }
+    };
+    bot.send_message(msg.chat.id, response).await?;
+
+    Ok(())
+}
+
+pub async fn cancel_schedule_command(bot: Bot, msg: Message, db_pool: &Arc<DbPool>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let response = match active_season_schedule(db_pool).await? {
+        Some(schedule) if matches!(schedule.status, ScheduleStatus::Running | ScheduleStatus::Paused) => {
+            set_schedule_status(db_pool, schedule.season_id, ScheduleStatus::Cancelled).await?;
+            format!("The round schedule is cancelled, {} remaining rounds dropped. An ongoing round still closes at its deadline, or use /stopround.", schedule.rounds_remaining)
+        }
+        Some(schedule) => {
+            refuse(&bot, msg.chat.id, format!("The round schedule is already {}.", schedule.status.as_str())).await?;
+            return Ok(());
+        }
+        None => {
+            refuse(&bot, msg.chat.id, "There is no round schedule for the current season.").await?;
+            return Ok(());
+        }
+    };
+    bot.send_message(msg.chat.id, response).await?;
+
+    Ok(())
+}
+
+
+// Create or replace the schedule of a season. The first round may start right away.
+pub async fn save_schedule(pool: &DbPool, season_id: i32, rounds: i64, round_duration_secs: i64, gap_secs: i64) -> Result<(), RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    conn.execute(
+        "INSERT INTO round_schedule (season_id, rounds_remaining, round_duration_secs, gap_secs, next_start, status)
+         VALUES (?1, ?2, ?3, ?4, CURRENT_TIMESTAMP, 'running')
+         ON CONFLICT(season_id) DO UPDATE SET
+            rounds_remaining = excluded.rounds_remaining,
+            round_duration_secs = excluded.round_duration_secs,
+            gap_secs = excluded.gap_secs,
+            next_start = excluded.next_start,
+            status = excluded.status",
+        params![season_id, rounds, round_duration_secs, gap_secs],
+    )?;
+    Ok(())
+}
+
+pub async fn set_schedule_status(pool: &DbPool, season_id: i32, status: ScheduleStatus) -> Result<(), RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    conn.execute(
+        "UPDATE round_schedule SET status = ?1 WHERE season_id = ?2",
+        params![status.as_str(), season_id],
+    )?;
+    Ok(())
+}
+
+pub async fn get_schedule(pool: &DbPool, season_id: i32) -> Result<Option<RoundSchedule>, RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    conn.query_row(
+        "SELECT season_id, rounds_remaining, round_duration_secs, gap_secs, next_start, status FROM round_schedule WHERE season_id = ?1",
+        params![season_id],
+        |row| {
+            let status: String = row.get(5)?;
+            Ok(RoundSchedule {
+                season_id: row.get(0)?,
+                rounds_remaining: row.get(1)?,
+                round_duration_secs: row.get(2)?,
+                gap_secs: row.get(3)?,
+                next_start: row.get(4)?,
+                status: ScheduleStatus::from_db(&status),
+            })
+        },
+    ).optional()
+}
+
+pub async fn active_season_schedule(pool: &DbPool) -> Result<Option<RoundSchedule>, RusqliteError> {
+    match current_active_season_details(pool).await? {
+        Some((season_id, _, _, _, _)) => get_schedule(pool, season_id).await,
+        None => Ok(None),
+    }
+}
+
+// Whether the schedule wants a new round to start now
+pub async fn scheduled_round_due(pool: &DbPool, season_id: i32) -> Result<Option<RoundSchedule>, RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    let due: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM round_schedule
+         WHERE season_id = ?1 AND status = 'running' AND rounds_remaining > 0
+           AND next_start IS NOT NULL AND next_start <= CURRENT_TIMESTAMP",
+        params![season_id],
+        |row| row.get(0),
+    )?;
+    drop(conn);
+
+    if due {
+        get_schedule(pool, season_id).await
+    } else {
+        Ok(None)
+    }
+}
+
+// Called when the scheduler starts a round: one less to go, and no next start until this one closes
+pub async fn mark_scheduled_round_started(pool: &DbPool, season_id: i32) -> Result<(), RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    conn.execute(
+        "UPDATE round_schedule SET rounds_remaining = rounds_remaining - 1, next_start = NULL WHERE season_id = ?1",
+        params![season_id],
+    )?;
+    Ok(())
+}
+
+// Called whenever a round closes, by hand or by deadline. The next scheduled round waits for the gap.
+pub async fn reschedule_after_round(pool: &DbPool, season_id: i32) -> Result<(), RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    conn.execute(
+        "UPDATE round_schedule SET
+            next_start = datetime('now', '+' || gap_secs || ' seconds'),
+            status = CASE WHEN status = 'running' AND rounds_remaining <= 0 THEN 'finished' ELSE status END
+         WHERE season_id = ?1",
+        params![season_id],
+    )?;
+    Ok(())
+}
+
+// The ongoing round of a season if its deadline has passed
+pub async fn get_expired_round_id(pool: &DbPool, season_id: i32) -> Result<Option<i32>, RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    conn.query_row(
+        "SELECT id FROM MasterRoundTable
+         WHERE season_id = ?1 AND end_time IS NULL AND deadline IS NOT NULL AND deadline <= CURRENT_TIMESTAMP
+         ORDER BY id DESC LIMIT 1",
+        params![season_id],
+        |row| row.get(0),
+    ).optional()
+}
+
+// Deadline of a round, as stored (UTC)
+pub async fn get_round_deadline(pool: &DbPool, round_id: i32) -> Result<Option<String>, RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    conn.query_row(
+        "SELECT deadline FROM MasterRoundTable WHERE id = ?1",
+        params![round_id],
+        |row| row.get(0),
+    )
+}
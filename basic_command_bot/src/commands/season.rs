@@ -1,25 +1,98 @@
 /// season.rs
 
 use crate::database::DbPool;
-use rusqlite::{params, OptionalExtension, Error as RusqliteError};
+use crate::enums::{SeasonPhase, SeasonFormat};
+use crate::pairing::PairingKind;
+use crate::hands::HandSet;
+use crate::scoring::{set_season_scoring_rules_on, ScoringRules};
+use crate::config;
+use crate::commands::leaderboard_commands::{rank_season_winners, record_season_winners_on};
+use rusqlite::{params, Connection, OptionalExtension, Error as RusqliteError};
+
+// Settings picked when a season starts, given to /startnewseason as key=value
+#[derive(Debug, Clone)]
+pub struct SeasonOptions {
+    pub format: SeasonFormat,
+    // Only used by leagues, knockout seasons are paired by their bracket
+    pub pairing: PairingKind,
+    // Throws per match, the first to a majority wins it
+    pub best_of: i32,
+    // Custom sets have spaces in them, so they are set with /sethands instead
+    pub hands: HandSet,
+}
 
-// Function to start a new season
-pub async fn start_new_season(pool: &DbPool, name: &str, max_players: i32) -> Result<(), RusqliteError> {
-    let conn = pool.get().expect("Failed to get connection from pool");
-    conn.execute(
-        "INSERT INTO seasons (name, is_active, max_players, start_date, stop_date) VALUES (?1, true, ?2, CURRENT_TIMESTAMP, NULL)",
-        params![name, max_players],
+impl Default for SeasonOptions {
+    fn default() -> Self {
+        SeasonOptions { format: SeasonFormat::League, pairing: PairingKind::Random, best_of: 1, hands: HandSet::rps() }
+    }
+}
+
+impl SeasonOptions {
+    pub const USAGE: &'static str = "[format=league|knockout] [pairing=random|swiss|roundrobin] [best_of=1|3|5] [hands=rps|rpsls]";
+
+    pub fn parse(options: &[&str]) -> Result<SeasonOptions, String> {
+        let mut season_options = SeasonOptions::default();
+        for option in options {
+            let (key, value) = option
+                .split_once('=')
+                .ok_or_else(|| format!("Options look like key=value, got '{}'", option))?;
+            match key.to_lowercase().as_str() {
+                "format" => season_options.format = value.to_lowercase().parse()?,
+                "pairing" => season_options.pairing = value.to_lowercase().parse()?,
+                "hands" => {
+                    season_options.hands = match value.to_lowercase().as_str() {
+                        "rps" => HandSet::rps(),
+                        "rpsls" => HandSet::rpsls(),
+                        _ => return Err(format!("Unknown hand set '{}', use rps or rpsls. Custom sets are defined with /sethands", value)),
+                    }
+                }
+                "best_of" => {
+                    season_options.best_of = match value.parse() {
+                        Ok(best_of @ (1 | 3 | 5)) => best_of,
+                        _ => return Err(format!("Matches are best of 1, 3 or 5, got '{}'", value)),
+                    }
+                }
+                _ => return Err(format!("Unknown option '{}'", key)),
+            }
+        }
+        Ok(season_options)
+    }
+}
+
+// Function to start a new season. Its scoring rules start out as the configured defaults.
+pub async fn start_new_season(pool: &DbPool, name: &str, max_players: i32, options: &SeasonOptions) -> Result<(), RusqliteError> {
+    let mut conn = pool.get().expect("Failed to get connection from pool");
+    let tx = conn.transaction()?;
+    tx.execute(
+        "INSERT INTO seasons (name, is_active, max_players, start_date, stop_date, pairing, format, best_of, hand_set) VALUES (?1, true, ?2, CURRENT_TIMESTAMP, NULL, ?3, ?4, ?5, ?6)",
+        params![name, max_players, options.pairing, options.format, options.best_of, options.hands],
     )?;
+    let season_id = tx.last_insert_rowid() as i32;
+    set_season_scoring_rules_on(&tx, season_id, &ScoringRules::from(&config::get().scoring))?;
+    tx.commit()?;
     Ok(())
 }
 
 // Function to stop the current season
-pub async fn stop_current_season(pool: &DbPool) -> Result<(), RusqliteError> {
-    let conn = pool.get().expect("Failed to get connection from pool");
-    conn.execute(
-        "UPDATE seasons SET status = 'closed', is_active = false, stop_date = CURRENT_TIMESTAMP WHERE is_active = true",
+pub async fn stop_current_season(pool: &DbPool, from: SeasonPhase) -> Result<(), TransitionError> {
+    // The winners are stored with the close, for /seasons. Ranking takes its own connection, so it goes first.
+    let winners = match current_active_season_id(pool).await? {
+        Some(season_id) => Some((season_id, rank_season_winners(pool, season_id).await?)),
+        None => None,
+    };
+    let mut conn = pool.get().expect("Failed to get connection from pool");
+    let tx = conn.transaction()?;
+
+    transition_on(&tx, from, SeasonPhase::Closed)?;
+    tx.execute(
+        "UPDATE seasons SET is_active = false, stop_date = CURRENT_TIMESTAMP WHERE is_active = true",
         [],
     )?;
+    if let Some((season_id, winners)) = winners {
+        record_season_winners_on(&tx, season_id, &winners)?;
+    }
+
+    tx.commit()?;
     Ok(())
 }
 
@@ -45,8 +118,38 @@ pub async fn current_active_season_id(pool: &DbPool) -> Result<Option<i32>, Rusq
     Ok(season_id)
 }
 
+// The season given by id or name, or the active season (or else the latest one) if none is given
+pub async fn find_season(pool: &DbPool, season: &str) -> Result<Option<(i32, String)>, RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    if season.is_empty() {
+        return conn.query_row(
+            "SELECT id, name FROM seasons ORDER BY is_active DESC, id DESC LIMIT 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).optional();
+    }
+
+    if let Ok(season_id) = season.parse::<i32>() {
+        let found = conn.query_row(
+            "SELECT id, name FROM seasons WHERE id = ?1",
+            params![season_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).optional()?;
+        if found.is_some() {
+            return Ok(found);
+        }
+    }
+
+    // Season names don't have to be unique, take the newest
+    conn.query_row(
+        "SELECT id, name FROM seasons WHERE name = ?1 COLLATE NOCASE ORDER BY id DESC LIMIT 1",
+        params![season],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).optional()
+}
+
 // Function to get details of the current active season
-pub async fn current_active_season_details(pool: &DbPool) -> Result<Option<(i32, String, String, i32, String)>, RusqliteError> {
+pub async fn current_active_season_details(pool: &DbPool) -> Result<Option<(i32, String, String, i32, SeasonPhase)>, RusqliteError> {
     let conn = pool.get().expect("Failed to get connection from pool");
     let query = "
         SELECT id, name, start_date, max_players, status
@@ -62,69 +165,160 @@ pub async fn current_active_season_details(pool: &DbPool) -> Result<Option<(i32,
         let name: String = row.get(1)?;
         let start_date: String = row.get(2)?;
         let max_players: i32 = row.get(3)?;
-        let status: String = row.get(4)?;
+        let status: SeasonPhase = row.get(4)?;
         Ok(Some((season_id, name, start_date, max_players, status)))
     } else {
         Ok(None)
     }
 }
 
-pub async fn start_signup_phase(pool: &DbPool) -> Result<(), RusqliteError> {
-    let conn = pool.get().expect("Failed to get connection from pool");
-    let rows_updated = conn.execute(
-        "UPDATE seasons SET status = 'start_signup' WHERE is_active = TRUE AND (status = 'initial' OR status = 'stopped_signup')",
-        [],
-    )?;
+// Error returned when a season cannot move to the requested phase
+#[derive(Debug)]
+pub enum TransitionError {
+    Illegal { from: SeasonPhase, to: SeasonPhase },
+    NotInPhase { expected: SeasonPhase },
+    Database(RusqliteError),
+}
 
-    if rows_updated == 0 {
-        Err(RusqliteError::QueryReturnedNoRows) // or use a custom error
-    } else {
-        Ok(())
+impl std::fmt::Display for TransitionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransitionError::Illegal { from, to } => write!(f, "a season cannot go from '{}' to '{}'", from, to),
+            TransitionError::NotInPhase { expected } => write!(f, "the active season is no longer in the '{}' phase", expected),
+            TransitionError::Database(e) => write!(f, "database error: {}", e),
+        }
     }
 }
 
-pub async fn stop_signup_phase(pool: &DbPool) -> Result<(), RusqliteError> {
-    let conn = pool.get().expect("Failed to get connection from pool");
+impl std::error::Error for TransitionError {}
+
+impl From<RusqliteError> for TransitionError {
+    fn from(e: RusqliteError) -> Self {
+        TransitionError::Database(e)
+    }
+}
+
+// Move the active season from one phase to another, checking the transition table.
+// Takes a plain connection so it can also run inside a transaction.
+pub fn transition_on(conn: &Connection, from: SeasonPhase, to: SeasonPhase) -> Result<(), TransitionError> {
+    if !from.can_transition_to(to) {
+        return Err(TransitionError::Illegal { from, to });
+    }
+
     let rows_updated = conn.execute(
-        "UPDATE seasons SET status = 'stopped_signup' WHERE is_active = TRUE AND status = 'start_signup'",
-        [],
+        "UPDATE seasons SET status = ?1 WHERE is_active = TRUE AND status = ?2",
+        params![to, from],
     )?;
 
     if rows_updated == 0 {
-        Err(RusqliteError::QueryReturnedNoRows) // or use a custom error
+        Err(TransitionError::NotInPhase { expected: from })
     } else {
         Ok(())
     }
 }
 
-pub async fn start_gaming_phase(pool: &DbPool) -> Result<(), RusqliteError> {
+pub async fn transition(pool: &DbPool, from: SeasonPhase, to: SeasonPhase) -> Result<(), TransitionError> {
     let conn = pool.get().expect("Failed to get connection from pool");
-    let rows_updated = conn.execute(
-        "UPDATE seasons SET status = 'start_gaming' WHERE is_active = TRUE AND (status = 'stopped_signup' OR status = 'stopped_gaming')",
-        [],
+    transition_on(&conn, from, to)
+}
+
+
+// Whether players may change their hand until the round closes
+pub async fn season_allows_hand_change(pool: &DbPool, season_id: i32) -> Result<bool, RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    conn.query_row(
+        "SELECT allow_hand_change FROM seasons WHERE id = ?1",
+        params![season_id],
+        |row| row.get(0),
+    )
+}
+
+pub async fn set_season_allows_hand_change(pool: &DbPool, season_id: i32, allow: bool) -> Result<(), RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    conn.execute(
+        "UPDATE seasons SET allow_hand_change = ?1 WHERE id = ?2",
+        params![allow, season_id],
     )?;
+    Ok(())
+}
 
-    if rows_updated == 0 {
-        Err(RusqliteError::QueryReturnedNoRows) // or use a custom error
-    } else {
-        Ok(())
-    }
+// Whether the season hides hands behind commitments until the round closes
+pub async fn season_uses_commit_reveal(pool: &DbPool, season_id: i32) -> Result<bool, RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    conn.query_row(
+        "SELECT commit_reveal FROM seasons WHERE id = ?1",
+        params![season_id],
+        |row| row.get(0),
+    )
 }
 
-pub async fn stop_gaming_phase(pool: &DbPool) -> Result<(), RusqliteError> {
+// Whether the season plays its matches throw by throw, see series_commands.rs: best-of matches, and
+// knockout matches, where a drawn throw is replayed right away. Commit–reveal knockouts play single hands.
+pub async fn season_plays_series(pool: &DbPool, season_id: i32) -> Result<bool, RusqliteError> {
     let conn = pool.get().expect("Failed to get connection from pool");
-    let rows_updated = conn.execute(
-        "UPDATE seasons SET status = 'stopped_gaming' WHERE is_active = TRUE AND status = 'start_gaming'",
-        [],
+    conn.query_row(
+        "SELECT best_of > 1 OR (format = 'knockout' AND NOT commit_reveal) FROM seasons WHERE id = ?1",
+        params![season_id],
+        |row| row.get(0),
+    )
+}
+
+pub async fn set_season_commit_reveal(pool: &DbPool, season_id: i32, enabled: bool) -> Result<(), RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    conn.execute(
+        "UPDATE seasons SET commit_reveal = ?1 WHERE id = ?2",
+        params![enabled, season_id],
     )?;
+    Ok(())
+}
 
-    if rows_updated == 0 {
-        Err(RusqliteError::QueryReturnedNoRows) // or use a custom error
-    } else {
-        Ok(())
-    }
+// How the season pairs its players when a round closes
+pub async fn season_pairing(pool: &DbPool, season_id: i32) -> Result<PairingKind, RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    conn.query_row(
+        "SELECT pairing FROM seasons WHERE id = ?1",
+        params![season_id],
+        |row| row.get(0),
+    )
+}
+
+pub async fn season_format(pool: &DbPool, season_id: i32) -> Result<SeasonFormat, RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    conn.query_row(
+        "SELECT format FROM seasons WHERE id = ?1",
+        params![season_id],
+        |row| row.get(0),
+    )
+}
+
+// How many throws a match of the season has. 1 is a single throw, decided when the round closes.
+pub async fn season_best_of(pool: &DbPool, season_id: i32) -> Result<i32, RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    conn.query_row(
+        "SELECT best_of FROM seasons WHERE id = ?1",
+        params![season_id],
+        |row| row.get(0),
+    )
+}
+
+// The hands the season plays with
+pub async fn season_hand_set(pool: &DbPool, season_id: i32) -> Result<HandSet, RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    conn.query_row(
+        "SELECT hand_set FROM seasons WHERE id = ?1",
+        params![season_id],
+        |row| row.get(0),
+    )
 }
 
+pub async fn set_season_hand_set(pool: &DbPool, season_id: i32, hand_set: &HandSet) -> Result<(), RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    conn.execute(
+        "UPDATE seasons SET hand_set = ?1 WHERE id = ?2",
+        params![hand_set, season_id],
+    )?;
+    Ok(())
+}
 
 // Function to get the next round number for the current active season
 pub async fn get_next_round_number(pool: &DbPool, current_season_id: &i32) -> Result<i32, RusqliteError> {
@@ -143,36 +337,70 @@ pub async fn get_next_round_number(pool: &DbPool, current_season_id: &i32) -> Re
 }
 
 
-pub async fn start_new_round(pool: &DbPool, current_active_season_id_variable: i32, next_round_number_variable: i32) -> Result<(), RusqliteError> {
+// Start a round. With a duration the round gets a deadline, after which the scheduler closes it.
+pub async fn start_new_round(pool: &DbPool, current_active_season_id_variable: i32, next_round_number_variable: i32, duration_secs: Option<i64>) -> Result<(), TransitionError> {
     let mut conn = pool.get().expect("Failed to get connection from pool");
 
     // Start a transaction
     let tx = conn.transaction()?;
 
     // Insert a new round into MasterRoundTable
+    // datetime() returns NULL for a NULL modifier, so rounds without a duration have no deadline
     tx.execute(
-        "INSERT INTO MasterRoundTable (season_id, round_number, start_time) VALUES (?1, ?2, CURRENT_TIMESTAMP)",
-        params![current_active_season_id_variable, next_round_number_variable],
+        "INSERT INTO MasterRoundTable (season_id, round_number, start_time, deadline) VALUES (?1, ?2, CURRENT_TIMESTAMP, datetime('now', ?3))",
+        params![current_active_season_id_variable, next_round_number_variable, duration_secs.map(|secs| format!("+{} seconds", secs))],
     )?;
 
     // Update the status in the Seasons table
-    let rows_updated = tx.execute(
-        "UPDATE Seasons SET status = 'round_ongoing' WHERE id = ?1 AND status = 'start_gaming'",
-        params![current_active_season_id_variable],
+    transition_on(&tx, SeasonPhase::StartGaming, SeasonPhase::RoundOngoing)?;
+
+    // Commit the transaction
+    tx.commit()?;
+    Ok(())
+}
+
+
+// Close a commit–reveal round for new commitments. Players have until the reveal deadline to reveal.
+pub async fn start_reveal_phase(pool: &DbPool, current_active_season_id_variable: i32, reveal_secs: i64) -> Result<(), TransitionError> {
+    let mut conn = pool.get().expect("Failed to get connection from pool");
+    let tx = conn.transaction()?;
+
+    tx.execute(
+        "UPDATE MasterRoundTable SET reveal_deadline = datetime('now', ?2) WHERE season_id = ?1 AND end_time IS NULL",
+        params![current_active_season_id_variable, format!("+{} seconds", reveal_secs)],
     )?;
+    transition_on(&tx, SeasonPhase::RoundOngoing, SeasonPhase::RoundRevealing)?;
 
-    if rows_updated == 0 {
-        // No rows were updated
-        Err(RusqliteError::QueryReturnedNoRows) // or a custom error
-    } else {
-        // Commit the transaction
-        tx.commit()?;
-        Ok(())
-    }    
+    tx.commit()?;
+    Ok(())
+}
+
+// Claim the open round for closing by giving it its end time. Only one of /stopround and the
+// scheduler gets it, the other finds the round gone and leaves it alone.
+pub async fn claim_round(pool: &DbPool, round_id: i32) -> Result<bool, RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    let claimed = conn.execute(
+        "UPDATE MasterRoundTable SET end_time = CURRENT_TIMESTAMP WHERE id = ?1 AND end_time IS NULL",
+        params![round_id],
+    )?;
+    Ok(claimed == 1)
 }
 
+// Undo claim_round when closing the round failed. Once end_current_round has moved the season
+// out of the round, the round stays closed.
+pub async fn release_round(pool: &DbPool, season_id: i32, round_id: i32) -> Result<(), RusqliteError> {
+    let conn = pool.get().expect("Failed to get connection from pool");
+    conn.execute(
+        "UPDATE MasterRoundTable SET end_time = NULL
+         WHERE id = ?1 AND (SELECT status FROM seasons WHERE id = ?2) IN (?3, ?4)",
+        params![round_id, season_id, SeasonPhase::RoundOngoing, SeasonPhase::RoundRevealing],
+    )?;
+    Ok(())
+}
 
-pub async fn end_current_round(pool: &DbPool, current_active_season_id_variable: i32) -> Result<(), RusqliteError> {
+// End the open round. `from` is RoundOngoing, or RoundRevealing for commit–reveal rounds.
+// A round claimed with claim_round already has its end time, then only the phase changes.
+pub async fn end_current_round(pool: &DbPool, current_active_season_id_variable: i32, from: SeasonPhase) -> Result<(), TransitionError> {
     let mut conn = pool.get().expect("Failed to get connection from pool");
 
     // Start a transaction
@@ -185,27 +413,17 @@ pub async fn end_current_round(pool: &DbPool, current_active_season_id_variable:
     )?;
 
     // Update the status in the Seasons table
-    let rows_updated = tx.execute(
-        "UPDATE Seasons SET status = 'start_gaming' WHERE id = ?1 AND status = 'round_ongoing'",
-        params![current_active_season_id_variable],
-    )?;
+    transition_on(&tx, from, SeasonPhase::StartGaming)?;
 
-    if rows_updated == 0 {
-        // No rows were updated
-        Err(RusqliteError::QueryReturnedNoRows) // or a custom error
-    } else {
-        // Commit the transaction
-        tx.commit()?;
-        Ok(())
-    }
-        
+    // Commit the transaction
+    tx.commit()?;
+    Ok(())
 }
 
 pub async fn play_empty_hands_for_players(db_pool: &DbPool, season_id: i32, round_id: i32) -> Result<Vec<i64>, RusqliteError> {
-    let conn = db_pool.get().expect("Failed to get DB connection");
-
     // Get the list of player IDs who have not played in this round
     let player_ids = get_players_without_moves(db_pool, season_id, round_id).await?;
+    let conn = db_pool.get().expect("Failed to get DB connection");
 
     // Insert an empty hand for each of these players
     for &player_id in &player_ids {
@@ -219,7 +437,9 @@ pub async fn play_empty_hands_for_players(db_pool: &DbPool, season_id: i32, roun
 }
 
 
-
+// Players who neither played a hand, committed to one nor made a throw in their best-of match.
+// In a knockout season only players with an open match in the bracket count, and a best-of bye
+// has nothing to play.
 pub async fn get_players_without_moves(db_pool: &DbPool, season_id: i32, round_id: i32) -> Result<Vec<i64>, RusqliteError> {
     let conn = db_pool.get().expect("Failed to get DB connection");
 
@@ -227,7 +447,19 @@ pub async fn get_players_without_moves(db_pool: &DbPool, season_id: i32, round_i
         "SELECT pd.player_id
          FROM PlayerDetailsTable pd
          LEFT JOIN RoundDetailsTable rd ON pd.player_id = rd.player_id AND rd.round_id = ?2
-         WHERE pd.season_id = ?1 AND rd.player_id IS NULL",
+         LEFT JOIN hand_commitments hc ON pd.player_id = hc.player_id AND hc.round_id = ?2
+         JOIN seasons s ON s.id = pd.season_id
+         WHERE pd.season_id = ?1 AND rd.player_id IS NULL AND hc.player_id IS NULL
+           AND NOT EXISTS (
+               SELECT 1 FROM match_throws mt JOIN round_matches rm ON rm.id = mt.match_id
+               WHERE rm.round_id = ?2 AND mt.player_id = pd.player_id)
+           AND NOT EXISTS (
+               SELECT 1 FROM round_matches rm
+               WHERE rm.round_id = ?2 AND rm.player_one = pd.player_id AND rm.player_two IS NULL)
+           AND (s.format != 'knockout' OR EXISTS (
+               SELECT 1 FROM bracket_matches bm
+               WHERE bm.season_id = pd.season_id AND bm.winner IS NULL AND bm.player_one IS NOT NULL AND bm.player_two IS NOT NULL
+                 AND pd.player_id IN (bm.player_one, bm.player_two)))",
     )?;
 
     let rows = stmt.query_map(params![season_id, round_id], |row| row.get(0))?;
@@ -0,0 +1,442 @@
+/// series_commands.rs
+
+use teloxide::{prelude::* };
+use std::error::Error;
+use crate::database::{DbPool};
+use crate::enums::SeasonFormat;
+use rusqlite::{params, Connection, TransactionBehavior, OptionalExtension, Error as RusqliteError};
+
+use crate::commands::season::{
+    season_best_of,
+    season_plays_series,
+    season_format,
+    season_pairing,
+    season_hand_set,
+};
+
+use crate::commands::playing_commands::{
+    get_current_round_id,
+    get_username,
+    PlayOutcome,
+};
+
+use crate::commands::knockout_commands::{
+    get_season_player_ids,
+    knockout_pairs,
+};
+
+use crate::commands::basic_commands::hand_keyboard;
+use crate::pairing::load_pairing_context;
+use crate::hands::HandSet;
+
+// A match of a best-of round. Best-of seasons pair players when the round starts,
+// so that they can throw against each other one throw at a time.
+pub struct RoundMatch {
+    pub id: i64,
+    pub player_one: i64,
+    // None when player_one has a bye
+    pub player_two: Option<i64>,
+    pub winner: Option<i64>,
+}
+
+impl RoundMatch {
+    pub fn opponent_of(&self, player_id: i64) -> Option<i64> {
+        if player_id == self.player_one { self.player_two } else { Some(self.player_one) }
+    }
+}
+
+// One player's hand in one throw of a match
+pub struct MatchThrow {
+    pub throw_number: i32,
+    pub player_id: i64,
+    pub player_hand: String,
+}
+
+// Where a match stands after its throws
+struct SeriesScore {
+    wins_one: i32,
+    wins_two: i32,
+    // Throws both players made
+    completed: i32,
+    // The hands of every counted throw, of player one and player two
+    hands_one: Vec<String>,
+    hands_two: Vec<String>,
+}
+
+impl SeriesScore {
+    fn decided(&self, best_of: i32) -> bool {
+        self.wins_one.max(self.wins_two) >= majority(best_of)
+    }
+}
+
+// How a match is won, for the messages. A single-throw knockout match replays its draws.
+fn match_terms(best_of: i32) -> String {
+    if best_of > 1 {
+        format!("best of {}: the first to win {} throws takes the match", best_of, majority(best_of))
+    } else {
+        "one throw decides the match, a draw is thrown again".to_string()
+    }
+}
+
+// Throws needed to win a best-of-N match
+fn majority(best_of: i32) -> i32 {
+    best_of / 2 + 1
+}
+
+// Score a match from its throws. Drawn throws don't count for either player. The open throw,
+// where only one player threw, only counts when settling: the missing hand loses it.
+fn series_score(hand_set: &HandSet, round_match: &RoundMatch, throws: &[MatchThrow], settle: bool) -> SeriesScore {
+    let hand_of = |throw_number: i32, player_id: Option<i64>| {
+        throws
+            .iter()
+            .find(|t| t.throw_number == throw_number && Some(t.player_id) == player_id)
+            .map(|t| t.player_hand.clone())
+    };
+    let last_throw = throws.iter().map(|t| t.throw_number).max().unwrap_or(0);
+
+    let mut score = SeriesScore { wins_one: 0, wins_two: 0, completed: 0, hands_one: Vec::new(), hands_two: Vec::new() };
+    for throw_number in 1..=last_throw {
+        let (hand_one, hand_two) = match (hand_of(throw_number, Some(round_match.player_one)), hand_of(throw_number, round_match.player_two)) {
+            (Some(hand_one), Some(hand_two)) => {
+                score.completed += 1;
+                (hand_one, hand_two)
+            }
+            (hand_one, hand_two) if settle => (hand_one.unwrap_or_default(), hand_two.unwrap_or_default()),
+            _ => continue,
+        };
+        match hand_set.result(&hand_one, &hand_two) {
+            "won" => score.wins_one += 1,
+            "lost" => score.wins_two += 1,
+            _ => {}
+        }
+        score.hands_one.push(hand_one);
+        score.hands_two.push(hand_two);
+    }
+    score
+}
+
+// Pair the players of a best-of or knockout round as soon as it starts and DM everyone their opponent.
+// Other single-throw seasons pair when the round closes, so this does nothing for them.
+pub async fn start_series_matches(bot: &Bot, db_pool: &DbPool, season_id: i32) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if !season_plays_series(db_pool, season_id).await? {
+        return Ok(());
+    }
+    let best_of = season_best_of(db_pool, season_id).await?;
+    let round_id = get_current_round_id(db_pool, season_id)
+        .await?
+        .ok_or("No current round ID found")?;
+    let hand_set = season_hand_set(db_pool, season_id).await?;
+
+    // Nobody has played yet, so every hand is empty
+    let (pairs, bye) = if season_format(db_pool, season_id).await? == SeasonFormat::Knockout {
+        (knockout_pairs(db_pool, season_id, Vec::new()).await?, None)
+    } else {
+        let players = get_season_player_ids(db_pool, season_id)
+            .await?
+            .into_iter()
+            .map(|player_id| (player_id, String::new()))
+            .collect();
+        let pairing = season_pairing(db_pool, season_id).await?;
+        let pairing_context = load_pairing_context(db_pool, season_id, round_id).await?;
+        let pairings = pairing.strategy().pair(players, &pairing_context);
+        (pairings.pairs, pairings.unpaired)
+    };
+    log::debug!("Best of {} matches for round {}: {:?}, bye: {:?}", best_of, round_id, pairs, bye);
+
+    for ((player_one, _), (player_two, _)) in pairs {
+        insert_round_match(db_pool, round_id, player_one, Some(player_two)).await?;
+        for (player_id, opponent_id) in [(player_one, player_two), (player_two, player_one)] {
+            let text = format!(
+                "This round you play @{}, {}.\nThrow 1, pick your hand:",
+                get_username(db_pool, opponent_id).await?,
+                match_terms(best_of)
+            );
+            notify_player(bot, player_id, text, Some(&hand_set)).await;
+        }
+    }
+    if let Some((player_id, _)) = bye {
+        insert_round_match(db_pool, round_id, player_id, None).await?;
+        notify_player(bot, player_id, "You have a bye this round, there is nothing to play.".to_string(), None).await;
+    }
+
+    Ok(())
+}
+
+// Play the player's next throw in their best-of or knockout match. Once both players threw, both get
+// the result and, unless the match is decided, are asked for the next throw.
+pub async fn play_series_throw(bot: &Bot, db_pool: &DbPool, hand_set: &HandSet, round_id: i32, player_id: i64, hand: &str, best_of: i32) -> Result<PlayOutcome, Box<dyn Error + Send + Sync>> {
+    let round_match = match get_player_match(db_pool, round_id, player_id).await? {
+        Some(round_match) => round_match,
+        None => return Ok(PlayOutcome::NotInGame),
+    };
+    if round_match.player_two.is_none() {
+        return Ok(PlayOutcome::SeriesBye);
+    }
+
+    let throws = match record_throw(db_pool, hand_set, &round_match, player_id, hand, best_of).await? {
+        ThrowRecord::Recorded(throws) => throws,
+        ThrowRecord::AlreadyThrown => return Ok(PlayOutcome::AlreadyPlayed),
+        ThrowRecord::Decided => return Ok(PlayOutcome::SeriesDecided),
+    };
+
+    // The player has one hand per throw, so this is the number of the throw just made
+    let throw_number = throws.iter().filter(|t| t.player_id == player_id).count() as i32;
+    let score = series_score(hand_set, &round_match, &throws, false);
+    if score.completed == throw_number {
+        notify_throw_result(bot, db_pool, hand_set, &round_match, &score, best_of).await?;
+    }
+
+    Ok(PlayOutcome::Thrown(hand_set.label(hand)))
+}
+
+// Tell both players how the last throw went and where their match stands
+async fn notify_throw_result(bot: &Bot, db_pool: &DbPool, hand_set: &HandSet, round_match: &RoundMatch, score: &SeriesScore, best_of: i32) -> Result<(), RusqliteError> {
+    let player_two = match round_match.player_two {
+        Some(player_two) => player_two,
+        None => return Ok(()),
+    };
+    let hand_one = score.hands_one.last().cloned().unwrap_or_default();
+    let hand_two = score.hands_two.last().cloned().unwrap_or_default();
+    let decided = score.decided(best_of);
+
+    let sides = [
+        (round_match.player_one, player_two, hand_one.clone(), hand_two.clone(), score.wins_one, score.wins_two),
+        (player_two, round_match.player_one, hand_two, hand_one, score.wins_two, score.wins_one),
+    ];
+    for (player_id, opponent_id, hand, opponent_hand, wins, opponent_wins) in sides {
+        let opponent_username = get_username(db_pool, opponent_id).await?;
+        let throw_result = match hand_set.result(&hand, &opponent_hand) {
+            "won" => "you take it".to_string(),
+            "lost" => format!("@{} takes it", opponent_username),
+            _ => "a draw, it doesn't count".to_string(),
+        };
+        let mut text = format!(
+            "Throw {}: your {} against @{}'s {}, {}. Score {}–{}.",
+            score.completed,
+            hand_set.emoji(&hand),
+            opponent_username,
+            hand_set.emoji(&opponent_hand),
+            throw_result,
+            wins,
+            opponent_wins
+        );
+        if !decided {
+            text.push_str(&format!("\nThrow {}, pick your hand:", score.completed + 1));
+        } else if wins > opponent_wins {
+            text.push_str("\n🏆 You won the match! The points are added when the round closes.");
+        } else {
+            text.push_str("\nYou lost the match.");
+        }
+        notify_player(bot, player_id, text, if decided { None } else { Some(hand_set) }).await;
+    }
+
+    Ok(())
+}
+
+// DM a player, with the hand picker if they have a throw to make.
+// Players who never opened a DM with the bot are only logged.
+async fn notify_player(bot: &Bot, player_id: i64, text: String, hand_picker: Option<&HandSet>) {
+    let request = bot.send_message(ChatId(player_id), text);
+    let sent = match hand_picker {
+        Some(hand_set) => request.reply_markup(hand_keyboard(hand_set)).await,
+        None => request.await,
+    };
+    if let Err(e) = sent {
+        log::warn!("Could not send a match update to player {}: {}", player_id, e);
+    }
+}
+
+// Decide every match of a best-of or knockout round that is closing. The open throw goes to whoever threw,
+// then whoever won more throws takes the match, the same number is a draw.
+// Returns the results to score, like evaluate_matches does, and the announcement.
+pub async fn settle_series(db_pool: &DbPool, hand_set: &HandSet, round_id: i32) -> Result<(Vec<(i64, String, i64, String, String, i32)>, String), RusqliteError> {
+    let mut results = Vec::new();
+    let mut report = String::new();
+
+    for round_match in get_round_matches(db_pool, round_id).await? {
+        let player_one = round_match.player_one;
+        let player_one_username = get_username(db_pool, player_one).await?;
+
+        // A bye only has the player's row. There is no opponent, the player's own id only fills the slot.
+        let player_two = match round_match.player_two {
+            Some(player_two) => player_two,
+            None => {
+                insert_round_details(db_pool, round_id, player_one, "").await?;
+                results.push((player_one, String::new(), player_one, String::new(), "bye".to_string(), round_id));
+                report.push_str(&format!("@{} has a bye.\n", player_one_username));
+                continue;
+            }
+        };
+        let player_two_username = get_username(db_pool, player_two).await?;
+
+        let throws = get_match_throws(db_pool, round_match.id).await?;
+        let score = series_score(hand_set, &round_match, &throws, true);
+
+        // The round details keep the last hand each player threw. Only a player who never threw
+        // is left with an empty hand, and scored as not having played.
+        let last_hand = |hands: &[String]| hands.iter().rev().find(|hand| !hand.is_empty()).cloned().unwrap_or_default();
+        let hand_one = last_hand(&score.hands_one);
+        let hand_two = last_hand(&score.hands_two);
+        insert_round_details(db_pool, round_id, player_one, &hand_one).await?;
+        insert_round_details(db_pool, round_id, player_two, &hand_two).await?;
+
+        let game_status = match score.wins_one.cmp(&score.wins_two) {
+            std::cmp::Ordering::Greater => "won",
+            std::cmp::Ordering::Less => "lost",
+            std::cmp::Ordering::Equal => "draw",
+        };
+
+        let line = if throws.is_empty() {
+            format!("@{} and @{} made no throws. It's a draw!", player_one_username, player_two_username)
+        } else {
+            let hands_one: String = score.hands_one.iter().map(|hand| hand_set.emoji(hand)).collect();
+            let hands_two: String = score.hands_two.iter().map(|hand| hand_set.emoji(hand)).collect();
+            match game_status {
+                "won" => format!("@{} beat @{} {}–{} ({} vs {})", player_one_username, player_two_username, score.wins_one, score.wins_two, hands_one, hands_two),
+                "lost" => format!("@{} beat @{} {}–{} ({} vs {})", player_two_username, player_one_username, score.wins_two, score.wins_one, hands_two, hands_one),
+                _ => format!("@{} and @{} drew {}–{} ({} vs {})", player_one_username, player_two_username, score.wins_one, score.wins_two, hands_one, hands_two),
+            }
+        };
+        report.push_str(&line);
+        report.push('\n');
+
+        results.push((player_one, hand_one, player_two, hand_two, game_status.to_string(), round_id));
+    }
+
+    Ok((results, report))
+}
+
+// What /myhand shows in a best-of or knockout round
+pub async fn series_status(db_pool: &DbPool, hand_set: &HandSet, round_id: i32, player_id: i64, best_of: i32) -> Result<String, RusqliteError> {
+    let round_match = match get_player_match(db_pool, round_id, player_id).await? {
+        Some(round_match) => round_match,
+        None => return Ok(PlayOutcome::NotInGame.message()),
+    };
+    let opponent_id = match round_match.opponent_of(player_id) {
+        Some(opponent_id) => opponent_id,
+        None => return Ok(PlayOutcome::SeriesBye.message()),
+    };
+
+    let throws = get_match_throws(db_pool, round_match.id).await?;
+    let score = series_score(hand_set, &round_match, &throws, false);
+    let (wins, opponent_wins) = if player_id == round_match.player_one {
+        (score.wins_one, score.wins_two)
+    } else {
+        (score.wins_two, score.wins_one)
+    };
+    let opponent_username = get_username(db_pool, opponent_id).await?;
+
+    let mut response = if best_of > 1 {
+        format!("Your match against @{}, best of {}: {}–{}.", opponent_username, best_of, wins, opponent_wins)
+    } else {
+        format!("Your match against @{}.", opponent_username)
+    };
+    let open_throw = score.completed + 1;
+    if round_match.winner.is_some() {
+        response.push_str("\nThe match is decided.");
+    } else if let Some(throw) = throws.iter().find(|t| t.throw_number == open_throw && t.player_id == player_id) {
+        response.push_str(&format!("\nThrow {}: you threw {}, waiting for @{}.", open_throw, hand_set.label(&throw.player_hand), opponent_username));
+    } else {
+        response.push_str(&format!("\nThrow {}: pick your hand with /play.", open_throw));
+    }
+
+    Ok(response)
+}
+
+enum ThrowRecord {
+    Recorded(Vec<MatchThrow>),
+    AlreadyThrown,
+    Decided,
+}
+
+// Add a hand to the open throw of a match, and set the winner once someone has a majority.
+// The transaction takes the write lock up front, so two players throwing at once see each other's hand.
+async fn record_throw(db_pool: &DbPool, hand_set: &HandSet, round_match: &RoundMatch, player_id: i64, hand: &str, best_of: i32) -> Result<ThrowRecord, RusqliteError> {
+    let mut conn = db_pool.get().expect("Failed to get DB connection");
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    let mut throws = get_match_throws_on(&tx, round_match.id)?;
+    let score = series_score(hand_set, round_match, &throws, false);
+    if score.decided(best_of) {
+        return Ok(ThrowRecord::Decided);
+    }
+
+    let throw_number = score.completed + 1;
+    if throws.iter().any(|t| t.throw_number == throw_number && t.player_id == player_id) {
+        return Ok(ThrowRecord::AlreadyThrown);
+    }
+
+    tx.execute(
+        "INSERT INTO match_throws (match_id, throw_number, player_id, player_hand, timestamp) VALUES (?1, ?2, ?3, ?4, CURRENT_TIMESTAMP)",
+        params![round_match.id, throw_number, player_id, hand],
+    )?;
+    throws.push(MatchThrow { throw_number, player_id, player_hand: hand.to_string() });
+
+    let score = series_score(hand_set, round_match, &throws, false);
+    if score.decided(best_of) {
+        let winner = if score.wins_one > score.wins_two { Some(round_match.player_one) } else { round_match.player_two };
+        tx.execute(
+            "UPDATE round_matches SET winner = ?2 WHERE id = ?1",
+            params![round_match.id, winner],
+        )?;
+    }
+
+    tx.commit()?;
+    Ok(ThrowRecord::Recorded(throws))
+}
+
+async fn insert_round_match(db_pool: &DbPool, round_id: i32, player_one: i64, player_two: Option<i64>) -> Result<(), RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    conn.execute(
+        "INSERT INTO round_matches (round_id, player_one, player_two) VALUES (?1, ?2, ?3)",
+        params![round_id, player_one, player_two],
+    )?;
+    Ok(())
+}
+
+// Give a player their row in RoundDetailsTable, unless they have one already
+async fn insert_round_details(db_pool: &DbPool, round_id: i32, player_id: i64, player_hand: &str) -> Result<(), RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    conn.execute(
+        "INSERT INTO RoundDetailsTable (round_id, player_id, player_hand, timestamp)
+         SELECT ?1, ?2, ?3, CURRENT_TIMESTAMP
+         WHERE NOT EXISTS (SELECT 1 FROM RoundDetailsTable WHERE round_id = ?1 AND player_id = ?2)",
+        params![round_id, player_id, player_hand],
+    )?;
+    Ok(())
+}
+
+pub async fn get_player_match(db_pool: &DbPool, round_id: i32, player_id: i64) -> Result<Option<RoundMatch>, RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    conn.query_row(
+        "SELECT id, player_one, player_two, winner FROM round_matches WHERE round_id = ?1 AND (player_one = ?2 OR player_two = ?2)",
+        params![round_id, player_id],
+        |row| Ok(RoundMatch { id: row.get(0)?, player_one: row.get(1)?, player_two: row.get(2)?, winner: row.get(3)? }),
+    ).optional()
+}
+
+pub async fn get_round_matches(db_pool: &DbPool, round_id: i32) -> Result<Vec<RoundMatch>, RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    let mut stmt = conn.prepare("SELECT id, player_one, player_two, winner FROM round_matches WHERE round_id = ?1 ORDER BY id")?;
+    let matches = stmt.query_map(params![round_id], |row| {
+        Ok(RoundMatch { id: row.get(0)?, player_one: row.get(1)?, player_two: row.get(2)?, winner: row.get(3)? })
+    })?
+    .collect::<Result<Vec<RoundMatch>, RusqliteError>>()?;
+
+    Ok(matches)
+}
+
+pub async fn get_match_throws(db_pool: &DbPool, match_id: i64) -> Result<Vec<MatchThrow>, RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    get_match_throws_on(&conn, match_id)
+}
+
+fn get_match_throws_on(conn: &Connection, match_id: i64) -> Result<Vec<MatchThrow>, RusqliteError> {
+    let mut stmt = conn.prepare("SELECT throw_number, player_id, player_hand FROM match_throws WHERE match_id = ?1 ORDER BY throw_number, id")?;
+    let throws = stmt.query_map(params![match_id], |row| {
+        Ok(MatchThrow { throw_number: row.get(0)?, player_id: row.get(1)?, player_hand: row.get(2)? })
+    })?
+    .collect::<Result<Vec<MatchThrow>, RusqliteError>>()?;
+
+    Ok(throws)
+}
@@ -0,0 +1,389 @@
+/// config.rs
+
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::sync::OnceLock;
+use crate::scoring::ScoringRules;
+use crate::ranking::TieBreaker;
+
+// Where the configuration file is read from, unless BOT_CONFIG says otherwise
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+static CONFIG: OnceLock<BotConfig> = OnceLock::new();
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BotConfig {
+    pub database: DatabaseConfig,
+    pub admins: AdminConfig,
+    pub scoring: ScoringConfig,
+    pub channels: ChannelConfig,
+    pub reminders: ReminderConfig,
+    pub commit_reveal: CommitRevealConfig,
+    pub ranking: RankingConfig,
+    pub rating: RatingConfig,
+    pub access: AccessConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub path: String,
+    pub pool_size: u32,
+}
+
+// Empty by default: a username built into the binary would go to whoever claims that handle first
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    // Telegram user ids or usernames of the owners, who may do everything. They get the owner role at
+    // startup. A username is tied to its user id the first time that owner messages the bot.
+    pub bootstrap: Vec<String>,
+    // Telegram user ids or usernames with the dev role, allowed to use the dev commands. Like the owners,
+    // a username is tied to its user id the first time that dev messages the bot and only the id counts after.
+    pub devs: Vec<String>,
+}
+
+// The scoring rules new seasons start with, see scoring.rs
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScoringConfig {
+    pub win: i32,
+    pub draw: i32,
+    pub loss: i32,
+    // Points for winning because the opponent didn't play
+    pub forfeit: i32,
+    // Points for sitting a round out when the number of players is odd
+    pub bye: i32,
+    pub no_play_penalty: i32,
+    pub streak_length: i32,
+    pub streak_bonus: i32,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChannelConfig {
+    // Used to fill channel_settings when nothing has been set with /setbroadcastchannel or /setgroupchannel
+    pub broadcast_channel_id: Option<String>,
+    pub group_channel_id: Option<String>,
+    // A private channel that gets a copy of every audit log entry. Off when not set.
+    pub admin_channel_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReminderConfig {
+    // Seconds before a round deadline at which players who haven't played get a DM
+    pub offsets_secs: Vec<i64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CommitRevealConfig {
+    // How long players get to reveal their hand once a commit–reveal round closes
+    pub reveal_window_secs: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RankingConfig {
+    // Applied in order to players with the same score, see ranking.rs
+    pub tie_breakers: Vec<TieBreaker>,
+}
+
+// ELO ratings across seasons, see rating.rs
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RatingConfig {
+    // Where players without rated matches start
+    pub initial: f64,
+    // The most a rating can move in one match
+    pub k_factor: f64,
+}
+
+// What users hear back when they run a command their role doesn't allow, see access.rs
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AccessConfig {
+    // Tell users that they may not run a command, instead of ignoring them
+    pub reply_denied: bool,
+    // Tell users in a private chat when their message isn't a command the bot knows
+    pub reply_unknown: bool,
+    // At most one of those replies per user in this many seconds, and at most one audit log entry
+    // for their refused commands, which counts the ones left out
+    pub reply_interval_secs: i64,
+    // Alert the admins once a user was refused this many commands within alert_window_secs, 0 is off
+    pub alert_after: i64,
+    pub alert_window_secs: i64,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            path: "my_database.db".to_string(),
+            pool_size: 10,
+        }
+    }
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        ScoringConfig { win: 2, draw: 1, loss: 0, forfeit: 2, bye: 2, no_play_penalty: 0, streak_length: 3, streak_bonus: 0 }
+    }
+}
+
+impl Default for ReminderConfig {
+    fn default() -> Self {
+        ReminderConfig { offsets_secs: vec![10 * 60, 60] }
+    }
+}
+
+impl Default for CommitRevealConfig {
+    fn default() -> Self {
+        CommitRevealConfig { reveal_window_secs: 5 * 60 }
+    }
+}
+
+impl Default for RatingConfig {
+    fn default() -> Self {
+        RatingConfig { initial: 1500.0, k_factor: 32.0 }
+    }
+}
+
+impl Default for AccessConfig {
+    fn default() -> Self {
+        AccessConfig { reply_denied: true, reply_unknown: true, reply_interval_secs: 60, alert_after: 5, alert_window_secs: 60 * 60 }
+    }
+}
+
+impl Default for RankingConfig {
+    fn default() -> Self {
+        RankingConfig { tie_breakers: TieBreaker::ALL.to_vec() }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read { path: String, source: std::io::Error },
+    Parse { path: String, source: toml::de::Error },
+    InvalidEnv { name: &'static str, value: String },
+    Invalid(String),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Read { path, source } => write!(f, "could not read config file '{}': {}", path, source),
+            ConfigError::Parse { path, source } => write!(f, "could not parse config file '{}': {}", path, source),
+            ConfigError::InvalidEnv { name, value } => write!(f, "environment variable {} has an invalid value '{}'", name, value),
+            ConfigError::Invalid(reason) => write!(f, "invalid configuration: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// Load the configuration file (if there is one), apply environment overrides and validate.
+// Call dotenv before this so that .env values count as environment overrides.
+pub fn load() -> Result<BotConfig, ConfigError> {
+    let path = env::var("BOT_CONFIG").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string());
+
+    let mut config = match fs::read_to_string(&path) {
+        Ok(contents) => toml::from_str(&contents).map_err(|source| ConfigError::Parse { path: path.clone(), source })?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && env::var("BOT_CONFIG").is_err() => {
+            log::info!("No {} found, using the default configuration.", path);
+            BotConfig::default()
+        }
+        Err(source) => return Err(ConfigError::Read { path, source }),
+    };
+
+    config.apply_env_overrides()?;
+    config.validate()?;
+    Ok(config)
+}
+
+// Make the configuration available to the rest of the bot. Only the first call has an effect.
+pub fn set(config: BotConfig) {
+    let _ = CONFIG.set(config);
+}
+
+// The active configuration. Falls back to the defaults if `set` was never called.
+pub fn get() -> &'static BotConfig {
+    CONFIG.get_or_init(BotConfig::default)
+}
+
+impl BotConfig {
+    fn apply_env_overrides(&mut self) -> Result<(), ConfigError> {
+        if let Some(path) = env_string("BOT_DB_PATH") {
+            self.database.path = path;
+        }
+        if let Some(pool_size) = env_parse("BOT_DB_POOL_SIZE")? {
+            self.database.pool_size = pool_size;
+        }
+        if let Some(admins) = env_string("BOT_ADMINS") {
+            self.admins.bootstrap = split_list(&admins);
+        }
+        if let Some(devs) = env_string("BOT_DEVS") {
+            self.admins.devs = split_list(&devs);
+        }
+        if let Some(win) = env_parse("BOT_SCORE_WIN")? {
+            self.scoring.win = win;
+        }
+        if let Some(draw) = env_parse("BOT_SCORE_DRAW")? {
+            self.scoring.draw = draw;
+        }
+        if let Some(loss) = env_parse("BOT_SCORE_LOSS")? {
+            self.scoring.loss = loss;
+        }
+        if let Some(forfeit) = env_parse("BOT_SCORE_FORFEIT")? {
+            self.scoring.forfeit = forfeit;
+        }
+        if let Some(bye) = env_parse("BOT_SCORE_BYE")? {
+            self.scoring.bye = bye;
+        }
+        if let Some(no_play_penalty) = env_parse("BOT_SCORE_NO_PLAY_PENALTY")? {
+            self.scoring.no_play_penalty = no_play_penalty;
+        }
+        if let Some(streak_length) = env_parse("BOT_SCORE_STREAK_LENGTH")? {
+            self.scoring.streak_length = streak_length;
+        }
+        if let Some(streak_bonus) = env_parse("BOT_SCORE_STREAK_BONUS")? {
+            self.scoring.streak_bonus = streak_bonus;
+        }
+        if let Some(channel_id) = env_string("BOT_BROADCAST_CHANNEL_ID") {
+            self.channels.broadcast_channel_id = Some(channel_id);
+        }
+        if let Some(channel_id) = env_string("BOT_GROUP_CHANNEL_ID") {
+            self.channels.group_channel_id = Some(channel_id);
+        }
+        if let Some(channel_id) = env_string("BOT_ADMIN_CHANNEL_ID") {
+            self.channels.admin_channel_id = Some(channel_id);
+        }
+        if let Some(offsets) = env_string("BOT_REMINDER_OFFSETS") {
+            self.reminders.offsets_secs = split_list(&offsets)
+                .into_iter()
+                .map(|offset| offset.parse().map_err(|_| ConfigError::InvalidEnv { name: "BOT_REMINDER_OFFSETS", value: offsets.clone() }))
+                .collect::<Result<Vec<i64>, ConfigError>>()?;
+        }
+        if let Some(reveal_window_secs) = env_parse("BOT_REVEAL_WINDOW_SECS")? {
+            self.commit_reveal.reveal_window_secs = reveal_window_secs;
+        }
+        if let Some(initial) = env_parse("BOT_RATING_INITIAL")? {
+            self.rating.initial = initial;
+        }
+        if let Some(k_factor) = env_parse("BOT_RATING_K_FACTOR")? {
+            self.rating.k_factor = k_factor;
+        }
+        if let Some(reply_denied) = env_parse("BOT_REPLY_DENIED")? {
+            self.access.reply_denied = reply_denied;
+        }
+        if let Some(reply_unknown) = env_parse("BOT_REPLY_UNKNOWN")? {
+            self.access.reply_unknown = reply_unknown;
+        }
+        if let Some(reply_interval_secs) = env_parse("BOT_REPLY_INTERVAL_SECS")? {
+            self.access.reply_interval_secs = reply_interval_secs;
+        }
+        if let Some(alert_after) = env_parse("BOT_ALERT_AFTER")? {
+            self.access.alert_after = alert_after;
+        }
+        if let Some(alert_window_secs) = env_parse("BOT_ALERT_WINDOW_SECS")? {
+            self.access.alert_window_secs = alert_window_secs;
+        }
+        if let Some(tie_breakers) = env_string("BOT_TIE_BREAKERS") {
+            self.ranking.tie_breakers = split_list(&tie_breakers)
+                .into_iter()
+                .map(|tie_breaker| tie_breaker.parse().map_err(|_| ConfigError::InvalidEnv { name: "BOT_TIE_BREAKERS", value: tie_breakers.clone() }))
+                .collect::<Result<Vec<TieBreaker>, ConfigError>>()?;
+        }
+        Ok(())
+    }
+
+    fn validate(&mut self) -> Result<(), ConfigError> {
+        if self.database.path.trim().is_empty() {
+            return Err(ConfigError::Invalid("database.path must not be empty".to_string()));
+        }
+        if self.database.pool_size == 0 {
+            return Err(ConfigError::Invalid("database.pool_size must be at least 1".to_string()));
+        }
+
+        // Accept "@name" as well as "name"
+        for list in [&mut self.admins.bootstrap, &mut self.admins.devs] {
+            for username in list.iter_mut() {
+                *username = username.trim().trim_start_matches('@').to_string();
+                if username.is_empty() || username.contains(char::is_whitespace) {
+                    return Err(ConfigError::Invalid(format!("'{}' is not a valid Telegram username", username)));
+                }
+            }
+        }
+        if self.admins.bootstrap.is_empty() {
+            return Err(ConfigError::Invalid("admins.bootstrap (or BOT_ADMINS) needs at least one owner's user id or username, otherwise nobody can run the bot".to_string()));
+        }
+
+        ScoringRules::from(&self.scoring)
+            .validate()
+            .map_err(|reason| ConfigError::Invalid(format!("scoring: {}", reason)))?;
+
+        for (name, channel_id) in [
+            ("channels.broadcast_channel_id", &self.channels.broadcast_channel_id),
+            ("channels.group_channel_id", &self.channels.group_channel_id),
+            ("channels.admin_channel_id", &self.channels.admin_channel_id),
+        ] {
+            if let Some(channel_id) = channel_id {
+                if channel_id.is_empty() || !channel_id.chars().all(char::is_numeric) {
+                    return Err(ConfigError::Invalid(format!("{} must be a numeric channel id, got '{}'", name, channel_id)));
+                }
+            }
+        }
+
+        if self.reminders.offsets_secs.iter().any(|offset| *offset <= 0) {
+            return Err(ConfigError::Invalid("reminders.offsets_secs must all be positive".to_string()));
+        }
+        self.reminders.offsets_secs.sort_unstable();
+        self.reminders.offsets_secs.dedup();
+
+        if self.commit_reveal.reveal_window_secs <= 0 {
+            return Err(ConfigError::Invalid("commit_reveal.reveal_window_secs must be positive".to_string()));
+        }
+
+        if !self.rating.initial.is_finite() || self.rating.initial <= 0.0 {
+            return Err(ConfigError::Invalid("rating.initial must be a positive number".to_string()));
+        }
+        if !self.rating.k_factor.is_finite() || self.rating.k_factor <= 0.0 {
+            return Err(ConfigError::Invalid("rating.k_factor must be a positive number".to_string()));
+        }
+
+        if self.access.reply_interval_secs < 0 {
+            return Err(ConfigError::Invalid("access.reply_interval_secs must not be negative".to_string()));
+        }
+        if self.access.alert_after < 0 {
+            return Err(ConfigError::Invalid("access.alert_after must not be negative, 0 turns the alerts off".to_string()));
+        }
+        if self.access.alert_window_secs <= 0 {
+            return Err(ConfigError::Invalid("access.alert_window_secs must be positive".to_string()));
+        }
+
+        for (index, tie_breaker) in self.ranking.tie_breakers.iter().enumerate() {
+            if self.ranking.tie_breakers[..index].contains(tie_breaker) {
+                return Err(ConfigError::Invalid(format!("ranking.tie_breakers lists {} twice", tie_breaker)));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn env_string(name: &'static str) -> Option<String> {
+    env::var(name).ok().map(|value| value.trim().to_string()).filter(|value| !value.is_empty())
+}
+
+fn env_parse<T: std::str::FromStr>(name: &'static str) -> Result<Option<T>, ConfigError> {
+    match env_string(name) {
+        Some(value) => value.parse().map(Some).map_err(|_| ConfigError::InvalidEnv { name, value }),
+        None => Ok(None),
+    }
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value.split(',').map(|item| item.trim().to_string()).filter(|item| !item.is_empty()).collect()
+}
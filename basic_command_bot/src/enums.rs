@@ -1,6 +1,7 @@
 // enum.rs
 
 use teloxide::utils::command::BotCommands;
+use crate::admin::Role;
 
 #[derive(BotCommands, Clone)]
 #[command(rename_rule = "lowercase", description = "These commands are supported:")]
@@ -18,12 +19,36 @@ pub enum Command {
 
         ")]
     Status,
+    #[command(description = "Pick your hand for the current round. ")]
+    Play,
+    #[command(description = "Show the hand you have locked in for this round. ")]
+    MyHand,
     #[command(description = "Play the rock hand. ")]
     PlayRock,
     #[command(description = "Play the paper hand. ")]
     PlayPaper,
     #[command(description = "Play the scissors hand. ")]
     PlayScissors,
+    #[command(description = "Commit–reveal seasons: commit to a hand with sha256(<hand>:<nonce>). ")]
+    Commit(String),
+    #[command(description = "Commit–reveal seasons: reveal your hand after the round closes: /reveal <hand> <nonce> ")]
+    Reveal(String),
+    #[command(description = "Show the bracket of a knockout season. ")]
+    Bracket,
+    #[command(description = "Turn round reminders on or off: /reminders on|off ")]
+    Reminders(String),
+    #[command(description = "Your round-by-round results: /history [season name or id] ")]
+    History(String),
+    #[command(description = "Lifetime statistics of you or another player: /profile [@username] ")]
+    Profile(String),
+    #[command(description = "List all seasons with their dates and winners. ")]
+    Seasons,
+    #[command(description = "Standings of any season: /leaderboard [season name or id] ")]
+    Leaderboard(String),
+    #[command(description = "Your skill rating across seasons, or another player's: /rating [@username] ")]
+    Rating(String),
+    #[command(description = "The rating ladder of all players. ")]
+    Ratings,
     //
     //DevCommands
     //
@@ -48,6 +73,12 @@ pub enum Command {
     #[command(description = "off")]
     ListAdmins,
     #[command(description = "off")]
+    Grant(String),
+    #[command(description = "off")]
+    Revoke(String),
+    #[command(description = "off")]
+    AuditLog(String),
+    #[command(description = "off")]
     StartNewSeason(String),
     #[command(description = "off")]
     StopNewSeason,
@@ -66,6 +97,22 @@ pub enum Command {
     #[command(description = "off")]
     StopRound,    
     #[command(description = "off")]
+    AllowHandChange(String),
+    #[command(description = "off")]
+    CommitReveal(String),
+    #[command(description = "off")]
+    SetScoring(String),
+    #[command(description = "off")]
+    SetHands(String),
+    #[command(description = "off")]
+    ScheduleRounds(String),
+    #[command(description = "off")]
+    PauseSchedule,
+    #[command(description = "off")]
+    ResumeSchedule,
+    #[command(description = "off")]
+    CancelSchedule,
+    #[command(description = "off")]
     ApprovePlayer(String),
     #[command(description = "off")]
     RefusePlayer(String),
@@ -97,6 +144,97 @@ pub enum Command {
     ResetPlayerTable,
 }
 
+impl Command {
+    // The least a user needs to be to run the command, enforced by the filter in main.rs `schema()`.
+    // Users without it are handled by access::denied_command.
+    pub fn min_role(&self) -> Role {
+        match self {
+            Command::Help
+            | Command::Signup
+            | Command::Version
+            | Command::Status
+            | Command::Play
+            | Command::MyHand
+            | Command::PlayRock
+            | Command::PlayPaper
+            | Command::PlayScissors
+            | Command::Commit(_)
+            | Command::Reveal(_)
+            | Command::Bracket
+            | Command::Reminders(_)
+            | Command::History(_)
+            | Command::Profile(_)
+            | Command::Seasons
+            | Command::Leaderboard(_)
+            | Command::Rating(_)
+            | Command::Ratings
+            | Command::ViewLeaderboard
+            | Command::ReadChangelog => Role::Player,
+
+            Command::Username
+            | Command::UsernameAndAge
+            | Command::Writesql(_)
+            | Command::Readsql
+            | Command::ResetCandidateTable
+            | Command::ResetPlayerTable => Role::Dev,
+
+            Command::ListAdmins
+            | Command::CurrentSeasonStatus
+            | Command::ApprovePlayer(_)
+            | Command::RefusePlayer(_)
+            | Command::ViewSignupList
+            | Command::ViewApprovedList
+            | Command::ViewRefusedList => Role::Moderator,
+
+            Command::AddAdmin(_)
+            | Command::RemoveAdmin(_)
+            | Command::Grant(_)
+            | Command::Revoke(_)
+            | Command::AuditLog(_)
+            | Command::StartNewSeason(_)
+            | Command::StopNewSeason
+            | Command::StartSignupPhase
+            | Command::StopSignupPhase
+            | Command::StartGamingPhase
+            | Command::StopGamingPhase
+            | Command::StartRound
+            | Command::StopRound
+            | Command::AllowHandChange(_)
+            | Command::CommitReveal(_)
+            | Command::SetScoring(_)
+            | Command::SetHands(_)
+            | Command::ScheduleRounds(_)
+            | Command::PauseSchedule
+            | Command::ResumeSchedule
+            | Command::CancelSchedule
+            | Command::SetBroadcastChannel(_)
+            | Command::SetGroupChannel(_)
+            | Command::MsgBroadcastChannel(_)
+            | Command::MsgGroup(_)
+            | Command::GetGroupBroadcastId
+            | Command::ResetGroupBroadcast => Role::Admin,
+        }
+    }
+
+    // Everything beyond the player commands goes into the audit log, except reading the log itself
+    pub fn is_audited(&self) -> bool {
+        self.min_role() != Role::Player && !matches!(self, Command::AuditLog(_))
+    }
+
+    // The user an administrative command acts on, for the audit log
+    pub fn target(&self) -> Option<&str> {
+        match self {
+            Command::AddAdmin(args)
+            | Command::RemoveAdmin(args)
+            | Command::Grant(args)
+            | Command::Revoke(args)
+            | Command::ApprovePlayer(args)
+            | Command::RefusePlayer(args) => args.split_whitespace().next(),
+            _ => None,
+        }
+    }
+}
+
 #[derive(BotCommands, Clone)]
 #[command(rename_rule = "lowercase", description = "These 🌟 Admin 🌟 commands are supported:")]
 pub enum AdminCommand {
@@ -104,10 +242,16 @@ pub enum AdminCommand {
     AddAdmin(String),
     #[command(description = "remove a user from the admin list. ")]
     RemoveAdmin(String),
-    #[command(description = "list admin users.  
+    #[command(description = "list admin users and everyone else with a role. ")]
+    ListAdmins,
+    #[command(description = "give a user a role, everywhere or in one season: <@username or user id> <admin|moderator|dev|owner> [season name or id] ")]
+    Grant,
+    #[command(description = "take a granted role away: <@username or user id> <role> [season name or id] ")]
+    Revoke,
+    #[command(description = "the latest administrative actions, optionally only those mentioning a text: [count] [text]  
 
         ")]
-    ListAdmins,
+    AuditLog,
     #[command(description = "Start a new season for the rock-paper-scissors game with a given name and max number of players.  ")]
     StartNewSeason(String),
     #[command(description = "Stop the current season of the rock-paper-scissors game. ")]
@@ -130,10 +274,26 @@ pub enum AdminCommand {
     #[command(description = "The core of the game, start a round.")]
     StartRound,
 
-    #[command(description = "Stop a game round. 
+    #[command(description = "Stop a game round. ")]
+    StopRound,    
+    #[command(description = "Let players change their hand until the round closes: on|off ")]
+    AllowHandChange,
+    #[command(description = "Hide hands until the round closes, players commit to a hash and reveal later: on|off ")]
+    CommitReveal,
+    #[command(description = "Show or change the scoring rules of the season, e.g. win=3 no_play_penalty=1 streak_bonus=1 ")]
+    SetScoring,
+    #[command(description = "Show or change the hands of the season: rps, rpsls or a custom set, e.g. fire:🔥 plant:🌱 water:💧 ")]
+    SetHands,
+    #[command(description = "Schedule rounds automatically: <count> <duration> <gap>, e.g. 5 10m 2m. ")]
+    ScheduleRounds,
+    #[command(description = "Pause the round schedule. ")]
+    PauseSchedule,
+    #[command(description = "Resume the round schedule. ")]
+    ResumeSchedule,
+    #[command(description = "Cancel the remaining scheduled rounds. 
 
         ")]
-    StopRound,    
+    CancelSchedule,
     #[command(description = "View the list of players who signed up. ")]
     ViewSignupList,
     #[command(description = "View the list of approved players. ")]
@@ -181,3 +341,149 @@ pub enum DevCommand {
     ResetPlayerTable,
 }
 
+
+// Lifecycle of a season. The string form is what gets stored in `seasons.status`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeasonPhase {
+    Initial,
+    StartSignup,
+    StoppedSignup,
+    StartGaming,
+    RoundOngoing,
+    // Commit–reveal seasons only: the round is closed and players reveal their hands
+    RoundRevealing,
+    StoppedGaming,
+    Closed,
+}
+
+impl SeasonPhase {
+    pub const ALL: [SeasonPhase; 8] = [
+        SeasonPhase::Initial,
+        SeasonPhase::StartSignup,
+        SeasonPhase::StoppedSignup,
+        SeasonPhase::StartGaming,
+        SeasonPhase::RoundOngoing,
+        SeasonPhase::RoundRevealing,
+        SeasonPhase::StoppedGaming,
+        SeasonPhase::Closed,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SeasonPhase::Initial => "initial",
+            SeasonPhase::StartSignup => "start_signup",
+            SeasonPhase::StoppedSignup => "stopped_signup",
+            SeasonPhase::StartGaming => "start_gaming",
+            SeasonPhase::RoundOngoing => "round_ongoing",
+            SeasonPhase::RoundRevealing => "round_revealing",
+            SeasonPhase::StoppedGaming => "stopped_gaming",
+            SeasonPhase::Closed => "closed",
+        }
+    }
+
+    // The transition table. Anything not listed here is illegal.
+    pub fn can_transition_to(&self, to: SeasonPhase) -> bool {
+        use SeasonPhase::*;
+        match (self, to) {
+            (Closed, _) => false,
+            (_, Closed) => true,
+            (Initial, StartSignup) => true,
+            (StartSignup, StoppedSignup) => true,
+            (StoppedSignup, StartSignup) => true,
+            (StoppedSignup, StartGaming) => true,
+            (StartGaming, RoundOngoing) => true,
+            (StartGaming, StoppedGaming) => true,
+            (RoundOngoing, StartGaming) => true,
+            (RoundOngoing, RoundRevealing) => true,
+            (RoundRevealing, StartGaming) => true,
+            (StoppedGaming, StartGaming) => true,
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for SeasonPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for SeasonPhase {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SeasonPhase::ALL
+            .iter()
+            .find(|phase| phase.as_str() == s)
+            .copied()
+            .ok_or_else(|| format!("Unknown season status '{}'", s))
+    }
+}
+
+impl rusqlite::types::ToSql for SeasonPhase {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl rusqlite::types::FromSql for SeasonPhase {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: String| rusqlite::types::FromSqlError::Other(e.into()))
+    }
+}
+
+// How a season is played. The string form is what gets stored in `seasons.format`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeasonFormat {
+    // Everyone plays every round and collects points
+    League,
+    // Single elimination bracket, losers are out
+    Knockout,
+}
+
+impl SeasonFormat {
+    pub const ALL: [SeasonFormat; 2] = [SeasonFormat::League, SeasonFormat::Knockout];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SeasonFormat::League => "league",
+            SeasonFormat::Knockout => "knockout",
+        }
+    }
+}
+
+impl std::fmt::Display for SeasonFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for SeasonFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SeasonFormat::ALL
+            .iter()
+            .find(|format| format.as_str() == s)
+            .copied()
+            .ok_or_else(|| format!("Unknown format '{}', use league or knockout", s))
+    }
+}
+
+impl rusqlite::types::ToSql for SeasonFormat {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl rusqlite::types::FromSql for SeasonFormat {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: String| rusqlite::types::FromSqlError::Other(e.into()))
+    }
+}
@@ -0,0 +1,269 @@
+/// hands.rs
+
+// One hand of a set, and the hands it beats
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hand {
+    pub name: String,
+    pub emoji: String,
+    pub beats: Vec<String>,
+}
+
+// The hands a season plays with. Built in are rock-paper-scissors and rock-paper-scissors-lizard-Spock,
+// admins can also define their own set. The spec form is what gets stored in `seasons.hand_set`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandSet {
+    pub name: String,
+    pub hands: Vec<Hand>,
+}
+
+// Keeps the hand picker and the callback data small
+const MAX_HANDS: usize = 9;
+const MAX_HAND_NAME_LEN: usize = 20;
+
+impl Default for HandSet {
+    fn default() -> Self {
+        HandSet::rps()
+    }
+}
+
+impl HandSet {
+    pub const USAGE: &'static str = "rps | rpsls | an odd number of name:emoji hands in cyclic order, each beating the half that follows it, e.g. fire:🔥 plant:🌱 water:💧 | the same with explicit wins, e.g. fire:🔥>plant plant:🌱>water water:💧>fire";
+
+    pub fn rps() -> HandSet {
+        HandSet::built_in("rps", &[
+            ("rock", "🪨", &["scissors"]),
+            ("paper", "📜", &["rock"]),
+            ("scissors", "✂️", &["paper"]),
+        ])
+    }
+
+    pub fn rpsls() -> HandSet {
+        HandSet::built_in("rpsls", &[
+            ("rock", "🪨", &["scissors", "lizard"]),
+            ("paper", "📜", &["rock", "spock"]),
+            ("scissors", "✂️", &["paper", "lizard"]),
+            ("lizard", "🦎", &["spock", "paper"]),
+            ("spock", "🖖", &["scissors", "rock"]),
+        ])
+    }
+
+    fn built_in(name: &str, hands: &[(&str, &str, &[&str])]) -> HandSet {
+        HandSet {
+            name: name.to_string(),
+            hands: hands
+                .iter()
+                .map(|(hand, emoji, beats)| Hand {
+                    name: hand.to_string(),
+                    emoji: emoji.to_string(),
+                    beats: beats.iter().map(|beaten| beaten.to_string()).collect(),
+                })
+                .collect(),
+        }
+    }
+
+    // Read a set from "rps", "rpsls" or a custom definition. A custom set either lists its hands in
+    // cyclic order, where each hand beats the half of the others that follows it, or says for every
+    // hand which hands it beats.
+    pub fn parse(spec: &str) -> Result<HandSet, String> {
+        match spec.trim().to_lowercase().as_str() {
+            "rps" => return Ok(HandSet::rps()),
+            "rpsls" => return Ok(HandSet::rpsls()),
+            _ => {}
+        }
+
+        let tokens: Vec<&str> = spec.split_whitespace().collect();
+        let explicit = tokens.iter().any(|token| token.contains('>'));
+        let mut hands = Vec::new();
+        for token in &tokens {
+            let (hand, beats) = match token.split_once('>') {
+                Some((hand, beats)) => (hand, beats.split(',').filter(|beaten| !beaten.is_empty()).map(|beaten| beaten.to_lowercase()).collect()),
+                None if explicit => return Err(format!("'{}' doesn't say which hands it beats", token)),
+                None => (*token, Vec::new()),
+            };
+            let (name, emoji) = hand
+                .split_once(':')
+                .ok_or_else(|| format!("Hands look like name:emoji, got '{}'", hand))?;
+            hands.push(Hand { name: name.to_lowercase(), emoji: emoji.to_string(), beats });
+        }
+
+        if !explicit {
+            let count = hands.len();
+            let names: Vec<String> = hands.iter().map(|hand| hand.name.clone()).collect();
+            for (index, hand) in hands.iter_mut().enumerate() {
+                hand.beats = (1..=count / 2).map(|offset| names[(index + offset) % count].clone()).collect();
+            }
+        }
+
+        let hand_set = HandSet { name: "custom".to_string(), hands };
+        hand_set.validate()?;
+        Ok(hand_set)
+    }
+
+    // Every pair of different hands needs exactly one winner, and every hand wins as often as it loses
+    pub fn validate(&self) -> Result<(), String> {
+        let count = self.hands.len();
+        if !(3..=MAX_HANDS).contains(&count) || count.is_multiple_of(2) {
+            return Err(format!("A hand set has an odd number of hands between 3 and {}, got {}", MAX_HANDS, count));
+        }
+
+        for (index, hand) in self.hands.iter().enumerate() {
+            if hand.name.is_empty() || hand.name.len() > MAX_HAND_NAME_LEN || !hand.name.chars().all(|c| c.is_ascii_alphanumeric()) {
+                return Err(format!("'{}' is not a valid hand name, use up to {} letters and digits", hand.name, MAX_HAND_NAME_LEN));
+            }
+            if hand.emoji.is_empty() {
+                return Err(format!("{} needs an emoji", hand.name));
+            }
+            if self.hands[..index].iter().any(|other| other.name == hand.name) {
+                return Err(format!("{} is in the set twice", hand.name));
+            }
+            if let Some(beaten) = hand.beats.iter().find(|beaten| !self.contains(beaten) || *beaten == &hand.name) {
+                return Err(format!("{} can't beat '{}'", hand.name, beaten));
+            }
+            if hand.beats.len() != count / 2 {
+                return Err(format!("{} beats {} hands, every hand has to beat exactly {}", hand.name, hand.beats.len(), count / 2));
+            }
+        }
+
+        for (index, hand) in self.hands.iter().enumerate() {
+            for other in &self.hands[index + 1..] {
+                if self.beats(&hand.name, &other.name) == self.beats(&other.name, &hand.name) {
+                    return Err(format!("{} and {} need exactly one winner", hand.name, other.name));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // The stored form. Custom sets are stored with their wins spelled out.
+    pub fn spec(&self) -> String {
+        if self.name != "custom" {
+            return self.name.clone();
+        }
+        self.hands
+            .iter()
+            .map(|hand| format!("{}:{}>{}", hand.name, hand.emoji, hand.beats.join(",")))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    pub fn contains(&self, hand: &str) -> bool {
+        self.hands.iter().any(|h| h.name == hand)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.hands.iter().map(|hand| hand.name.as_str()).collect()
+    }
+
+    pub fn emoji(&self, hand: &str) -> &str {
+        match hand {
+            "" => "🚫",
+            _ => self.hands.iter().find(|h| h.name == hand).map(|h| h.emoji.as_str()).unwrap_or("❓"),
+        }
+    }
+
+    // "rock 🪨", for messages
+    pub fn label(&self, hand: &str) -> String {
+        format!("{} {}", hand, self.emoji(hand))
+    }
+
+    pub fn beats(&self, hand: &str, other: &str) -> bool {
+        self.hands.iter().any(|h| h.name == hand && h.beats.iter().any(|beaten| beaten == other))
+    }
+
+    // The result of one throw, seen from the player: "won", "lost" or "draw"
+    pub fn result(&self, player_hand: &str, opponent_hand: &str) -> &'static str {
+        match (player_hand, opponent_hand) {
+            // Both players have empty hands. Missing or mismatched reveals are empty hands too.
+            ("", "") => "draw",
+            // Player wins when opponent has an empty hand and player doesn't
+            (_, "") => "won",
+            // Player loses when their hand is empty and opponent's isn't
+            ("", _) => "lost",
+            _ if self.beats(player_hand, opponent_hand) => "won",
+            _ if self.beats(opponent_hand, player_hand) => "lost",
+            // Draw when both hands are the same
+            _ => "draw",
+        }
+    }
+}
+
+impl std::fmt::Display for HandSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hands = self.hands.iter().map(|hand| self.label(&hand.name)).collect::<Vec<String>>().join(", ");
+        write!(f, "{} ({})", self.name, hands)
+    }
+}
+
+impl rusqlite::types::ToSql for HandSet {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(self.spec().into())
+    }
+}
+
+impl rusqlite::types::FromSql for HandSet {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        HandSet::parse(value.as_str()?).map_err(|e| rusqlite::types::FromSqlError::Other(e.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_built_in_sets() {
+        assert_eq!(HandSet::parse("rps"), Ok(HandSet::rps()));
+        assert_eq!(HandSet::parse(" RPSLS "), Ok(HandSet::rpsls()));
+        assert!(HandSet::rps().validate().is_ok());
+        assert!(HandSet::rpsls().validate().is_ok());
+    }
+
+    #[test]
+    fn cyclic_set_beats_the_half_that_follows() {
+        let hand_set = HandSet::parse("fire:🔥 plant:🌱 water:💧").unwrap();
+        assert_eq!(hand_set.names(), vec!["fire", "plant", "water"]);
+        assert!(hand_set.beats("fire", "plant"));
+        assert!(hand_set.beats("plant", "water"));
+        assert!(hand_set.beats("water", "fire"));
+        assert_eq!(hand_set.result("water", "plant"), "lost");
+    }
+
+    #[test]
+    fn rejects_an_even_number_of_hands() {
+        assert!(HandSet::parse("a:1 b:2 c:3 d:4").is_err());
+        assert!(HandSet::parse("a:1>b b:2>c c:3>d d:4>a").is_err());
+        assert!(HandSet::parse("a:1").is_err());
+    }
+
+    #[test]
+    fn rejects_pairs_without_exactly_one_winner() {
+        // Every hand beats one other, but a and b beat each other and nothing decides b against c
+        let error = HandSet::parse("a:1>b b:2>a c:3>a").unwrap_err();
+        assert!(error.contains("exactly one winner"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_malformed_hands() {
+        assert!(HandSet::parse("fire:🔥>plant plant:🌱 water:💧>fire").is_err());
+        assert!(HandSet::parse("fire plant water").is_err());
+        assert!(HandSet::parse("fire:🔥 fire:🌱 water:💧").is_err());
+        assert!(HandSet::parse("fire:🔥>smoke plant:🌱>water water:💧>fire").is_err());
+    }
+
+    #[test]
+    fn spec_round_trips() {
+        assert_eq!(HandSet::rps().spec(), "rps");
+        let hand_set = HandSet::parse("fire:🔥 plant:🌱 water:💧").unwrap();
+        assert_eq!(hand_set.spec(), "fire:🔥>plant plant:🌱>water water:💧>fire");
+        assert_eq!(HandSet::parse(&hand_set.spec()), Ok(hand_set));
+    }
+
+    #[test]
+    fn empty_hands_lose() {
+        let hand_set = HandSet::rps();
+        assert_eq!(hand_set.result("rock", ""), "won");
+        assert_eq!(hand_set.result("", "rock"), "lost");
+        assert_eq!(hand_set.result("", ""), "draw");
+    }
+}
@@ -7,13 +7,25 @@ use teloxide::{
 
 //use teloxide::adaptors::throttle::Limits;
 
-//use dotenv::dotenv;
+use dotenv::dotenv;
 
 use chrono::{Local, DateTime};
 mod admin;
+use admin::is_authorized;
+mod audit;
+mod access;
+mod config;
 
 
 mod database;
+mod migrations;
+mod pairing;
+mod hands;
+mod scoring;
+mod ranking;
+mod rating;
+mod messages;
+mod scheduler;
 use database::{init_db_pool, DbPool};
 use std::sync::Arc;
 
@@ -30,6 +42,8 @@ use commands::basic_commands::{
     playrock_command,
     playpaper_command,
     playscissors_command,
+    play_command,
+    myhand_command,
 };
 
 use commands::admin_commands::{
@@ -45,6 +59,10 @@ use commands::admin_commands::{
     stopgamingphase_command,
     start_round_command,
     stop_round_command,
+    allow_hand_change_command,
+    commit_reveal_command,
+    set_scoring_command,
+    set_hands_command,
 };
 
 use commands::registration_commands::{
@@ -73,21 +91,90 @@ use commands::grp_broadcast_commands::{
     msg_group_command,
 };
 
+use commands::schedule_commands::{
+    schedule_rounds_command,
+    pause_schedule_command,
+    resume_schedule_command,
+    cancel_schedule_command,
+};
+
+use commands::commit_reveal_commands::{
+    commit_command,
+    reveal_command,
+};
+
+use commands::knockout_commands::{
+    bracket_command,
+};
+
+use commands::reminder_commands::{
+    reminders_command,
+};
+
+use commands::callback_commands::{
+    handle_callback_query,
+};
+
+use commands::history_commands::{
+    history_command,
+};
+
+use commands::profile_commands::{
+    profile_command,
+};
+
+use commands::leaderboard_commands::{
+    seasons_command,
+    leaderboard_command,
+};
+
+use commands::rating_commands::{
+    rating_command,
+    ratings_command,
+};
+
+use commands::role_commands::{
+    grant_command,
+    revoke_command,
+};
+
+use commands::audit_commands::{
+    audit_log_command,
+};
+
 use commands::changelogread::{
     send_changelog,
 };
 
 #[tokio::main]
 async fn main() {
+    dotenv().ok();
     pretty_env_logger::init();
     let now: DateTime<Local> = Local::now();
     log::info!("Starting command bot...");
     log::info!("Starting timestamp: {}...", now.format("%Y-%m-%d %H:%M:%S %:z"));
-    let db_pool = Arc::new(init_db_pool());     
+    let bot_config = match config::load() {
+        Ok(bot_config) => bot_config,
+        Err(e) => {
+            log::error!("Failed to load the configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+    config::set(bot_config);
+    let db_pool = match init_db_pool(config::get()) {
+        Ok(pool) => Arc::new(pool),
+        Err(e) => {
+            log::error!("Failed to initialize the database: {}", e);
+            std::process::exit(1);
+        }
+    };
     let bot = Bot::from_env()
         //.throttle(Limits::default())
         ;
 
+    // Starts and closes rounds on their deadlines
+    tokio::spawn(scheduler::run(bot.clone(), db_pool.clone()));
+
     Dispatcher::builder(bot, schema())
         .dependencies(dptree::deps![db_pool])
         .enable_ctrlc_handler()
@@ -100,6 +187,11 @@ fn schema() -> UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>>
     use dptree::case;
 
     let command_handler = teloxide::filter_command::<Command, _>()
+        // Every command needs the role it is annotated with in Command::min_role. Anyone else falls
+        // through to access::denied_command.
+        .filter(|msg: Message, cmd: Command, db_pool: Arc<DbPool>| is_authorized(&msg, &db_pool, cmd.min_role()))
+        // Administrative commands are recorded once their handler is done, see audit.rs
+        .chain(audit::audit_trail())
         //
         //BasicCommands
         //
@@ -132,6 +224,20 @@ fn schema() -> UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>>
                 }
             )
         ) 
+        .branch(
+            case![Command::Play].endpoint(
+                |bot: Bot, msg: Message, db_pool: Arc<DbPool>| async move {
+                    play_command(bot, msg, &db_pool).await
+                }
+            )
+        ) 
+        .branch(
+            case![Command::MyHand].endpoint(
+                |bot: Bot, msg: Message, db_pool: Arc<DbPool>| async move {
+                    myhand_command(bot, msg, &db_pool).await
+                }
+            )
+        ) 
         .branch(
             case![Command::PlayRock].endpoint(
                 |bot: Bot, msg: Message, db_pool: Arc<DbPool>| async move {
@@ -153,6 +259,76 @@ fn schema() -> UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>>
                 }
             )
         ) 
+        .branch(
+            case![Command::Commit(commitment)].endpoint(
+                |bot: Bot, msg: Message, db_pool: Arc<DbPool>, commitment: String| async move {
+                    commit_command(bot, msg, &db_pool, commitment).await
+                }
+            )
+        ) 
+        .branch(
+            case![Command::Reveal(args)].endpoint(
+                |bot: Bot, msg: Message, db_pool: Arc<DbPool>, args: String| async move {
+                    reveal_command(bot, msg, &db_pool, args).await
+                }
+            )
+        ) 
+        .branch(
+            case![Command::Bracket].endpoint(
+                |bot: Bot, msg: Message, db_pool: Arc<DbPool>| async move {
+                    bracket_command(bot, msg, &db_pool).await
+                }
+            )
+        ) 
+        .branch(
+            case![Command::Reminders(setting)].endpoint(
+                |bot: Bot, msg: Message, db_pool: Arc<DbPool>, setting: String| async move {
+                    reminders_command(bot, msg, &db_pool, setting).await
+                }
+            )
+        ) 
+        .branch(
+            case![Command::History(season)].endpoint(
+                |bot: Bot, msg: Message, db_pool: Arc<DbPool>, season: String| async move {
+                    history_command(bot, msg, &db_pool, season).await
+                }
+            )
+        ) 
+        .branch(
+            case![Command::Profile(username)].endpoint(
+                |bot: Bot, msg: Message, db_pool: Arc<DbPool>, username: String| async move {
+                    profile_command(bot, msg, &db_pool, username).await
+                }
+            )
+        ) 
+        .branch(
+            case![Command::Seasons].endpoint(
+                |bot: Bot, msg: Message, db_pool: Arc<DbPool>| async move {
+                    seasons_command(bot, msg, &db_pool).await
+                }
+            )
+        ) 
+        .branch(
+            case![Command::Leaderboard(season)].endpoint(
+                |bot: Bot, msg: Message, db_pool: Arc<DbPool>, season: String| async move {
+                    leaderboard_command(bot, msg, &db_pool, season).await
+                }
+            )
+        ) 
+        .branch(
+            case![Command::Rating(username)].endpoint(
+                |bot: Bot, msg: Message, db_pool: Arc<DbPool>, username: String| async move {
+                    rating_command(bot, msg, &db_pool, username).await
+                }
+            )
+        ) 
+        .branch(
+            case![Command::Ratings].endpoint(
+                |bot: Bot, msg: Message, db_pool: Arc<DbPool>| async move {
+                    ratings_command(bot, msg, &db_pool).await
+                }
+            )
+        ) 
         //
         //DevCommands
         //
@@ -240,6 +416,62 @@ fn schema() -> UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>>
                 }
             )
         )
+        .branch(
+            case![Command::AllowHandChange(setting)].endpoint(
+                |bot: Bot, msg: Message, db_pool: Arc<DbPool>, setting: String| async move {
+                    allow_hand_change_command(bot, msg, &db_pool, setting).await
+                }
+            )
+        )
+        .branch(
+            case![Command::CommitReveal(setting)].endpoint(
+                |bot: Bot, msg: Message, db_pool: Arc<DbPool>, setting: String| async move {
+                    commit_reveal_command(bot, msg, &db_pool, setting).await
+                }
+            )
+        )
+        .branch(
+            case![Command::SetScoring(rules)].endpoint(
+                |bot: Bot, msg: Message, db_pool: Arc<DbPool>, rules: String| async move {
+                    set_scoring_command(bot, msg, &db_pool, rules).await
+                }
+            )
+        )
+        .branch(
+            case![Command::SetHands(spec)].endpoint(
+                |bot: Bot, msg: Message, db_pool: Arc<DbPool>, spec: String| async move {
+                    set_hands_command(bot, msg, &db_pool, spec).await
+                }
+            )
+        )
+        .branch(
+            case![Command::ScheduleRounds(schedule_info)].endpoint(
+                |bot: Bot, msg: Message, db_pool: Arc<DbPool>, schedule_info: String| async move {
+                    schedule_rounds_command(bot, msg, &db_pool, schedule_info).await
+                }
+            )
+        )
+        .branch(
+            case![Command::PauseSchedule].endpoint(
+                |bot: Bot, msg: Message, db_pool: Arc<DbPool>| async move {
+                    pause_schedule_command(bot, msg, &db_pool).await
+                }
+            )
+        )
+        .branch(
+            case![Command::ResumeSchedule].endpoint(
+                |bot: Bot, msg: Message, db_pool: Arc<DbPool>| async move {
+                    resume_schedule_command(bot, msg, &db_pool).await
+                }
+            )
+        )
+        .branch(
+            case![Command::CancelSchedule].endpoint(
+                |bot: Bot, msg: Message, db_pool: Arc<DbPool>| async move {
+                    cancel_schedule_command(bot, msg, &db_pool).await
+                }
+            )
+        )
         .branch(
             case![Command::ApprovePlayer(username)].endpoint(
                 |bot: Bot, msg: Message, db_pool: Arc<DbPool>, username: String| async move {
@@ -343,23 +575,60 @@ fn schema() -> UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>>
             .endpoint(|bot: Bot, msg: Message, db_pool: Arc<DbPool>, username: String| async move {
                 remove_admin_command(bot, msg, &db_pool, username).await
             })
+        )
+        .branch(
+            case![Command::Grant(args)].endpoint(
+                |bot: Bot, msg: Message, db_pool: Arc<DbPool>, args: String| async move {
+                    grant_command(bot, msg, &db_pool, args).await
+                }
+            )
+        )
+        .branch(
+            case![Command::Revoke(args)].endpoint(
+                |bot: Bot, msg: Message, db_pool: Arc<DbPool>, args: String| async move {
+                    revoke_command(bot, msg, &db_pool, args).await
+                }
+            )
+        )
+        .branch(
+            case![Command::AuditLog(args)].endpoint(
+                |bot: Bot, msg: Message, db_pool: Arc<DbPool>, args: String| async move {
+                    audit_log_command(bot, msg, &db_pool, args).await
+                }
+            )
         );
 
     let message_handler = Update::filter_message()
         .branch(command_handler)
+        // Commands that didn't pass the role filter
+        .branch(teloxide::filter_command::<Command, _>().endpoint(
+            |bot: Bot, msg: Message, cmd: Command, db_pool: Arc<DbPool>| async move {
+                access::denied_command(bot, msg, cmd, &db_pool).await
+            }
+        ))
         .branch(dptree::endpoint(handle_invalid_text_message)) 
         ;
-    message_handler
+
+    // Inline keyboard button presses
+    let callback_handler = Update::filter_callback_query().endpoint(
+        |bot: Bot, q: CallbackQuery, db_pool: Arc<DbPool>| async move {
+            handle_callback_query(bot, q, &db_pool).await
+        }
+    );
+
+    dptree::entry()
+        .branch(message_handler)
+        .branch(callback_handler)
 
 }
 
 // When you don't receive a message that is a command (starts with /)
-async fn handle_invalid_text_message(_bot: Bot, msg: Message) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+async fn handle_invalid_text_message(bot: Bot, msg: Message) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     if let Some(username) = msg.from().and_then(|user| user.username.clone()) {
         log::info!("ChatId: {}, Date {} \nFrom: {} \nContent: {}", msg.chat.id, msg.date, username, msg.text().unwrap_or_default());
         //log::info!("{:?}",msg);
     }
-    //bot.send_message(msg.chat.id, "Received your message, this is not a valid command. Try /help.").await?;
+    access::unknown_message(&bot, &msg).await?;
     Ok(())
 }
 
@@ -0,0 +1,120 @@
+/// messages.rs
+
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+// Telegram refuses longer messages. The limit counts UTF-16 code units, not bytes.
+pub const MAX_MESSAGE_LENGTH: usize = 4096;
+
+// One page of an interactive listing
+pub struct Page {
+    pub text: String,
+    pub keyboard: Option<InlineKeyboardMarkup>,
+}
+
+fn message_length(text: &str) -> usize {
+    text.encode_utf16().count()
+}
+
+// Split a text into messages of at most `limit`, breaking between lines where possible.
+// Only a single line longer than the limit is cut in the middle.
+pub fn split_message(text: &str, limit: usize) -> Vec<String> {
+    let mut messages = Vec::new();
+    let mut current = String::new();
+
+    for line in text.split_inclusive('\n') {
+        if message_length(&current) + message_length(line) > limit && !current.is_empty() {
+            messages.push(std::mem::take(&mut current));
+        }
+        if message_length(line) <= limit {
+            current.push_str(line);
+            continue;
+        }
+        for c in line.chars() {
+            if message_length(&current) + c.len_utf16() > limit {
+                messages.push(std::mem::take(&mut current));
+            }
+            current.push(c);
+        }
+    }
+    if !current.trim().is_empty() {
+        messages.push(current);
+    }
+
+    messages
+}
+
+// Send a text that may be longer than one message as several messages
+pub async fn send_long_message(bot: &Bot, chat_id: ChatId, text: &str) -> Result<(), teloxide::RequestError> {
+    for message in split_message(text, MAX_MESSAGE_LENGTH) {
+        bot.send_message(chat_id, message).await?;
+    }
+    Ok(())
+}
+
+// Put lines on pages of at most `page_size` lines, each page fitting in a message together with the header
+pub fn paginate(header: &str, lines: &[String], page_size: usize) -> Vec<String> {
+    // Room for the "Page x/y" line
+    let limit = MAX_MESSAGE_LENGTH - 20;
+    let mut pages = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut length = message_length(header);
+
+    for line in lines {
+        let line_length = message_length(line) + 1;
+        if !current.is_empty() && (current.len() >= page_size || length + line_length > limit) {
+            pages.push(format!("{}{}\n", header, current.join("\n")));
+            current.clear();
+            length = message_length(header);
+        }
+        current.push(line);
+        length += line_length;
+    }
+    if !current.is_empty() || pages.is_empty() {
+        pages.push(format!("{}{}\n", header, current.join("\n")));
+    }
+
+    pages
+}
+
+// The requested page with its "Page x/y" line and previous/next buttons. `callback_data` gives the
+// data of the button that opens a page. Pages past the end show the last page.
+pub fn page(pages: &[String], page: usize, callback_data: impl Fn(usize) -> String) -> Page {
+    let count = pages.len().max(1);
+    let page = page.min(count - 1);
+    let mut text = pages.get(page).cloned().unwrap_or_default();
+    if count == 1 {
+        return Page { text, keyboard: None };
+    }
+    text.push_str(&format!("\nPage {}/{}", page + 1, count));
+
+    let mut buttons = Vec::new();
+    if page > 0 {
+        buttons.push(InlineKeyboardButton::callback("◀️ Previous", callback_data(page - 1)));
+    }
+    if page + 1 < count {
+        buttons.push(InlineKeyboardButton::callback("Next ▶️", callback_data(page + 1)));
+    }
+    Page { text, keyboard: Some(InlineKeyboardMarkup::new(vec![buttons])) }
+}
+
+// Send the first page of a listing
+pub async fn send_page(bot: &Bot, chat_id: ChatId, page: Page) -> Result<(), teloxide::RequestError> {
+    match page.keyboard {
+        Some(keyboard) => bot.send_message(chat_id, page.text).reply_markup(keyboard).await?,
+        None => bot.send_message(chat_id, page.text).await?,
+    };
+    Ok(())
+}
+
+// Show another page in place of the one a button was pressed on
+pub async fn edit_page(bot: &Bot, q: &CallbackQuery, page: Page) -> Result<(), teloxide::RequestError> {
+    if let Some(message) = &q.message {
+        let edit = bot.edit_message_text(message.chat.id, message.id, page.text);
+        match page.keyboard {
+            Some(keyboard) => edit.reply_markup(keyboard).await?,
+            None => edit.await?,
+        };
+    }
+    Ok(())
+}
@@ -0,0 +1,629 @@
+/// migrations.rs
+
+use rusqlite::{params, Connection, Transaction, Error as RusqliteError};
+
+// A single schema change. Migrations are applied in order, each one inside its own transaction.
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    pub up: fn(&Transaction) -> Result<(), RusqliteError>,
+}
+
+// Every schema change goes here, append only. Never edit a migration that has shipped.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial schema",
+        up: initial_schema,
+    },
+    Migration {
+        version: 2,
+        description: "player and opponent usernames in RoundDetailsTable",
+        up: round_details_usernames,
+    },
+    Migration {
+        version: 3,
+        description: "round deadlines and the round schedule",
+        up: round_schedule,
+    },
+    Migration {
+        version: 4,
+        description: "round reminders and player settings",
+        up: round_reminders,
+    },
+    Migration {
+        version: 5,
+        description: "hand changes",
+        up: hand_changes,
+    },
+    Migration {
+        version: 6,
+        description: "commit–reveal rounds",
+        up: hand_commitments,
+    },
+    Migration {
+        version: 7,
+        description: "pairing strategy per season",
+        up: season_pairing,
+    },
+    Migration {
+        version: 8,
+        description: "knockout seasons and brackets",
+        up: knockout_brackets,
+    },
+    Migration {
+        version: 9,
+        description: "best-of-N matches",
+        up: best_of_matches,
+    },
+    Migration {
+        version: 10,
+        description: "scoring rules per season",
+        up: season_scoring,
+    },
+    Migration {
+        version: 11,
+        description: "hand sets per season",
+        up: season_hand_set,
+    },
+    Migration {
+        version: 12,
+        description: "points per round",
+        up: round_points,
+    },
+    Migration {
+        version: 13,
+        description: "players across seasons",
+        up: players,
+    },
+    Migration {
+        version: 14,
+        description: "player ratings",
+        up: player_ratings,
+    },
+    Migration {
+        version: 15,
+        description: "administrators by user id",
+        up: administrators_by_id,
+    },
+    Migration {
+        version: 16,
+        description: "user roles",
+        up: user_roles,
+    },
+    Migration {
+        version: 17,
+        description: "audit log",
+        up: audit_log,
+    },
+    Migration {
+        version: 18,
+        description: "season winners",
+        up: season_winners,
+    },
+];
+
+// The newest schema this binary knows about
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+#[derive(Debug)]
+pub enum MigrationError {
+    DatabaseTooNew { database_version: i64, binary_version: i64 },
+    Failed { version: i64, description: &'static str, source: RusqliteError },
+    Database(RusqliteError),
+}
+
+impl std::fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MigrationError::DatabaseTooNew { database_version, binary_version } => write!(
+                f,
+                "the database schema is at version {} but this binary only knows up to version {}. Refusing to start, please upgrade the bot.",
+                database_version, binary_version
+            ),
+            MigrationError::Failed { version, description, source } => {
+                write!(f, "migration {} ({}) failed: {}", version, description, source)
+            }
+            MigrationError::Database(e) => write!(f, "database error while migrating: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+impl From<RusqliteError> for MigrationError {
+    fn from(e: RusqliteError) -> Self {
+        MigrationError::Database(e)
+    }
+}
+
+// Bring the database up to the latest schema version
+pub fn run_migrations(conn: &mut Connection) -> Result<(), MigrationError> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    let current: i64 = conn.query_row("SELECT IFNULL(MAX(version), 0) FROM schema_version", [], |row| row.get(0))?;
+    let latest = latest_version();
+
+    if current > latest {
+        return Err(MigrationError::DatabaseTooNew { database_version: current, binary_version: latest });
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        log::info!("Applying migration {}: {}", migration.version, migration.description);
+
+        let tx = conn.transaction()?;
+        let applied = (migration.up)(&tx).and_then(|_| {
+            tx.execute(
+                "INSERT INTO schema_version (version, description, applied_at) VALUES (?1, ?2, CURRENT_TIMESTAMP)",
+                params![migration.version, migration.description],
+            )
+        });
+
+        match applied {
+            Ok(_) => tx.commit()?,
+            Err(source) => {
+                // Dropping the transaction rolls it back
+                return Err(MigrationError::Failed { version: migration.version, description: migration.description, source });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Add a column unless it is already there. Older deployments created some tables
+// with columns that were later added to the CREATE TABLE statement.
+fn add_column_if_missing(tx: &Transaction, table: &str, column: &str, definition: &str) -> Result<(), RusqliteError> {
+    let mut stmt = tx.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<String>, RusqliteError>>()?
+        .iter()
+        .any(|name| name.eq_ignore_ascii_case(column));
+
+    if !exists {
+        tx.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+    }
+    Ok(())
+}
+
+//
+// Migration steps
+//
+
+// Version 1 is the schema as it existed before migrations. It uses IF NOT EXISTS
+// so that databases created by older binaries are adopted as-is.
+fn initial_schema(tx: &Transaction) -> Result<(), RusqliteError> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS my_table (
+            id INTEGER PRIMARY KEY,
+            value TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS administrators (
+            username TEXT PRIMARY KEY
+        )",
+        [],
+    )?;
+
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS seasons (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            is_active BOOLEAN NOT NULL,
+            max_players INTEGER NOT NULL,
+            start_date TEXT,
+            stop_date TEXT,
+            status TEXT NOT NULL DEFAULT 'initial'
+        )",
+        [],
+    )?;
+
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS channel_settings (
+            id INTEGER PRIMARY KEY,
+            broadcast_channel_id TEXT,
+            group_channel_id TEXT
+        )",
+        [],
+    )?;
+
+    // There is only ever one row of channel settings
+    tx.execute(
+        "INSERT INTO channel_settings (id, broadcast_channel_id, group_channel_id) VALUES (1, NULL, NULL) ON CONFLICT(id) DO NOTHING",
+        [],
+    )?;
+
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS MasterRoundTable (
+            id INTEGER PRIMARY KEY,
+            season_id INTEGER,
+            round_number INTEGER NOT NULL,
+            start_time TEXT,
+            end_time TEXT,
+            FOREIGN KEY(season_id) REFERENCES Seasons(id)
+        )",
+        [],
+    )?;
+
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS RoundDetailsTable (
+            id INTEGER PRIMARY KEY,
+            round_id INTEGER,
+            player_id INTEGER,
+            player_hand TEXT,
+            opponent INTEGER,
+            opponent_hand TEXT,
+            timestamp TEXT,
+            game_status TEXT,
+            FOREIGN KEY(round_id) REFERENCES MasterRoundTable(id)
+        )",
+        [],
+    )?;
+
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS PlayerDetailsTable (
+            id INTEGER PRIMARY KEY,
+            season_id INTEGER,
+            player_id INTEGER,
+            player_username TEXT,
+            player_wallet TEXT,
+            score INTEGER,
+            FOREIGN KEY(season_id) REFERENCES Seasons(id)
+        )",
+        [],
+    )?;
+
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS MasterCandidateTable (
+            id INTEGER PRIMARY KEY,
+            season_id INTEGER,
+            player_id INTEGER,
+            player_username TEXT,
+            player_wallet TEXT,
+            player_status TEXT,
+            FOREIGN KEY(season_id) REFERENCES Seasons(id)
+        )",
+        [],
+    )?;
+
+    Ok(())
+}
+
+// The usernames were added to RoundDetailsTable after the first deployments
+fn round_details_usernames(tx: &Transaction) -> Result<(), RusqliteError> {
+    add_column_if_missing(tx, "RoundDetailsTable", "player_username", "TEXT")?;
+    add_column_if_missing(tx, "RoundDetailsTable", "opponent_username", "TEXT")?;
+    Ok(())
+}
+
+// Deadlines let the scheduler close rounds, and survive restarts
+fn round_schedule(tx: &Transaction) -> Result<(), RusqliteError> {
+    add_column_if_missing(tx, "MasterRoundTable", "deadline", "TEXT")?;
+
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS round_schedule (
+            season_id INTEGER PRIMARY KEY,
+            rounds_remaining INTEGER NOT NULL,
+            round_duration_secs INTEGER NOT NULL,
+            gap_secs INTEGER NOT NULL,
+            next_start TEXT,
+            status TEXT NOT NULL,
+            FOREIGN KEY(season_id) REFERENCES Seasons(id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+// Which reminders went out for which round, and who doesn't want them
+fn round_reminders(tx: &Transaction) -> Result<(), RusqliteError> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS round_reminders (
+            round_id INTEGER NOT NULL,
+            offset_secs INTEGER NOT NULL,
+            sent_at TEXT NOT NULL,
+            PRIMARY KEY(round_id, offset_secs),
+            FOREIGN KEY(round_id) REFERENCES MasterRoundTable(id)
+        )",
+        [],
+    )?;
+
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS player_settings (
+            player_id INTEGER PRIMARY KEY,
+            reminders_enabled BOOLEAN NOT NULL DEFAULT 1
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+// Seasons can let players change their hand until the round closes. Every submission is kept.
+fn hand_changes(tx: &Transaction) -> Result<(), RusqliteError> {
+    add_column_if_missing(tx, "seasons", "allow_hand_change", "BOOLEAN NOT NULL DEFAULT 0")?;
+
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS hand_changes (
+            id INTEGER PRIMARY KEY,
+            round_id INTEGER NOT NULL,
+            player_id INTEGER NOT NULL,
+            player_hand TEXT NOT NULL,
+            timestamp TEXT NOT NULL,
+            FOREIGN KEY(round_id) REFERENCES MasterRoundTable(id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+// Commit–reveal seasons keep only a hash of each hand until the round closes
+fn hand_commitments(tx: &Transaction) -> Result<(), RusqliteError> {
+    add_column_if_missing(tx, "seasons", "commit_reveal", "BOOLEAN NOT NULL DEFAULT 0")?;
+    add_column_if_missing(tx, "MasterRoundTable", "reveal_deadline", "TEXT")?;
+
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS hand_commitments (
+            round_id INTEGER NOT NULL,
+            player_id INTEGER NOT NULL,
+            commitment TEXT NOT NULL,
+            committed_at TEXT NOT NULL,
+            revealed_hand TEXT,
+            nonce TEXT,
+            revealed_at TEXT,
+            PRIMARY KEY(round_id, player_id),
+            FOREIGN KEY(round_id) REFERENCES MasterRoundTable(id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+// Existing seasons keep the random pairing they always had
+fn season_pairing(tx: &Transaction) -> Result<(), RusqliteError> {
+    add_column_if_missing(tx, "seasons", "pairing", "TEXT NOT NULL DEFAULT 'random'")?;
+    Ok(())
+}
+
+// Seasons are leagues unless created as knockout tournaments
+fn knockout_brackets(tx: &Transaction) -> Result<(), RusqliteError> {
+    add_column_if_missing(tx, "seasons", "format", "TEXT NOT NULL DEFAULT 'league'")?;
+
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS bracket_matches (
+            id INTEGER PRIMARY KEY,
+            season_id INTEGER NOT NULL,
+            stage INTEGER NOT NULL,
+            position INTEGER NOT NULL,
+            player_one INTEGER,
+            player_two INTEGER,
+            winner INTEGER,
+            UNIQUE(season_id, stage, position),
+            FOREIGN KEY(season_id) REFERENCES Seasons(id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+// Best-of-N seasons pair players when the round starts and keep every throw of a match
+fn best_of_matches(tx: &Transaction) -> Result<(), RusqliteError> {
+    add_column_if_missing(tx, "seasons", "best_of", "INTEGER NOT NULL DEFAULT 1")?;
+
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS round_matches (
+            id INTEGER PRIMARY KEY,
+            round_id INTEGER NOT NULL,
+            player_one INTEGER NOT NULL,
+            player_two INTEGER,
+            winner INTEGER,
+            FOREIGN KEY(round_id) REFERENCES MasterRoundTable(id)
+        )",
+        [],
+    )?;
+
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS match_throws (
+            id INTEGER PRIMARY KEY,
+            match_id INTEGER NOT NULL,
+            throw_number INTEGER NOT NULL,
+            player_id INTEGER NOT NULL,
+            player_hand TEXT NOT NULL,
+            timestamp TEXT NOT NULL,
+            UNIQUE(match_id, throw_number, player_id),
+            FOREIGN KEY(match_id) REFERENCES round_matches(id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+// Seasons without a row here are scored with the configured defaults
+fn season_scoring(tx: &Transaction) -> Result<(), RusqliteError> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS season_scoring (
+            season_id INTEGER PRIMARY KEY,
+            win INTEGER NOT NULL,
+            draw INTEGER NOT NULL,
+            loss INTEGER NOT NULL,
+            forfeit INTEGER NOT NULL,
+            bye INTEGER NOT NULL,
+            no_play_penalty INTEGER NOT NULL,
+            streak_length INTEGER NOT NULL,
+            streak_bonus INTEGER NOT NULL,
+            FOREIGN KEY(season_id) REFERENCES Seasons(id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+// Existing seasons keep playing rock-paper-scissors
+fn season_hand_set(tx: &Transaction) -> Result<(), RusqliteError> {
+    add_column_if_missing(tx, "seasons", "hand_set", "TEXT NOT NULL DEFAULT 'rps'")?;
+    Ok(())
+}
+
+// The points a player got for a round, for /history. Rounds scored before this are NULL.
+fn round_points(tx: &Transaction) -> Result<(), RusqliteError> {
+    add_column_if_missing(tx, "RoundDetailsTable", "points", "INTEGER")?;
+    Ok(())
+}
+
+// One row per Telegram user, whatever seasons they signed up for. Existing players are filled in
+// from the signups, with the username of their latest season.
+fn players(tx: &Transaction) -> Result<(), RusqliteError> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS players (
+            player_id INTEGER PRIMARY KEY,
+            username TEXT NOT NULL,
+            first_seen TEXT NOT NULL,
+            last_seen TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    for table in ["MasterCandidateTable", "PlayerDetailsTable"] {
+        tx.execute(
+            &format!(
+                "INSERT OR IGNORE INTO players (player_id, username, first_seen, last_seen)
+                 SELECT t.player_id,
+                        COALESCE((SELECT latest.player_username FROM {table} latest WHERE latest.player_id = t.player_id ORDER BY latest.season_id DESC LIMIT 1), 'unknown'),
+                        COALESCE(MIN(s.start_date), CURRENT_TIMESTAMP),
+                        COALESCE(MAX(s.start_date), CURRENT_TIMESTAMP)
+                 FROM {table} t
+                 LEFT JOIN seasons s ON s.id = t.season_id
+                 GROUP BY t.player_id"
+            ),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+// ELO ratings. A NULL rating means the player hasn't had a rated match and starts at the configured rating.
+fn player_ratings(tx: &Transaction) -> Result<(), RusqliteError> {
+    add_column_if_missing(tx, "players", "rating", "REAL")?;
+    add_column_if_missing(tx, "players", "rated_matches", "INTEGER NOT NULL DEFAULT 0")?;
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS rating_history (
+            id INTEGER PRIMARY KEY,
+            player_id INTEGER NOT NULL,
+            round_id INTEGER NOT NULL,
+            opponent_id INTEGER NOT NULL,
+            result TEXT NOT NULL,
+            rating_before REAL NOT NULL,
+            rating_after REAL NOT NULL,
+            created_at TEXT NOT NULL,
+            FOREIGN KEY(player_id) REFERENCES players(player_id),
+            FOREIGN KEY(round_id) REFERENCES MasterRoundTable(id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+// Admins were keyed by username, which users can change or give up. The existing usernames stay
+// without a user id until that admin next messages the bot, see admin::claim_admin.
+fn administrators_by_id(tx: &Transaction) -> Result<(), RusqliteError> {
+    tx.execute(
+        "CREATE TABLE administrators_by_id (
+            id INTEGER PRIMARY KEY,
+            user_id INTEGER UNIQUE,
+            username TEXT,
+            added_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    tx.execute(
+        "INSERT INTO administrators_by_id (user_id, username, added_at) SELECT NULL, username, CURRENT_TIMESTAMP FROM administrators",
+        [],
+    )?;
+    tx.execute("DROP TABLE administrators", [])?;
+    tx.execute("ALTER TABLE administrators_by_id RENAME TO administrators", [])?;
+    Ok(())
+}
+
+// Admins become the admin role. Owners and devs are filled in from the configuration at every start,
+// see admin::sync_config_roles. A NULL season_id is a role in every season.
+fn user_roles(tx: &Transaction) -> Result<(), RusqliteError> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS user_roles (
+            id INTEGER PRIMARY KEY,
+            user_id INTEGER,
+            username TEXT,
+            role TEXT NOT NULL,
+            season_id INTEGER,
+            granted_by INTEGER,
+            granted_at TEXT NOT NULL,
+            from_config BOOLEAN NOT NULL DEFAULT 0,
+            FOREIGN KEY(season_id) REFERENCES Seasons(id)
+        )",
+        [],
+    )?;
+    tx.execute(
+        "INSERT INTO user_roles (user_id, username, role, season_id, granted_by, granted_at, from_config)
+         SELECT user_id, username, 'admin', NULL, NULL, added_at, 0 FROM administrators",
+        [],
+    )?;
+    tx.execute("DROP TABLE administrators", [])?;
+    Ok(())
+}
+
+// Every administrative action. The triggers refuse updates and deletes, which keeps the bot's own
+// statements append-only. Someone with the database file can still drop them.
+fn audit_log(tx: &Transaction) -> Result<(), RusqliteError> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY,
+            actor_id INTEGER,
+            actor_username TEXT,
+            command TEXT NOT NULL,
+            arguments TEXT,
+            target TEXT,
+            result TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    for (name, operation) in [("audit_log_no_update", "UPDATE"), ("audit_log_no_delete", "DELETE")] {
+        tx.execute(
+            &format!(
+                "CREATE TRIGGER IF NOT EXISTS {name} BEFORE {operation} ON audit_log
+                 BEGIN SELECT RAISE(ABORT, 'audit_log is append-only'); END"
+            ),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+// The winners of a closed season, stored when it closes so /seasons doesn't rank every past season.
+// Seasons closed before this have winners_recorded = 0 and are filled in the first time they are listed.
+fn season_winners(tx: &Transaction) -> Result<(), RusqliteError> {
+    add_column_if_missing(tx, "seasons", "winners_recorded", "BOOLEAN NOT NULL DEFAULT 0")?;
+
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS season_winners (
+            season_id INTEGER NOT NULL,
+            player_id INTEGER NOT NULL,
+            username TEXT NOT NULL,
+            PRIMARY KEY(season_id, player_id),
+            FOREIGN KEY(season_id) REFERENCES Seasons(id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
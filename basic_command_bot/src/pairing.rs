@@ -0,0 +1,273 @@
+/// pairing.rs
+
+use std::collections::{HashMap, HashSet};
+use rand::seq::SliceRandom;
+use rusqlite::{params, Error as RusqliteError};
+use crate::database::DbPool;
+
+// A player and the hand they played this round
+pub type PlayerHand = (i64, String);
+
+// The pairings for a round. With an odd number of players one of them is left over and gets a bye.
+pub struct Pairings {
+    pub pairs: Vec<(PlayerHand, PlayerHand)>,
+    pub unpaired: Option<PlayerHand>,
+}
+
+// What a strategy may look at besides the hands
+pub struct PairingContext {
+    pub round_number: i32,
+    // Current season score of each player
+    pub scores: HashMap<i64, i32>,
+    // Every pair of players that already met this season, smallest id first
+    pub past_opponents: HashSet<(i64, i64)>,
+    // How many byes each player had this season
+    pub byes: HashMap<i64, i32>,
+}
+
+impl PairingContext {
+    pub fn score(&self, player_id: i64) -> i32 {
+        self.scores.get(&player_id).copied().unwrap_or(0)
+    }
+
+    pub fn have_met(&self, a: i64, b: i64) -> bool {
+        self.past_opponents.contains(&(a.min(b), a.max(b)))
+    }
+
+    pub fn byes(&self, player_id: i64) -> i32 {
+        self.byes.get(&player_id).copied().unwrap_or(0)
+    }
+
+    // Nobody gets a second bye before everyone had one, so only the players
+    // with the fewest byes can get the next one
+    pub fn can_have_bye(&self, player_id: i64, players: &[PlayerHand]) -> bool {
+        let fewest = players.iter().map(|(id, _)| self.byes(*id)).min().unwrap_or(0);
+        self.byes(player_id) == fewest
+    }
+}
+
+pub trait PairingStrategy {
+    fn pair(&self, players: Vec<PlayerHand>, context: &PairingContext) -> Pairings;
+}
+
+// How a season pairs its players. The string form is what gets stored in `seasons.pairing`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PairingKind {
+    Random,
+    Swiss,
+    RoundRobin,
+}
+
+impl PairingKind {
+    pub const ALL: [PairingKind; 3] = [PairingKind::Random, PairingKind::Swiss, PairingKind::RoundRobin];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PairingKind::Random => "random",
+            PairingKind::Swiss => "swiss",
+            PairingKind::RoundRobin => "roundrobin",
+        }
+    }
+
+    pub fn strategy(&self) -> Box<dyn PairingStrategy + Send + Sync> {
+        match self {
+            PairingKind::Random => Box::new(RandomPairing),
+            PairingKind::Swiss => Box::new(SwissPairing),
+            PairingKind::RoundRobin => Box::new(RoundRobinPairing),
+        }
+    }
+}
+
+impl std::fmt::Display for PairingKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for PairingKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PairingKind::ALL
+            .iter()
+            .find(|kind| kind.as_str() == s)
+            .copied()
+            .ok_or_else(|| format!("Unknown pairing '{}', use one of random, swiss, roundrobin", s))
+    }
+}
+
+impl rusqlite::types::ToSql for PairingKind {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl rusqlite::types::FromSql for PairingKind {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: String| rusqlite::types::FromSqlError::Other(e.into()))
+    }
+}
+
+// Shuffle everyone and pair them in order
+pub struct RandomPairing;
+
+impl PairingStrategy for RandomPairing {
+    fn pair(&self, mut players: Vec<PlayerHand>, context: &PairingContext) -> Pairings {
+        players.shuffle(&mut rand::thread_rng());
+
+        // After the shuffle the first player allowed a bye is a random one
+        let unpaired = if !players.len().is_multiple_of(2) {
+            let bye_index = players
+                .iter()
+                .position(|(player_id, _)| context.can_have_bye(*player_id, &players))
+                .unwrap_or(0);
+            Some(players.remove(bye_index))
+        } else {
+            None
+        };
+
+        Pairings { pairs: pair_in_order(players), unpaired }
+    }
+}
+
+// Pair players with similar scores, avoiding rematches where possible.
+// With an odd number of players the lowest ranked one allowed a bye is left over.
+pub struct SwissPairing;
+
+impl PairingStrategy for SwissPairing {
+    fn pair(&self, mut players: Vec<PlayerHand>, context: &PairingContext) -> Pairings {
+        // Shuffle first so that players on the same score are ordered randomly
+        players.shuffle(&mut rand::thread_rng());
+        players.sort_by_key(|(player_id, _)| std::cmp::Reverse(context.score(*player_id)));
+
+        let unpaired = if !players.len().is_multiple_of(2) {
+            let bye_index = players
+                .iter()
+                .rposition(|(player_id, _)| context.can_have_bye(*player_id, &players))
+                .unwrap_or(players.len() - 1);
+            Some(players.remove(bye_index))
+        } else {
+            None
+        };
+
+        let mut pairs = Vec::new();
+        while !players.is_empty() {
+            let player = players.remove(0);
+            // The closest ranked player not met yet, or simply the next one if everyone was met
+            let opponent_index = players
+                .iter()
+                .position(|(opponent_id, _)| !context.have_met(player.0, *opponent_id))
+                .unwrap_or(0);
+            let opponent = players.remove(opponent_index);
+            pairs.push((player, opponent));
+        }
+
+        Pairings { pairs, unpaired }
+    }
+}
+
+// Everyone meets everyone once, using the circle method. After a full cycle it starts over.
+// With an odd number of players the circle gives everyone one bye per cycle.
+pub struct RoundRobinPairing;
+
+impl PairingStrategy for RoundRobinPairing {
+    fn pair(&self, mut players: Vec<PlayerHand>, context: &PairingContext) -> Pairings {
+        // A stable order, so that every round continues the same schedule
+        players.sort_by_key(|(player_id, _)| *player_id);
+
+        let pairings = circle_pairings(players.clone(), context.round_number);
+        match &pairings.unpaired {
+            // The roster changed mid-cycle and the circle picked someone who already had a bye.
+            // Take the bye away from the circle and pair the others.
+            Some((player_id, _)) if !context.can_have_bye(*player_id, &players) => {
+                let bye_index = players
+                    .iter()
+                    .position(|(player_id, _)| context.can_have_bye(*player_id, &players))
+                    .unwrap_or(0);
+                let unpaired = players.remove(bye_index);
+                Pairings { pairs: circle_pairings(players, context.round_number).pairs, unpaired: Some(unpaired) }
+            }
+            _ => pairings,
+        }
+    }
+}
+
+// One round of the circle method for the given players
+fn circle_pairings(players: Vec<PlayerHand>, round_number: i32) -> Pairings {
+    let mut slots: Vec<Option<PlayerHand>> = players.into_iter().map(Some).collect();
+    if !slots.len().is_multiple_of(2) {
+        slots.push(None);
+    }
+    if slots.len() < 2 {
+        return Pairings { pairs: Vec::new(), unpaired: slots.into_iter().flatten().next() };
+    }
+
+    // Keep the first slot fixed and rotate the rest once per round
+    let rotation = (round_number.max(1) - 1) as usize % (slots.len() - 1);
+    slots[1..].rotate_right(rotation);
+
+    let mut pairs = Vec::new();
+    let mut unpaired = None;
+    let last = slots.len() - 1;
+    for i in 0..slots.len() / 2 {
+        match (slots[i].take(), slots[last - i].take()) {
+            (Some(player), Some(opponent)) => pairs.push((player, opponent)),
+            (Some(player), None) | (None, Some(player)) => unpaired = Some(player),
+            (None, None) => {}
+        }
+    }
+
+    Pairings { pairs, unpaired }
+}
+
+fn pair_in_order(players: Vec<PlayerHand>) -> Vec<(PlayerHand, PlayerHand)> {
+    let mut pairs = Vec::new();
+    let mut players = players.into_iter();
+    while let (Some(player_one), Some(player_two)) = (players.next(), players.next()) {
+        pairs.push((player_one, player_two));
+    }
+    pairs
+}
+
+// Gather the scores and past pairings of a season for the round being closed
+pub async fn load_pairing_context(db_pool: &DbPool, season_id: i32, round_id: i32) -> Result<PairingContext, RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+
+    let round_number: i32 = conn.query_row(
+        "SELECT round_number FROM MasterRoundTable WHERE id = ?1",
+        params![round_id],
+        |row| row.get(0),
+    )?;
+
+    let mut stmt = conn.prepare("SELECT player_id, score FROM PlayerDetailsTable WHERE season_id = ?1")?;
+    let scores = stmt
+        .query_map(params![season_id], |row| Ok((row.get(0)?, row.get::<_, Option<i32>>(1)?.unwrap_or(0))))?
+        .collect::<Result<HashMap<i64, i32>, RusqliteError>>()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT rdt.player_id, rdt.opponent
+         FROM RoundDetailsTable rdt
+         JOIN MasterRoundTable mrt ON rdt.round_id = mrt.id
+         WHERE mrt.season_id = ?1 AND rdt.opponent IS NOT NULL AND rdt.opponent != rdt.player_id",
+    )?;
+    let past_opponents = stmt
+        .query_map(params![season_id], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))?
+        .map(|pair| pair.map(|(a, b)| (a.min(b), a.max(b))))
+        .collect::<Result<HashSet<(i64, i64)>, RusqliteError>>()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT rdt.player_id, COUNT(*)
+         FROM RoundDetailsTable rdt
+         JOIN MasterRoundTable mrt ON rdt.round_id = mrt.id
+         WHERE mrt.season_id = ?1 AND rdt.game_status = 'bye'
+         GROUP BY rdt.player_id",
+    )?;
+    let byes = stmt
+        .query_map(params![season_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<HashMap<i64, i32>, RusqliteError>>()?;
+
+    Ok(PairingContext { round_number, scores, past_opponents, byes })
+}
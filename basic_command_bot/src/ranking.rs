@@ -0,0 +1,194 @@
+/// ranking.rs
+
+use std::collections::HashMap;
+use serde::Deserialize;
+use rusqlite::{params, Error as RusqliteError};
+use crate::config;
+use crate::database::DbPool;
+
+// How players with the same score are told apart, applied in the order of `[ranking] tie_breakers`.
+// Players still equal after all of them share their place.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TieBreaker {
+    // Wins against the other players with the same score
+    HeadToHead,
+    Wins,
+    // The summed scores of everyone the player met, a hard schedule counts
+    Buchholz,
+    // Matches where the player didn't play a hand, fewer is better
+    FewestForfeits,
+}
+
+impl TieBreaker {
+    pub const ALL: [TieBreaker; 4] = [TieBreaker::HeadToHead, TieBreaker::Wins, TieBreaker::Buchholz, TieBreaker::FewestForfeits];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TieBreaker::HeadToHead => "head_to_head",
+            TieBreaker::Wins => "wins",
+            TieBreaker::Buchholz => "buchholz",
+            TieBreaker::FewestForfeits => "fewest_forfeits",
+        }
+    }
+
+    // Higher is better for every key, forfeits count negative
+    fn key(&self, standing: &Standing) -> i32 {
+        match self {
+            TieBreaker::HeadToHead => standing.head_to_head,
+            TieBreaker::Wins => standing.wins,
+            TieBreaker::Buchholz => standing.buchholz,
+            TieBreaker::FewestForfeits => -standing.forfeits,
+        }
+    }
+}
+
+impl std::fmt::Display for TieBreaker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for TieBreaker {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TieBreaker::ALL
+            .iter()
+            .find(|tie_breaker| tie_breaker.as_str() == s)
+            .copied()
+            .ok_or_else(|| format!("Unknown tie-breaker '{}', use any of head_to_head, wins, buchholz, fewest_forfeits", s))
+    }
+}
+
+// A player's place in a season. Tied players share the rank and the next rank is skipped (1, 2, 2, 4).
+pub struct Standing {
+    pub rank: usize,
+    pub player_id: i64,
+    pub username: String,
+    pub score: i32,
+    pub wins: i32,
+    pub head_to_head: i32,
+    pub buchholz: i32,
+    pub forfeits: i32,
+}
+
+// The standings of a season, ranked by score and then by the configured tie-breakers
+pub async fn season_standings(db_pool: &DbPool, season_id: i32) -> Result<Vec<Standing>, RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    let mut stmt = conn.prepare("SELECT player_id, player_username, score FROM PlayerDetailsTable WHERE season_id = ?1")?;
+    let mut standings = stmt
+        .query_map(params![season_id], |row| {
+            Ok(Standing {
+                rank: 0,
+                player_id: row.get(0)?,
+                username: row.get::<_, Option<String>>(1)?.unwrap_or_else(|| "unknown".to_string()),
+                score: row.get::<_, Option<i32>>(2)?.unwrap_or(0),
+                wins: 0,
+                head_to_head: 0,
+                buchholz: 0,
+                forfeits: 0,
+            })
+        })?
+        .collect::<Result<Vec<Standing>, RusqliteError>>()?;
+
+    // (player, opponent, result, played) of every scored match
+    let mut stmt = conn.prepare(
+        "SELECT rdt.player_id, rdt.opponent, rdt.game_status, COALESCE(rdt.player_hand, '') != ''
+         FROM RoundDetailsTable rdt
+         JOIN MasterRoundTable mrt ON rdt.round_id = mrt.id
+         WHERE mrt.season_id = ?1 AND rdt.game_status IS NOT NULL AND rdt.game_status != 'bye'",
+    )?;
+    let matches = stmt
+        .query_map(params![season_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+        .collect::<Result<Vec<(i64, Option<i64>, String, bool)>, RusqliteError>>()?;
+
+    let scores: HashMap<i64, i32> = standings.iter().map(|standing| (standing.player_id, standing.score)).collect();
+    for standing in standings.iter_mut() {
+        for (player_id, opponent, game_status, played) in matches.iter().filter(|m| m.0 == standing.player_id) {
+            if game_status == "won" {
+                standing.wins += 1;
+                if opponent.and_then(|opponent| scores.get(&opponent)) == Some(&scores[player_id]) {
+                    standing.head_to_head += 1;
+                }
+            }
+            if let Some(opponent_score) = opponent.and_then(|opponent| scores.get(&opponent)) {
+                standing.buchholz += opponent_score;
+            }
+            if !played {
+                standing.forfeits += 1;
+            }
+        }
+    }
+
+    rank(&mut standings, &config::get().ranking.tie_breakers);
+    Ok(standings)
+}
+
+// Sort best first and hand out competition ranks. Tied players are listed by username.
+pub fn rank(standings: &mut [Standing], tie_breakers: &[TieBreaker]) {
+    let keys = |standing: &Standing| -> Vec<i32> {
+        std::iter::once(standing.score).chain(tie_breakers.iter().map(|tie_breaker| tie_breaker.key(standing))).collect()
+    };
+    standings.sort_by(|a, b| keys(b).cmp(&keys(a)).then_with(|| a.username.cmp(&b.username)));
+
+    for index in 0..standings.len() {
+        standings[index].rank = if index > 0 && keys(&standings[index]) == keys(&standings[index - 1]) {
+            standings[index - 1].rank
+        } else {
+            index + 1
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn standing(username: &str, score: i32, wins: i32) -> Standing {
+        Standing {
+            rank: 0,
+            player_id: 0,
+            username: username.to_string(),
+            score,
+            wins,
+            head_to_head: 0,
+            buchholz: 0,
+            forfeits: 0,
+        }
+    }
+
+    fn ranks(standings: &[Standing]) -> Vec<(usize, &str)> {
+        standings.iter().map(|standing| (standing.rank, standing.username.as_str())).collect()
+    }
+
+    #[test]
+    fn tied_players_share_their_rank() {
+        let mut standings = vec![standing("dave", 1, 0), standing("carol", 3, 1), standing("bob", 3, 2), standing("alice", 5, 2)];
+        rank(&mut standings, &[]);
+        assert_eq!(ranks(&standings), vec![(1, "alice"), (2, "bob"), (2, "carol"), (4, "dave")]);
+    }
+
+    #[test]
+    fn tie_breakers_split_equal_scores() {
+        let mut standings = vec![standing("dave", 1, 0), standing("bob", 3, 1), standing("carol", 3, 2), standing("alice", 5, 2)];
+        rank(&mut standings, &[TieBreaker::Wins]);
+        assert_eq!(ranks(&standings), vec![(1, "alice"), (2, "carol"), (3, "bob"), (4, "dave")]);
+    }
+
+    #[test]
+    fn fewer_forfeits_rank_higher() {
+        let mut standings = vec![standing("alice", 3, 1), standing("bob", 3, 1)];
+        standings[0].forfeits = 2;
+        rank(&mut standings, &[TieBreaker::Wins, TieBreaker::FewestForfeits]);
+        assert_eq!(ranks(&standings), vec![(1, "bob"), (2, "alice")]);
+    }
+
+    #[test]
+    fn parses_tie_breakers() {
+        for tie_breaker in TieBreaker::ALL {
+            assert_eq!(tie_breaker.as_str().parse::<TieBreaker>(), Ok(tie_breaker));
+        }
+        assert!("coin_flip".parse::<TieBreaker>().is_err());
+    }
+}
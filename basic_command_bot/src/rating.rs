@@ -0,0 +1,131 @@
+/// rating.rs
+
+use rusqlite::{params, Connection, OptionalExtension, Error as RusqliteError};
+use crate::config;
+use crate::database::DbPool;
+
+// A player on the /ratings ladder
+pub struct RatedPlayer {
+    pub player_id: i64,
+    pub username: String,
+    pub rating: f64,
+    pub rated_matches: i32,
+}
+
+// One rating change, newest first when listed
+pub struct RatingChange {
+    pub opponent_username: String,
+    pub result: String,
+    pub rating_before: f64,
+    pub rating_after: f64,
+}
+
+// The chance of winning against the opponent, a draw counting as half a win
+pub fn expected_score(rating: f64, opponent_rating: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent_rating - rating) / 400.0))
+}
+
+// Players who haven't had a rated match yet start at the configured rating
+pub fn player_rating_on(conn: &Connection, player_id: i64) -> Result<f64, RusqliteError> {
+    let rating: Option<f64> = conn.query_row(
+        "SELECT rating FROM players WHERE player_id = ?1",
+        params![player_id],
+        |row| row.get(0),
+    ).optional()?.flatten();
+    Ok(rating.unwrap_or(config::get().rating.initial))
+}
+
+pub async fn player_rating(db_pool: &DbPool, player_id: i64) -> Result<f64, RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    player_rating_on(&conn, player_id)
+}
+
+// Update the ratings of both players of a match with the ELO formula and keep the history.
+// game_status is the result of player_id. Returns the new ratings.
+pub fn rate_match(conn: &Connection, round_id: i32, player_id: i64, opponent_id: i64, game_status: &str) -> Result<(f64, f64), RusqliteError> {
+    let score = match game_status {
+        "won" => 1.0,
+        "lost" => 0.0,
+        _ => 0.5,
+    };
+    let opponent_status = match game_status {
+        "won" => "lost",
+        "lost" => "won",
+        _ => "draw",
+    };
+
+    let rating = player_rating_on(conn, player_id)?;
+    let opponent_rating = player_rating_on(conn, opponent_id)?;
+    let k_factor = config::get().rating.k_factor;
+    let change = k_factor * (score - expected_score(rating, opponent_rating));
+
+    for (id, against, before, after, status) in [
+        (player_id, opponent_id, rating, rating + change, game_status),
+        (opponent_id, player_id, opponent_rating, opponent_rating - change, opponent_status),
+    ] {
+        // Players from before the players table may not have a row yet
+        conn.execute(
+            "INSERT OR IGNORE INTO players (player_id, username, first_seen, last_seen)
+             SELECT player_id, COALESCE(player_username, 'unknown'), CURRENT_TIMESTAMP, CURRENT_TIMESTAMP
+             FROM PlayerDetailsTable WHERE player_id = ?1 LIMIT 1",
+            params![id],
+        )?;
+        conn.execute(
+            "UPDATE players SET rating = ?2, rated_matches = rated_matches + 1 WHERE player_id = ?1",
+            params![id, after],
+        )?;
+        conn.execute(
+            "INSERT INTO rating_history (player_id, round_id, opponent_id, result, rating_before, rating_after, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, CURRENT_TIMESTAMP)",
+            params![id, round_id, against, status, before, after],
+        )?;
+    }
+
+    Ok((rating + change, opponent_rating - change))
+}
+
+// The rated players, best first
+pub async fn rating_ladder(db_pool: &DbPool) -> Result<Vec<RatedPlayer>, RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    let mut stmt = conn.prepare(
+        "SELECT player_id, username, rating, rated_matches FROM players
+         WHERE rating IS NOT NULL AND rated_matches > 0
+         ORDER BY rating DESC, username",
+    )?;
+    let ladder = stmt
+        .query_map([], |row| {
+            Ok(RatedPlayer {
+                player_id: row.get(0)?,
+                username: row.get(1)?,
+                rating: row.get(2)?,
+                rated_matches: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<RatedPlayer>, RusqliteError>>()?;
+
+    Ok(ladder)
+}
+
+pub async fn rating_changes(db_pool: &DbPool, player_id: i64, limit: i64) -> Result<Vec<RatingChange>, RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    let mut stmt = conn.prepare(
+        "SELECT COALESCE(p.username, 'unknown'), rh.result, rh.rating_before, rh.rating_after
+         FROM rating_history rh
+         LEFT JOIN players p ON p.player_id = rh.opponent_id
+         WHERE rh.player_id = ?1
+         ORDER BY rh.id DESC
+         LIMIT ?2",
+    )?;
+    let changes = stmt
+        .query_map(params![player_id, limit], |row| {
+            Ok(RatingChange {
+                opponent_username: row.get(0)?,
+                result: row.get(1)?,
+                rating_before: row.get(2)?,
+                rating_after: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<RatingChange>, RusqliteError>>()?;
+
+    Ok(changes)
+}
@@ -0,0 +1,143 @@
+/// scheduler.rs
+
+use teloxide::{prelude::*};
+use std::{error::Error, sync::Arc, time::Duration};
+use crate::database::{DbPool};
+use crate::enums::SeasonPhase;
+
+use crate::commands::season::{
+    current_active_season_details,
+    get_next_round_number,
+    start_new_round,
+    season_uses_commit_reveal,
+};
+
+use crate::commands::playing_commands::{
+    close_current_round,
+    notify_empty_hands,
+    get_current_round_id,
+};
+
+use crate::commands::schedule_commands::{
+    scheduled_round_due,
+    mark_scheduled_round_started,
+    get_expired_round_id,
+    get_round_deadline,
+    format_duration,
+};
+
+use crate::commands::grp_broadcast_commands::get_group_broadcast_chat_ids;
+use crate::commands::reminder_commands::send_due_reminders;
+use crate::messages::send_long_message;
+use crate::commands::series_commands::start_series_matches;
+use crate::commands::commit_reveal_commands::{
+    open_reveal_window,
+    get_expired_reveal_round_id,
+};
+
+// How often the scheduler looks at the database. Deadlines are at most this late.
+const TICK_SECS: u64 = 15;
+
+// Runs forever, starting scheduled rounds, reminding players before a deadline
+// and closing rounds (or their reveal window) whose deadline has passed.
+// All state lives in the database, so a restart just picks up where it left off.
+pub async fn run(bot: Bot, db_pool: Arc<DbPool>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(TICK_SECS));
+    loop {
+        interval.tick().await;
+        if let Err(e) = tick(&bot, &db_pool).await {
+            log::error!("Scheduler tick failed: {}", e);
+        }
+    }
+}
+
+async fn tick(bot: &Bot, db_pool: &DbPool) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (season_id, phase) = match current_active_season_details(db_pool).await? {
+        Some((season_id, _, _, _, phase)) => (season_id, phase),
+        None => return Ok(()),
+    };
+
+    match phase {
+        SeasonPhase::RoundOngoing => {
+            if get_expired_round_id(db_pool, season_id).await?.is_some() {
+                if season_uses_commit_reveal(db_pool, season_id).await? {
+                    let announcement = open_reveal_window(bot, db_pool, season_id).await?;
+                    announce(bot, db_pool, &format!("Time is up! {}", announcement)).await;
+                } else {
+                    close_expired_round(bot, db_pool, season_id).await?;
+                }
+            } else if let Some(round_id) = get_current_round_id(db_pool, season_id).await? {
+                send_due_reminders(bot, db_pool, season_id, round_id).await?;
+            }
+        }
+        SeasonPhase::RoundRevealing if get_expired_reveal_round_id(db_pool, season_id).await?.is_some() => {
+            close_expired_round(bot, db_pool, season_id).await?;
+        }
+        SeasonPhase::StartGaming => {
+            if let Some(schedule) = scheduled_round_due(db_pool, season_id).await? {
+                start_scheduled_round(bot, db_pool, season_id, schedule.round_duration_secs, schedule.rounds_remaining - 1).await?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+async fn close_expired_round(bot: &Bot, db_pool: &DbPool, season_id: i32) -> Result<(), Box<dyn Error + Send + Sync>> {
+    log::info!("Round deadline passed for season {}, closing the round", season_id);
+    let closed_round = match close_current_round(db_pool, season_id).await? {
+        Some(closed_round) => closed_round,
+        None => {
+            log::info!("The round of season {} was already closed", season_id);
+            return Ok(());
+        }
+    };
+
+    notify_empty_hands(bot, &closed_round.players_without_moves).await;
+
+    let mut message = String::from("Time is up! Round is over, everyone back to their corner!");
+    if !closed_round.announcement.is_empty() {
+        message.push_str("\n\n");
+        message.push_str(&closed_round.announcement);
+    }
+    announce(bot, db_pool, &message).await;
+
+    Ok(())
+}
+
+async fn start_scheduled_round(bot: &Bot, db_pool: &DbPool, season_id: i32, duration_secs: i64, rounds_left_after: i64) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let next_round_number = get_next_round_number(db_pool, &season_id).await?;
+    start_new_round(db_pool, season_id, next_round_number, Some(duration_secs)).await?;
+    mark_scheduled_round_started(db_pool, season_id).await?;
+    log::info!("Scheduler started round {} of season {}", next_round_number, season_id);
+    start_series_matches(bot, db_pool, season_id).await?;
+
+    let deadline = match get_current_round_id(db_pool, season_id).await? {
+        Some(round_id) => get_round_deadline(db_pool, round_id).await?,
+        None => None,
+    };
+    let message = format!(
+        "Round {} has started! You have {} to play your hand{}. {} more scheduled after this one.",
+        next_round_number,
+        format_duration(duration_secs),
+        deadline.map(|d| format!(" (closes at {} UTC)", d)).unwrap_or_default(),
+        rounds_left_after
+    );
+    announce(bot, db_pool, &message).await;
+
+    Ok(())
+}
+
+// Post to the group channel. Without one the message only goes to the log.
+async fn announce(bot: &Bot, db_pool: &DbPool, message: &str) {
+    match get_group_broadcast_chat_ids(db_pool).await {
+        Ok((_, Some(group_chat_id))) => {
+            if let Err(e) = send_long_message(bot, group_chat_id, message).await {
+                log::error!("Failed to send scheduler announcement: {}", e);
+            }
+        }
+        Ok((_, None)) => log::info!("No group channel set, scheduler announcement: {}", message),
+        Err(e) => log::error!("Failed to get the group channel: {}", e),
+    }
+}
@@ -0,0 +1,252 @@
+/// scoring.rs
+
+use rusqlite::{params, Connection, OptionalExtension, Error as RusqliteError};
+use crate::config::{self, ScoringConfig};
+use crate::database::DbPool;
+
+// How a season scores its matches. New seasons copy the [scoring] defaults of the config,
+// admins change them with /setscoring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScoringRules {
+    pub win: i32,
+    pub draw: i32,
+    pub loss: i32,
+    // Points for winning because the opponent didn't play
+    pub forfeit: i32,
+    // Points for sitting a round out when the number of players is odd
+    pub bye: i32,
+    // Taken off the loss points of a player who didn't play
+    pub no_play_penalty: i32,
+    // Every win that makes a streak of at least streak_length wins is worth streak_bonus extra.
+    // A bonus of 0 turns streaks off.
+    pub streak_length: i32,
+    pub streak_bonus: i32,
+}
+
+impl From<&ScoringConfig> for ScoringRules {
+    fn from(scoring: &ScoringConfig) -> Self {
+        ScoringRules {
+            win: scoring.win,
+            draw: scoring.draw,
+            loss: scoring.loss,
+            forfeit: scoring.forfeit,
+            bye: scoring.bye,
+            no_play_penalty: scoring.no_play_penalty,
+            streak_length: scoring.streak_length,
+            streak_bonus: scoring.streak_bonus,
+        }
+    }
+}
+
+impl ScoringRules {
+    pub const USAGE: &'static str = "win=2 draw=1 loss=0 forfeit=2 bye=2 no_play_penalty=0 streak_length=3 streak_bonus=0";
+
+    // Change the rules given as key=value, the others stay as they are
+    pub fn parse(&self, options: &[&str]) -> Result<ScoringRules, String> {
+        let mut rules = *self;
+        for option in options {
+            let (key, value) = option
+                .split_once('=')
+                .ok_or_else(|| format!("Rules look like key=value, got '{}'", option))?;
+            let value: i32 = value.parse().map_err(|_| format!("'{}' is not a number", value))?;
+            match key.to_lowercase().as_str() {
+                "win" => rules.win = value,
+                "draw" => rules.draw = value,
+                "loss" => rules.loss = value,
+                "forfeit" => rules.forfeit = value,
+                "bye" => rules.bye = value,
+                "no_play_penalty" => rules.no_play_penalty = value,
+                "streak_length" => rules.streak_length = value,
+                "streak_bonus" => rules.streak_bonus = value,
+                _ => return Err(format!("Unknown rule '{}'", key)),
+            }
+        }
+        rules.validate()?;
+        Ok(rules)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.win < self.draw || self.draw < self.loss {
+            return Err(format!("win >= draw >= loss is required, got win={} draw={} loss={}", self.win, self.draw, self.loss));
+        }
+        if self.bye < self.loss || self.bye > self.win {
+            return Err(format!("bye must be between loss and win, got bye={}", self.bye));
+        }
+        if self.forfeit < self.loss {
+            return Err(format!("forfeit can't be less than loss, got forfeit={}", self.forfeit));
+        }
+        if self.no_play_penalty < 0 || self.streak_bonus < 0 {
+            return Err("no_play_penalty and streak_bonus can't be negative".to_string());
+        }
+        if self.streak_length < 2 {
+            return Err(format!("a streak is at least 2 wins, got streak_length={}", self.streak_length));
+        }
+        Ok(())
+    }
+
+    // Points for one side of a match. A player who didn't play gets the loss points minus the penalty,
+    // whatever the result, a bye included, and their opponent wins by forfeit.
+    pub fn points(&self, game_status: &str, played: bool, opponent_played: bool) -> i32 {
+        match (game_status, played, opponent_played) {
+            ("bye", true, _) => self.bye,
+            (_, false, _) => self.loss - self.no_play_penalty,
+            (_, true, false) => self.forfeit,
+            ("won", true, true) => self.win,
+            ("draw", true, true) => self.draw,
+            _ => self.loss,
+        }
+    }
+
+    // Extra points for a win that makes a streak of `streak` wins
+    pub fn streak_points(&self, streak: i32) -> i32 {
+        if streak >= self.streak_length { self.streak_bonus } else { 0 }
+    }
+}
+
+impl std::fmt::Display for ScoringRules {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "win {}, draw {}, loss {}, win by forfeit {}, bye {}, not playing -{}",
+            self.win, self.draw, self.loss, self.forfeit, self.bye, self.no_play_penalty
+        )?;
+        if self.streak_bonus > 0 {
+            write!(f, ", +{} per win from {} wins in a row", self.streak_bonus, self.streak_length)
+        } else {
+            write!(f, ", no streak bonus")
+        }
+    }
+}
+
+// The rules of a season. Seasons from before per-season rules use the configured defaults.
+pub async fn season_scoring_rules(db_pool: &DbPool, season_id: i32) -> Result<ScoringRules, RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    let rules = conn.query_row(
+        "SELECT win, draw, loss, forfeit, bye, no_play_penalty, streak_length, streak_bonus FROM season_scoring WHERE season_id = ?1",
+        params![season_id],
+        |row| {
+            Ok(ScoringRules {
+                win: row.get(0)?,
+                draw: row.get(1)?,
+                loss: row.get(2)?,
+                forfeit: row.get(3)?,
+                bye: row.get(4)?,
+                no_play_penalty: row.get(5)?,
+                streak_length: row.get(6)?,
+                streak_bonus: row.get(7)?,
+            })
+        },
+    ).optional()?;
+
+    Ok(rules.unwrap_or_else(|| ScoringRules::from(&config::get().scoring)))
+}
+
+pub async fn set_season_scoring_rules(db_pool: &DbPool, season_id: i32, rules: &ScoringRules) -> Result<(), RusqliteError> {
+    let conn = db_pool.get().expect("Failed to get DB connection");
+    set_season_scoring_rules_on(&conn, season_id, rules)
+}
+
+pub fn set_season_scoring_rules_on(conn: &Connection, season_id: i32, rules: &ScoringRules) -> Result<(), RusqliteError> {
+    conn.execute(
+        "INSERT INTO season_scoring (season_id, win, draw, loss, forfeit, bye, no_play_penalty, streak_length, streak_bonus)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+         ON CONFLICT(season_id) DO UPDATE SET
+             win = excluded.win, draw = excluded.draw, loss = excluded.loss, forfeit = excluded.forfeit, bye = excluded.bye,
+             no_play_penalty = excluded.no_play_penalty, streak_length = excluded.streak_length, streak_bonus = excluded.streak_bonus",
+        params![
+            season_id,
+            rules.win,
+            rules.draw,
+            rules.loss,
+            rules.forfeit,
+            rules.bye,
+            rules.no_play_penalty,
+            rules.streak_length,
+            rules.streak_bonus
+        ],
+    )?;
+    Ok(())
+}
+
+// How many matches in a row a player won this season before the given round. Byes don't break a streak.
+pub fn win_streak(conn: &Connection, season_id: i32, player_id: i64, round_id: i32) -> Result<i32, RusqliteError> {
+    let mut stmt = conn.prepare(
+        "SELECT rdt.game_status
+         FROM RoundDetailsTable rdt
+         JOIN MasterRoundTable mrt ON rdt.round_id = mrt.id
+         WHERE mrt.season_id = ?1 AND rdt.player_id = ?2 AND rdt.round_id != ?3
+           AND rdt.game_status IS NOT NULL AND rdt.game_status != 'bye'
+         ORDER BY mrt.round_number DESC",
+    )?;
+    let statuses = stmt
+        .query_map(params![season_id, player_id, round_id], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<String>, RusqliteError>>()?;
+
+    Ok(statuses.iter().take_while(|status| status.as_str() == "won").count() as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RULES: ScoringRules = ScoringRules {
+        win: 3,
+        draw: 1,
+        loss: 0,
+        forfeit: 2,
+        bye: 2,
+        no_play_penalty: 1,
+        streak_length: 3,
+        streak_bonus: 0,
+    };
+
+    #[test]
+    fn parse_changes_only_the_given_rules() {
+        let rules = RULES.parse(&["WIN=4", "streak_bonus=1"]).unwrap();
+        assert_eq!(rules, ScoringRules { win: 4, streak_bonus: 1, ..RULES });
+        assert_eq!(RULES.parse(&[]), Ok(RULES));
+    }
+
+    #[test]
+    fn parse_rejects_bad_rules() {
+        assert!(RULES.parse(&["win"]).is_err());
+        assert!(RULES.parse(&["win=lots"]).is_err());
+        assert!(RULES.parse(&["jackpot=5"]).is_err());
+        assert!(RULES.parse(&["draw=5"]).is_err());
+        assert!(RULES.parse(&["bye=5"]).is_err());
+        assert!(RULES.parse(&["forfeit=-1"]).is_err());
+        assert!(RULES.parse(&["no_play_penalty=-1"]).is_err());
+        assert!(RULES.parse(&["streak_length=1"]).is_err());
+    }
+
+    #[test]
+    fn points_when_both_played() {
+        assert_eq!(RULES.points("won", true, true), 3);
+        assert_eq!(RULES.points("draw", true, true), 1);
+        assert_eq!(RULES.points("lost", true, true), 0);
+    }
+
+    #[test]
+    fn points_when_someone_didnt_play() {
+        // The opponent didn't play: a win by forfeit, whatever the result says
+        assert_eq!(RULES.points("won", true, false), 2);
+        assert_eq!(RULES.points("draw", true, false), 2);
+        // Not playing is a loss minus the penalty, even when neither played
+        assert_eq!(RULES.points("lost", false, true), -1);
+        assert_eq!(RULES.points("draw", false, false), -1);
+    }
+
+    #[test]
+    fn bye_needs_a_hand_unless_there_was_nothing_to_play() {
+        assert_eq!(RULES.points("bye", true, false), 2);
+        assert_eq!(RULES.points("bye", false, false), -1);
+    }
+
+    #[test]
+    fn streak_bonus_from_streak_length() {
+        let rules = ScoringRules { streak_bonus: 2, ..RULES };
+        assert_eq!(rules.streak_points(2), 0);
+        assert_eq!(rules.streak_points(3), 2);
+        assert_eq!(RULES.streak_points(5), 0);
+    }
+}